@@ -0,0 +1,94 @@
+//! Premultiplying a 2048x2048 texture on the CPU against the compute
+//! shader, used to pick the default [PremultiplyMode].
+//!
+//! The CPU timing excludes the texture upload, the compute timing
+//! excludes the upload of the source texture and waits for the GPU to
+//! finish the pass. The compute benchmark requires a D3D11 device, WARP
+//! is used when there is no GPU (results are then not representative)
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use nalgebra::Vector2;
+use vtftk_spout::{
+    com::ComPtr,
+    dx::{
+        device::{create_device_and_context, DEFAULT_FEATURE_LEVELS},
+        shader::ShaderResourceView,
+        texture::Texture,
+    },
+    premultiply::{Premultiply, PremultiplyMode},
+    texture_loader::{premultiply_alpha, GradientDirection, TextureData},
+};
+use winapi::{
+    shared::winerror::S_FALSE,
+    um::d3d11::{
+        ID3D11Device, ID3D11DeviceContext, ID3D11Query, D3D11_QUERY_DESC, D3D11_QUERY_EVENT,
+    },
+};
+
+const TEXTURE_SIZE: u32 = 2048;
+
+/// Texture with a range of colors and alphas to premultiply
+fn texture_data() -> TextureData {
+    TextureData::linear_gradient(
+        TEXTURE_SIZE,
+        TEXTURE_SIZE,
+        [255, 128, 0, 0],
+        [0, 128, 255, 255],
+        GradientDirection::Horizontal,
+    )
+}
+
+/// Blocks until the GPU has finished the work submitted so far
+fn wait_for_gpu(device: &ID3D11Device, ctx: &ID3D11DeviceContext) {
+    let desc = D3D11_QUERY_DESC {
+        Query: D3D11_QUERY_EVENT,
+        MiscFlags: 0,
+    };
+
+    unsafe {
+        let mut query: *mut ID3D11Query = std::ptr::null_mut();
+        let hr = device.CreateQuery(&desc, &mut query);
+        assert!(hr >= 0, "failed to create event query: HRESULT {hr:#010x}");
+        let mut query: ComPtr<ID3D11Query> = query.into();
+
+        ctx.End(query.cast_as_mut());
+        while ctx.GetData(query.cast_as_mut(), std::ptr::null_mut(), 0, 0) == S_FALSE {
+            std::hint::spin_loop();
+        }
+    }
+}
+
+fn premultiply(c: &mut Criterion) {
+    let mut group = c.benchmark_group(format!("premultiply_{TEXTURE_SIZE}"));
+    group.throughput(Throughput::Elements(
+        TEXTURE_SIZE as u64 * TEXTURE_SIZE as u64,
+    ));
+
+    group.bench_function(format!("{:?}", PremultiplyMode::Cpu), |b| {
+        b.iter_batched(texture_data, premultiply_alpha, BatchSize::LargeInput)
+    });
+
+    let data = texture_data();
+    let (device, ctx, _) = create_device_and_context(false, &DEFAULT_FEATURE_LEVELS).unwrap();
+    let mut premultiply = Premultiply::create(&device).unwrap();
+    let mut source =
+        Texture::create_from_data(&device, data.width, data.height, &data.buffer).unwrap();
+    let mut source_srv =
+        ShaderResourceView::create_from_texture(&device, source.texture.cast_as_mut()).unwrap();
+    let size = Vector2::new(data.width, data.height);
+
+    group.bench_function(format!("{:?}", PremultiplyMode::Compute), |b| {
+        b.iter(|| {
+            let output = premultiply
+                .apply(&device, &ctx, &mut source_srv, size)
+                .unwrap();
+            wait_for_gpu(&device, &ctx);
+            output
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, premultiply);
+criterion_main!(benches);
@@ -0,0 +1,148 @@
+//! Render loop throughput, renders frames on a headless renderer with a
+//! number of items alive and reports frames per second as the throughput.
+//! The `render_loop_stages` group reports the time of each stage of the
+//! frame on its own, using the [FrameStats] recorded by the renderer for
+//! the CPU stages and timing the readback for the GPU.
+//!
+//! Requires a D3D11 device, the WARP software rasterizer is used when
+//! there is no GPU (results are then not representative of hardware).
+//! Each frame is read back so the GPU work is included in the timing.
+//! Headless renderers don't send frames, the send stage only measures
+//! the Spout sender when rendering with [Renderer::new]
+
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use nalgebra::Vector2;
+use vtftk_spout::{
+    item::{ItemId, ItemTransform, QueuedItemDefinition},
+    renderer::FrameStats,
+    ItemDefinition, Renderer, RendererConfig,
+};
+
+/// Numbers of items alive while rendering
+const ITEM_COUNTS: [usize; 3] = [1, 64, 256];
+
+/// Items are laid out in a grid of this many columns and rows
+const GRID_SIZE: usize = 16;
+
+/// Stages of a frame timed by the renderer
+const STAGES: [(&str, fn(&FrameStats) -> Duration); 3] = [
+    ("update", |stats| stats.update),
+    ("draw", |stats| stats.draw),
+    ("send", |stats| stats.send),
+];
+
+/// Creates a headless renderer with `item_count` items spawned, must be
+/// called from within the tokio runtime
+fn create_renderer(runtime: &tokio::runtime::Runtime, item_count: usize) -> Renderer {
+    let config = RendererConfig {
+        screen_size: Vector2::new(1280, 720),
+        font_path: None,
+        max_spawns_per_frame: item_count,
+        max_items: None,
+        spawn_seed: Some(0),
+        target_fps: None,
+        idle_timeout: None,
+        ..Default::default()
+    };
+    let mut renderer = Renderer::headless(config).unwrap();
+
+    for index in 0..item_count {
+        let cell = Vector2::new(index % GRID_SIZE, index / GRID_SIZE % GRID_SIZE);
+        let definition = ItemDefinition::builder()
+            .solid_color(64, 64, [255, 128, 0, 255])
+            .static_transform(ItemTransform {
+                position: (cell.cast::<f32>() + Vector2::repeat(0.5)) / GRID_SIZE as f32,
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+
+        let item = runtime.block_on(QueuedItemDefinition::load_or_placeholder(
+            ItemId::random(),
+            definition,
+        ));
+        renderer.item_queue().push(item);
+    }
+
+    // Spawn every item before measuring
+    renderer.tick().unwrap();
+
+    renderer
+}
+
+fn render_loop(c: &mut Criterion) {
+    // Item loading and the renderer require a tokio runtime
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let _runtime_guard = runtime.enter();
+
+    let mut group = c.benchmark_group("render_loop");
+    group.throughput(Throughput::Elements(1));
+
+    for item_count in ITEM_COUNTS {
+        let mut renderer = create_renderer(&runtime, item_count);
+
+        group.bench_with_input(
+            BenchmarkId::from_parameter(item_count),
+            &item_count,
+            |b, _| {
+                b.iter(|| {
+                    renderer.tick().unwrap();
+                    // Waits for the GPU to finish the frame
+                    renderer.read_frame().unwrap()
+                })
+            },
+        );
+
+        renderer.shutdown();
+    }
+
+    group.finish();
+}
+
+fn render_loop_stages(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let _runtime_guard = runtime.enter();
+
+    let mut group = c.benchmark_group("render_loop_stages");
+
+    for item_count in ITEM_COUNTS {
+        let mut renderer = create_renderer(&runtime, item_count);
+
+        for (stage, stage_time) in STAGES {
+            group.bench_with_input(BenchmarkId::new(stage, item_count), &item_count, |b, _| {
+                b.iter_custom(|iters| {
+                    let mut total = Duration::ZERO;
+                    for _ in 0..iters {
+                        renderer.tick().unwrap();
+                        total += stage_time(&renderer.frame_stats());
+                        renderer.read_frame().unwrap();
+                    }
+                    total
+                })
+            });
+        }
+
+        // Waiting for the GPU to finish the frame and copying it back
+        group.bench_with_input(BenchmarkId::new("gpu", item_count), &item_count, |b, _| {
+            b.iter_custom(|iters| {
+                let mut total = Duration::ZERO;
+                for _ in 0..iters {
+                    renderer.tick().unwrap();
+                    let start = Instant::now();
+                    renderer.read_frame().unwrap();
+                    total += start.elapsed();
+                }
+                total
+            })
+        });
+
+        renderer.shutdown();
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, render_loop, render_loop_stages);
+criterion_main!(benches);
@@ -0,0 +1,70 @@
+//! Creating 100 same sized textures one at a time against uploading them
+//! as the layers of a single [TextureArray].
+//!
+//! Both timings include the upload of the initial data, the texture data
+//! is generated once outside of the timing. Requires a D3D11 device, WARP
+//! is used when there is no GPU (results are then not representative)
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use vtftk_spout::{
+    dx::{
+        device::{create_device_and_context, DEFAULT_FEATURE_LEVELS},
+        texture::{Texture, TextureArray},
+    },
+    texture_loader::{GradientDirection, TextureData},
+};
+
+const TEXTURE_COUNT: usize = 100;
+
+const TEXTURE_SIZE: u32 = 256;
+
+/// Textures with a different gradient each so no two uploads are equal
+fn textures() -> Vec<TextureData> {
+    (0..TEXTURE_COUNT)
+        .map(|index| {
+            let shade = (index * 255 / TEXTURE_COUNT) as u8;
+            TextureData::linear_gradient(
+                TEXTURE_SIZE,
+                TEXTURE_SIZE,
+                [shade, 0, 255 - shade, 255],
+                [0, shade, 255, 255],
+                GradientDirection::Horizontal,
+            )
+        })
+        .collect()
+}
+
+fn texture_array(c: &mut Criterion) {
+    let mut group = c.benchmark_group(format!("create_{TEXTURE_COUNT}_textures"));
+    group.throughput(Throughput::Elements(TEXTURE_COUNT as u64));
+
+    let textures = textures();
+    let slices: Vec<&[u8]> = textures
+        .iter()
+        .map(|data| data.buffer.as_raw().as_slice())
+        .collect();
+    let (device, _ctx, _) = create_device_and_context(false, &DEFAULT_FEATURE_LEVELS).unwrap();
+
+    group.bench_function("Texture", |b| {
+        b.iter(|| {
+            textures
+                .iter()
+                .map(|data| {
+                    Texture::create_from_data(&device, data.width, data.height, &data.buffer)
+                        .unwrap()
+                })
+                .collect::<Vec<_>>()
+        })
+    });
+
+    group.bench_function("TextureArray", |b| {
+        b.iter(|| {
+            TextureArray::create_from_slices(&device, TEXTURE_SIZE, TEXTURE_SIZE, &slices).unwrap()
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, texture_array);
+criterion_main!(benches);
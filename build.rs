@@ -1,4 +1,23 @@
-use std::path::Path;
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Shaders compiled ahead of time: (file, target, entrypoint)
+const SHADERS: &[(&str, &str, &str)] = &[
+    ("vertex_shader.hlsl", "vs_5_0", "VSMain"),
+    ("fragment_shader.hlsl", "ps_5_0", "PSMain"),
+    ("debug_vertex_shader.hlsl", "vs_5_0", "VSMain"),
+    ("debug_fragment_shader.hlsl", "ps_5_0", "PSMain"),
+    ("shadow_blur.hlsl", "cs_5_0", "CSMain"),
+    ("premultiply.hlsl", "cs_5_0", "CSMain"),
+    ("downsample.hlsl", "vs_5_0", "VSMain"),
+    ("downsample.hlsl", "ps_5_0", "PSMain"),
+    ("clear.hlsl", "vs_5_0", "VSMain"),
+    ("clear.hlsl", "ps_5_0", "PSMain"),
+    ("particle.hlsl", "vs_5_0", "VSMain"),
+    ("particle.hlsl", "ps_5_0", "PSMain"),
+];
 
 fn main() -> miette::Result<()> {
     // build_spout2()?;
@@ -12,9 +31,135 @@ fn main() -> miette::Result<()> {
     let mut b = autocxx_build::Builder::new("src/spout/mod.rs", [&include_path]).build()?;
     b.flag_if_supported("-std=c++17").compile("spout-library"); // arbitrary library name, pick anything
 
+    // Shaders are compiled at runtime when using runtime shaders, or when fxc
+    // isn't installed so the crate still builds without the Windows SDK
+    let runtime_shaders = std::env::var_os("CARGO_FEATURE_RUNTIME_SHADERS").is_some()
+        || match find_fxc() {
+            Some(fxc) => {
+                compile_shaders(repo_root, &fxc)?;
+                false
+            }
+            None => {
+                println!(
+                    "cargo:warning=fxc.exe was not found, shaders will be compiled at runtime \
+                     (set FXC to the path of fxc.exe to compile them ahead of time)"
+                );
+                true
+            }
+        };
+
+    println!("cargo:rustc-check-cfg=cfg(runtime_shaders)");
+    if runtime_shaders {
+        println!("cargo:rustc-cfg=runtime_shaders");
+    }
+
     println!("cargo:rerun-if-changed=src/spout/mod.rs");
+    println!("cargo:rerun-if-changed=src/shaders");
+    println!("cargo:rerun-if-env-changed=FXC");
     println!("cargo:rustc-link-lib=SpoutDX");
     println!("cargo:rustc-link-search=native={}", lib_dir.display());
 
     Ok(())
 }
+
+/// Finds fxc, checking the FXC env variable, then the PATH, then the
+/// Windows SDK install directory
+fn find_fxc() -> Option<PathBuf> {
+    if let Some(fxc) = std::env::var_os("FXC") {
+        return Some(PathBuf::from(fxc));
+    }
+
+    let on_path = std::env::var_os("PATH").and_then(|path| {
+        std::env::split_paths(&path)
+            .map(|dir| dir.join("fxc.exe"))
+            .find(|fxc| fxc.is_file())
+    });
+
+    on_path.or_else(find_sdk_fxc)
+}
+
+/// Finds fxc within the newest installed Windows 10/11 SDK
+fn find_sdk_fxc() -> Option<PathBuf> {
+    let arch = if cfg!(target_arch = "x86") {
+        "x86"
+    } else {
+        "x64"
+    };
+
+    // Set by the Visual Studio developer prompt
+    if let Some(bin) = std::env::var_os("WindowsSdkVerBinPath") {
+        let fxc = Path::new(&bin).join(arch).join("fxc.exe");
+        if fxc.is_file() {
+            return Some(fxc);
+        }
+    }
+
+    let program_files =
+        std::env::var_os("ProgramFiles(x86)").or_else(|| std::env::var_os("ProgramFiles"))?;
+    let bin = Path::new(&program_files)
+        .join("Windows Kits")
+        .join("10")
+        .join("bin");
+
+    // Versioned SDK directories (e.g. 10.0.22621.0), newest first
+    let mut versions: Vec<(Vec<u32>, PathBuf)> = std::fs::read_dir(&bin)
+        .ok()?
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let version = entry
+                .file_name()
+                .to_str()?
+                .split('.')
+                .map(|part| part.parse().ok())
+                .collect::<Option<Vec<u32>>>()?;
+            Some((version, entry.path()))
+        })
+        .collect();
+    versions.sort_by(|(a, _), (b, _)| b.cmp(a));
+
+    versions
+        .into_iter()
+        .map(|(_, dir)| dir)
+        // Older SDKs place fxc directly in the bin directory
+        .chain(std::iter::once(bin))
+        .map(|dir| dir.join(arch).join("fxc.exe"))
+        .find(|fxc| fxc.is_file())
+}
+
+/// Compiles the shaders to bytecode (.cso) in the output directory using
+/// fxc
+fn compile_shaders(repo_root: &Path, fxc: &Path) -> miette::Result<()> {
+    let out_dir = std::env::var("OUT_DIR").map_err(|err| miette::miette!("{err}"))?;
+    let shader_out_dir = Path::new(&out_dir).join("shaders");
+    std::fs::create_dir_all(&shader_out_dir).map_err(|err| miette::miette!("{err}"))?;
+
+    for (file, target, entrypoint) in SHADERS {
+        let src = repo_root.join("src").join("shaders").join(file);
+        let out = shader_out_dir.join(format!("{file}.{entrypoint}.cso"));
+
+        let status = Command::new(fxc)
+            .arg("/nologo")
+            .arg("/Ges")
+            .args(["/T", target])
+            .args(["/E", entrypoint])
+            .arg("/Fo")
+            .arg(&out)
+            .arg(&src)
+            .status()
+            .map_err(|err| {
+                miette::miette!(
+                    "failed to run fxc at {} ({err}), set FXC to the path of fxc.exe \
+                     or enable the runtime-shaders feature",
+                    fxc.display()
+                )
+            })?;
+
+        if !status.success() {
+            return Err(miette::miette!(
+                "failed to compile shader {file} {target} {entrypoint}"
+            ));
+        }
+    }
+
+    Ok(())
+}
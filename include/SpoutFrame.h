@@ -0,0 +1,11 @@
+#pragma once
+
+#include "SpoutDX.h"
+
+// Frame counter of a spoutDX instance. Frame sync and frame counting live on
+// spoutFrameCount which spoutDX exposes as the public "frame" member, autocxx
+// doesn't bind data members so it is reached through this accessor
+inline spoutFrameCount& SpoutDXFrame(spoutDX& spout)
+{
+	return spout.frame;
+}
@@ -1,84 +1,816 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use image::{ImageBuffer, ImageFormat, RgbaImage};
 use nalgebra::Vector2;
-use winapi::um::d3d11::{ID3D11Device, ID3D11DeviceContext};
+use rand::{rngs::StdRng, SeedableRng};
+use winapi::{
+    shared::dxgiformat::{
+        DXGI_FORMAT, DXGI_FORMAT_B8G8R8A8_UNORM, DXGI_FORMAT_B8G8R8A8_UNORM_SRGB,
+        DXGI_FORMAT_R8G8B8A8_UNORM, DXGI_FORMAT_R8G8B8A8_UNORM_SRGB,
+    },
+    um::{
+        d3d11::{ID3D11Device, ID3D11DeviceContext},
+        d3dcommon::{D3D_FEATURE_LEVEL, D3D_FEATURE_LEVEL_11_0},
+    },
+};
 
 use crate::{
+    background::{Background, BackgroundFit},
+    clock::SceneClock,
     com::ComPtr,
+    debug::DebugRenderContext,
+    dirty::{DirtyRegion, DirtyTracker},
     dx::{
-        device::{create_device_and_context, Viewport},
-        texture::{BlendState, RenderTargetTexture},
+        annotation::Annotator,
+        device::{
+            adapter_video_memory, create_device_and_context, DeviceCapabilities, ScissorRect,
+            Viewport, DEFAULT_FEATURE_LEVELS,
+        },
+        info_queue::InfoQueue,
+        rasterizer::RasterizerState,
+        shader::ShaderResourceView,
+        texture::{
+            format_bytes_per_pixel, srgb_format, BlendState, RenderTargetTexture, Texture,
+            RENDER_TARGET_FORMAT,
+        },
     },
-    item::{ItemRenderContext, RenderItemDefinition},
+    emitter::{Emitter, EmitterRenderContext},
+    item::{
+        GlobalsBuffer, ItemDataBuffer, ItemEffectId, ItemId, ItemRenderContext,
+        RenderItemDefinition, SceneForces, FRONT_TEXTURE_SLOT,
+    },
+    pacer::FramePacer,
+    premultiply::{Premultiply, PremultiplyMode},
+    shadow::ShadowBlur,
+    supersample::{ResolveFilter, Ssaa, Supersample, MAX_RENDER_SCALE},
+    texture_loader::TextureData,
 };
 
+/// Configuration for the render loop
+pub struct RendererConfig {
+    /// Size of the render target in pixels
+    pub screen_size: Vector2<u32>,
+    /// Format of the render target, must be a format Spout can share.
+    /// DXGI_FORMAT_R16G16B16A16_FLOAT allows HDR output without clipping
+    pub render_target_format: DXGI_FORMAT,
+    /// Acceptable device feature levels, most preferred first
+    pub feature_levels: Vec<D3D_FEATURE_LEVEL>,
+    /// Whether to enable the D3D11 debug layer and log its warnings each
+    /// frame, requires the graphics tools to be installed
+    pub debug_layer: bool,
+    /// Whether to treat textures as sRGB and blend in linear space, avoids
+    /// overlapping semi-transparent edges looking too dark. Requires a
+    /// render target format with an sRGB variant and costs an extra copy
+    /// of the frame before sending
+    pub srgb: bool,
+    /// Premultiplies item textures by their alpha and blends with a
+    /// premultiplied blend state, avoids dark fringes around the edges
+    /// of scaled items. [None] blends straight alpha
+    pub premultiply: Option<PremultiplyMode>,
+    /// Scale of the resolution the scene is rendered at relative to
+    /// `screen_size` (1 to [MAX_RENDER_SCALE]). Scales above 1 render into
+    /// a larger target that is downsampled before sending, giving sharper
+    /// small text and emotes at the cost of fill rate. Positions and
+    /// stages stay in `screen_size` pixels
+    pub render_scale: f32,
+    /// Supersample anti-aliasing, multiplies `render_scale` so the
+    /// combined scale must stay within [MAX_RENDER_SCALE]
+    pub ssaa: Option<Ssaa>,
+    /// Filter used when downsampling a scene rendered above 1x
+    pub resolve_filter: ResolveFilter,
+    /// Whether to share the render target with a keyed mutex held while
+    /// each frame is rendered and sent, stops consumers opening the
+    /// [crate::Renderer::shared_handle] from reading a partial frame.
+    /// Consumers must acquire the mutex, see
+    /// [crate::dx::texture::RenderTargetTexture::shared_handle]
+    pub keyed_mutex: bool,
+    /// Name of the Spout sender receivers connect to
+    pub sender_name: String,
+    /// Whether to signal receivers when each frame is ready, see
+    /// [crate::spout::SpoutSender] for the handshake. Frames are numbered
+    /// when frame counting is enabled in the Spout settings
+    pub frame_sync: bool,
+    /// Font used for item labels, labels are disabled without a font
+    pub font_path: Option<PathBuf>,
+    /// Maximum number of queued items to spawn in a single frame, prevents
+    /// a flood of items from causing a large upload spike
+    pub max_spawns_per_frame: usize,
+    /// Maximum number of items that can be alive at once, `Some(0)` refuses
+    /// every item
+    pub max_items: Option<usize>,
+    /// Policy used when spawning would exceed `max_items`
+    pub eviction_policy: EvictionPolicy,
+    /// Seed for randomized spawn properties, using the same seed
+    /// reproduces the same sequence of spawns
+    pub spawn_seed: Option<u64>,
+    /// Frame rate to pace the render loop to, [None] runs uncapped
+    pub target_fps: Option<u32>,
+    /// Time without any items before the render loop goes idle and stops
+    /// rendering, [None] never goes idle
+    pub idle_timeout: Option<Duration>,
+    /// Interval to keep publishing frames at while idle so receivers
+    /// don't consider the sender lost, [None] stops publishing
+    pub idle_frame_interval: Option<Duration>,
+}
+
+impl RendererConfig {
+    /// Scale the scene is rendered at relative to `screen_size` including
+    /// any supersample anti-aliasing
+    pub fn scene_scale(&self) -> f32 {
+        self.render_scale * self.ssaa.map_or(1.0, |ssaa| ssaa.factor())
+    }
+
+    /// Creates the frame pacer for the configured frame rate
+    pub fn frame_pacer(&self) -> FramePacer {
+        FramePacer::new(self.target_fps)
+    }
+
+    /// Creates the random number generator used for spawning items
+    pub fn spawn_rng(&self) -> StdRng {
+        match self.spawn_seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        }
+    }
+}
+
+impl Default for RendererConfig {
+    fn default() -> Self {
+        Self {
+            screen_size: Vector2::new(1920, 1080),
+            render_target_format: RENDER_TARGET_FORMAT,
+            srgb: false,
+            premultiply: None,
+            render_scale: 1.0,
+            ssaa: None,
+            resolve_filter: ResolveFilter::default(),
+            feature_levels: DEFAULT_FEATURE_LEVELS.to_vec(),
+            debug_layer: false,
+            keyed_mutex: false,
+            sender_name: "VTFTK".to_string(),
+            frame_sync: false,
+            font_path: Some(PathBuf::from("./assets/font.ttf")),
+            max_spawns_per_frame: 8,
+            max_items: Some(256),
+            eviction_policy: EvictionPolicy::EvictOldest,
+            spawn_seed: None,
+            target_fps: Some(30),
+            idle_timeout: Some(Duration::from_secs(10)),
+            idle_frame_interval: Some(Duration::from_secs(1)),
+        }
+    }
+}
+
+/// Signal used to request the render loop stops, clones share the
+/// same signal
+#[derive(Clone, Default)]
+pub struct ShutdownSignal {
+    requested: Arc<AtomicBool>,
+}
+
+impl ShutdownSignal {
+    /// Requests that the render loop stops after the current frame
+    pub fn request(&self) {
+        self.requested.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether a shutdown has been requested
+    pub fn is_requested(&self) -> bool {
+        self.requested.load(Ordering::SeqCst)
+    }
+}
+
+/// Policy deciding which item is dropped when the item cap is reached
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvictionPolicy {
+    /// Remove the oldest live item to make room for the new item
+    EvictOldest,
+    /// Refuse to spawn the new item
+    RefuseNew,
+}
+
+/// Outcome of attempting to make room for a new item
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpawnOutcome {
+    /// There was room for the item
+    Spawned,
+    /// The oldest item was evicted to make room
+    EvictedOldest,
+    /// The new item was refused and should not be spawned
+    Refused,
+}
+
+/// Number of items dropped because of the item cap since the renderer
+/// started, hosts can compare counts between frames to log drops
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DroppedItems {
+    /// Live items evicted to make room for new items
+    pub evicted: u64,
+    /// New items refused without being spawned
+    pub refused: u64,
+}
+
+impl DroppedItems {
+    /// Counts an item dropped with the outcome
+    pub fn record(&mut self, outcome: SpawnOutcome) {
+        match outcome {
+            SpawnOutcome::Spawned => {}
+            SpawnOutcome::EvictedOldest => self.evicted += 1,
+            SpawnOutcome::Refused => self.refused += 1,
+        }
+    }
+}
+
+/// Makes room for a new item according to the configured item cap and
+/// eviction policy, must be called before spawning each item. Attached
+/// items (e.g. labels) don't count towards the cap, they are spawned and
+/// evicted along with their parent
+pub fn admit_item(config: &RendererConfig, items: &mut Vec<RenderItemDefinition>) -> SpawnOutcome {
+    let Some(max_items) = config.max_items else {
+        return SpawnOutcome::Spawned;
+    };
+
+    let live_items = items
+        .iter()
+        .filter(|item| item.attachment.is_none())
+        .count();
+    if live_items < max_items {
+        return SpawnOutcome::Spawned;
+    }
+
+    match config.eviction_policy {
+        EvictionPolicy::EvictOldest => {
+            // Items are stored in spawn order so the oldest are first
+            let excess = live_items + 1 - max_items;
+            let evicted: HashSet<ItemId> = items
+                .iter()
+                .filter(|item| item.attachment.is_none())
+                .take(excess)
+                .map(|item| item.id)
+                .collect();
+
+            // Nothing can be evicted when the cap is zero
+            if evicted.len() < excess {
+                return SpawnOutcome::Refused;
+            }
+
+            remove_items(items, |item| evicted.contains(&item.id));
+            SpawnOutcome::EvictedOldest
+        }
+        EvictionPolicy::RefuseNew => SpawnOutcome::Refused,
+    }
+}
+
+/// Removes items that have finished their lifetime along with any items
+/// attached to them
+pub fn expire_items(items: &mut Vec<RenderItemDefinition>) {
+    remove_items(items, RenderItemDefinition::is_expired);
+}
+
+/// Removes items matching `predicate` along with any items attached to
+/// them, dropping their GPU resources. Returns the number of items removed
+pub fn remove_items(
+    items: &mut Vec<RenderItemDefinition>,
+    predicate: impl Fn(&RenderItemDefinition) -> bool,
+) -> usize {
+    let mut removed: HashSet<ItemId> = items
+        .iter()
+        .filter(|item| predicate(item))
+        .map(|item| item.id)
+        .collect();
+
+    if removed.is_empty() {
+        return 0;
+    }
+
+    // Attachments can be chained, keep collecting children until no new
+    // items are found
+    loop {
+        let removed_count = removed.len();
+
+        for item in items.iter() {
+            if item
+                .attachment
+                .is_some_and(|attachment| removed.contains(&attachment.parent))
+            {
+                removed.insert(item.id);
+            }
+        }
+
+        if removed.len() == removed_count {
+            break;
+        }
+    }
+
+    let count = items.len();
+    items.retain(|item| !removed.contains(&item.id));
+    count - items.len()
+}
+
 /// Rendering context with DirectX11
 pub struct RenderContext {
     /// DirectX11 device
     pub device: ComPtr<ID3D11Device>,
     /// DirectX11 context
     pub ctx: ComPtr<ID3D11DeviceContext>,
-    /// Rendering target texture
+    /// Rendering target texture, this is the texture sent to receivers
     pub rtv: RenderTargetTexture,
+    /// Larger target the scene is drawn into and downsampled from, only
+    /// present when rendering with a render scale above 1
+    pub supersample: Option<Supersample>,
     ///  World rendering context
     pub world: WorldRenderContext,
     /// Item rendering context
     pub item: ItemRenderContext,
+    /// Optional region to clip rendering to
+    pub clip_region: Option<ScissorRect>,
+    /// Debug overlay rendering context
+    pub debug: DebugRenderContext,
+    /// Whether to draw the debug overlay showing item bounds and trajectories
+    pub debug_overlay: bool,
+    /// Texture used for items whose texture failed to load
+    pub placeholder: Texture,
+    /// Shader resource view for the placeholder texture
+    pub placeholder_srv: ShaderResourceView,
+    /// Optional image drawn behind all items
+    pub background: Option<Background>,
+    /// Debug layer messages, only present when the debug layer is enabled
+    pub info_queue: Option<InfoQueue>,
+    /// GPU debug markers around each pass for graphics debuggers, only
+    /// present when the debug layer is enabled
+    pub annotator: Option<Annotator>,
+    /// Capabilities of the device
+    pub capabilities: DeviceCapabilities,
+    /// Compute pass blurring item textures for drop shadows
+    pub shadow_blur: ShadowBlur,
+    /// Compute pass premultiplying item textures
+    pub premultiply: Premultiply,
+    /// Tracks the area that changed each frame, only present when dirty
+    /// rectangle rendering is enabled
+    pub dirty: Option<DirtyTracker>,
+    /// Particle emitter rendering context
+    pub emitter: EmitterRenderContext,
 }
 
 impl RenderContext {
-    pub fn create(screen_size: Vector2<u32>) -> anyhow::Result<RenderContext> {
-        let (device, ctx) = create_device_and_context()?;
-        let rtv = RenderTargetTexture::create(&device, screen_size.x, screen_size.y)?;
-        let world = WorldRenderContext::create(&device, screen_size.cast::<f32>())?;
+    /// Unbinds the geometry, textures, samplers, and render target bound
+    /// during the frame then resolves the supersampled scene, leaving the
+    /// pipeline clean for the next pass. See [setup_render_world] for the
+    /// frame lifecycle
+    pub fn end_frame(&mut self) {
+        let ctx = &mut self.ctx;
+
+        self.item.finish_render(ctx);
+        self.rtv.unbind(ctx);
+
+        if let Some(supersample) = self.supersample.as_mut() {
+            let _scope = self
+                .annotator
+                .as_ref()
+                .map(|annotator| annotator.scope("Resolve"));
+            supersample.resolve(ctx, &mut self.rtv);
+        }
+    }
+
+    /// Creates the render context and its device using the render target
+    /// and quality settings from the config
+    pub fn create(config: &RendererConfig) -> anyhow::Result<RenderContext> {
+        let (device, ctx, _) =
+            create_device_and_context(config.debug_layer, &config.feature_levels)?;
+
+        Self::from_existing(device, ctx, config)
+    }
+
+    /// Creates the render context on a device and immediate context owned
+    /// by the host application instead of creating a new device, the
+    /// device settings in the config are ignored. The device must support
+    /// feature level 11_0.
+    ///
+    /// Rendering binds its own pipeline state on the context each frame
+    /// and leaves it changed, hosts sharing the context must rebind their
+    /// own state after rendering
+    pub fn from_existing(
+        device: ComPtr<ID3D11Device>,
+        ctx: ComPtr<ID3D11DeviceContext>,
+        config: &RendererConfig,
+    ) -> anyhow::Result<RenderContext> {
+        let screen_size = config.screen_size;
+        let format = config.render_target_format;
+        let srgb = config.srgb;
+        let premultiply_mode = config.premultiply;
+        let render_scale = config.scene_scale();
+
+        if srgb && premultiply_mode == Some(PremultiplyMode::Compute) {
+            anyhow::bail!(
+                "compute premultiply is not supported with sRGB, use PremultiplyMode::Cpu"
+            );
+        }
+
+        if !(1.0..=MAX_RENDER_SCALE).contains(&render_scale) {
+            anyhow::bail!(
+                "render scale must be within 1 to {MAX_RENDER_SCALE} (got {render_scale})"
+            );
+        }
+
+        let info_queue = InfoQueue::from_device(&device);
+        let annotator = config
+            .debug_layer
+            .then(|| Annotator::from_context(&ctx))
+            .flatten();
+
+        let target_format = match srgb {
+            true => srgb_format(format)
+                .ok_or_else(|| anyhow::anyhow!("format {format} has no sRGB variant"))?,
+            false => format,
+        };
+
+        // Check the formats used for rendering and item textures
+        let capabilities = DeviceCapabilities::query(
+            &device,
+            &[
+                format,
+                target_format,
+                DXGI_FORMAT_R8G8B8A8_UNORM,
+                DXGI_FORMAT_R8G8B8A8_UNORM_SRGB,
+            ],
+        );
+
+        if capabilities.feature_level < D3D_FEATURE_LEVEL_11_0 {
+            anyhow::bail!(
+                "device feature level {:#x} is below the required 11_0",
+                capabilities.feature_level
+            );
+        }
+
+        if !capabilities
+            .format_support(target_format)
+            .is_some_and(|support| support.render_target && support.blendable)
+        {
+            anyhow::bail!("render target format {target_format} is not supported by the device");
+        }
+
+        let scene_size = (screen_size.cast::<f32>() * render_scale).map(|size| size.ceil() as u32);
+
+        if scene_size.x > capabilities.max_texture_dimension
+            || scene_size.y > capabilities.max_texture_dimension
+        {
+            anyhow::bail!(
+                "render target size {}x{} exceeds the device maximum of {}",
+                scene_size.x,
+                scene_size.y,
+                capabilities.max_texture_dimension
+            );
+        }
+
+        // The scene target is allocated on top of everything else, refuse
+        // scales that would take a large share of the adapter memory
+        if scene_size != screen_size {
+            let bytes_per_pixel = format_bytes_per_pixel(target_format).unwrap_or(16);
+            let scene_bytes =
+                scene_size.x as usize * scene_size.y as usize * bytes_per_pixel as usize;
+
+            if let Some(video_memory) = adapter_video_memory(&device).filter(|&memory| memory > 0) {
+                if scene_bytes > video_memory / MAX_SCENE_MEMORY_DIVISOR {
+                    anyhow::bail!(
+                        "render target size {}x{} needs {} MiB which is too large for the \
+                         {} MiB of video memory, lower the render scale",
+                        scene_size.x,
+                        scene_size.y,
+                        scene_bytes >> 20,
+                        video_memory >> 20
+                    );
+                }
+            }
+        }
+
+        let rtv = if srgb {
+            RenderTargetTexture::create_srgb(
+                &device,
+                screen_size.x,
+                screen_size.y,
+                format,
+                config.keyed_mutex,
+            )?
+        } else {
+            RenderTargetTexture::create(
+                &device,
+                screen_size.x,
+                screen_size.y,
+                format,
+                config.keyed_mutex,
+            )?
+        };
+        let supersample = match scene_size != screen_size {
+            true => Some(Supersample::create(
+                &device,
+                scene_size,
+                screen_size,
+                target_format,
+                premultiply_mode.is_some(),
+                config.resolve_filter,
+            )?),
+            false => None,
+        };
+        let world = WorldRenderContext::create(
+            &device,
+            screen_size.cast::<f32>(),
+            premultiply_mode.is_some(),
+            render_scale,
+        )?;
         let item = ItemRenderContext::create(&device)?;
+        let debug = DebugRenderContext::create(&device)?;
+        let shadow_blur = ShadowBlur::create(&device)?;
+        let premultiply = Premultiply::create(&device)?;
+        let emitter = EmitterRenderContext::create(&device)?;
+
+        let placeholder_data = TextureData::placeholder();
+        let mut placeholder = Texture::create_from_data(
+            &device,
+            placeholder_data.width,
+            placeholder_data.height,
+            &placeholder_data.buffer,
+        )?;
+        let placeholder_srv =
+            ShaderResourceView::create_from_texture(&device, placeholder.texture.cast_as_mut())?;
 
         Ok(RenderContext {
             device,
             ctx,
             rtv,
+            supersample,
             world,
             item,
+            clip_region: None,
+            debug,
+            debug_overlay: false,
+            placeholder,
+            placeholder_srv,
+            background: None,
+            info_queue,
+            annotator,
+            capabilities,
+            shadow_blur,
+            premultiply,
+            dirty: None,
+            emitter,
         })
     }
+
+    /// Runs every pass once so the driver finishes compiling the shaders
+    /// and creating the pipeline states before the first real frame,
+    /// drivers defer that work to the first draw using them which makes
+    /// the first frame stutter. Call after creating the context and
+    /// before the render loop, leaves the render target cleared
+    pub fn warmup(&mut self) -> anyhow::Result<()> {
+        // Compute passes, the results are discarded
+        let size = self.placeholder.size;
+        self.premultiply
+            .apply(&self.device, &self.ctx, &mut self.placeholder_srv, size)?;
+        self.shadow_blur
+            .blur(&self.device, &self.ctx, &mut self.placeholder_srv, size, 1)?;
+
+        // Nothing has opened the shared texture before the render loop so
+        // the keyed mutex (if any) is free
+        if !self.rtv.acquire_sync(Duration::ZERO)? {
+            anyhow::bail!("render target is held by a consumer, warm up before sending frames");
+        }
+
+        setup_render_world(self, None);
+
+        let ctx = &self.ctx;
+        scene_target(&mut self.rtv, &mut self.supersample).clear(ctx, &CLEAR_COLOR);
+
+        // Zero sized items cover no pixels but still go through the full
+        // pipeline
+        self.item
+            .set_current_data(ctx, &ItemDataBuffer::default())?;
+        self.placeholder_srv.bind_slot(ctx, FRONT_TEXTURE_SLOT);
+        self.item.quad.bind(ctx);
+
+        for pixelate in [false, true] {
+            self.item.set_sampler(ctx, pixelate);
+            for blend_state in [
+                &mut self.world.opaque_blend_state,
+                &mut self.world.blend_state,
+            ] {
+                blend_state.bind(ctx);
+                self.item.quad.draw(ctx);
+            }
+        }
+
+        self.end_frame();
+        self.rtv.clear(&self.ctx, &CLEAR_COLOR);
+        self.rtv.release_sync()?;
+
+        unsafe {
+            self.ctx.Flush();
+        }
+
+        Ok(())
+    }
+
+    /// Sets the image drawn behind all items, the clear color still shows
+    /// through where the background is transparent
+    pub fn set_background(&mut self, texture: Texture, fit: BackgroundFit) -> anyhow::Result<()> {
+        self.background = Some(Background::create(
+            &self.device,
+            texture,
+            fit,
+            self.world.screen_size,
+        )?);
+        self.invalidate();
+        Ok(())
+    }
+
+    /// Removes the background image
+    pub fn clear_background(&mut self) {
+        self.background = None;
+        self.invalidate();
+    }
+
+    /// Enables or disables dirty rectangle rendering, when enabled only
+    /// the area around items that moved or changed is redrawn each frame
+    /// and frames where nothing changed are not redrawn at all
+    pub fn set_dirty_tracking(&mut self, enabled: bool) -> anyhow::Result<()> {
+        match (enabled, self.dirty.is_some()) {
+            (true, false) => self.dirty = Some(DirtyTracker::create(&self.device, CLEAR_COLOR)?),
+            (false, true) => self.dirty = None,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Redraws the whole render target next frame, must be called when
+    /// anything other than the items changes how the scene looks
+    pub fn invalidate(&mut self) {
+        if let Some(dirty) = self.dirty.as_mut() {
+            dirty.invalidate();
+        }
+    }
+
+    /// Captures the current contents of the render target to a PNG file
+    pub fn capture_png<P: AsRef<Path>>(&mut self, path: P) -> anyhow::Result<()> {
+        if !matches!(
+            self.rtv.format,
+            DXGI_FORMAT_B8G8R8A8_UNORM | DXGI_FORMAT_B8G8R8A8_UNORM_SRGB
+        ) {
+            anyhow::bail!("capture is not supported for format {}", self.rtv.format);
+        }
+
+        let mut pixels = self.rtv.read_pixels(&self.device, &self.ctx)?;
+
+        // Convert BGRA to RGBA
+        pixels
+            .chunks_exact_mut(4)
+            .for_each(|pixel| pixel.swap(0, 2));
+
+        let size = self.rtv.size;
+        let image: RgbaImage = ImageBuffer::from_raw(size.x, size.y, pixels)
+            .ok_or_else(|| anyhow::anyhow!("captured pixel buffer size mismatch"))?;
+        image.save_with_format(path, ImageFormat::Png)?;
+
+        Ok(())
+    }
+
+    /// Sets the region items are confined to, items outside the region
+    /// are clipped. Takes effect on the next frame
+    pub fn set_clip_region(&mut self, clip_region: Option<ScissorRect>) {
+        self.clip_region = clip_region;
+        self.invalidate();
+    }
 }
 
 pub struct WorldRenderContext {
     pub screen_size: Vector2<f32>,
-    pub viewport: Viewport,
+    /// Viewports for each stage, items are rendered into the viewport of
+    /// their stage. The first stage covers the whole render target
+    pub viewports: Vec<Viewport>,
     pub blend_state: BlendState,
+    /// Blend state for alpha tested cutout items
+    pub opaque_blend_state: BlendState,
+    /// Whether the blend state expects premultiplied colors
+    pub premultiplied: bool,
+    /// Scale from viewport pixels to scene render target pixels
+    pub render_scale: f32,
+    pub rasterizer_state: RasterizerState,
+    pub scissor_state: RasterizerState,
+    /// Gravity and wind acting on the scene
+    pub forces: SceneForces,
+    /// Whether item edges are antialiased so slowly moving items glide
+    /// between pixels instead of snapping
+    pub subpixel_edges: bool,
 }
 
 impl WorldRenderContext {
     pub fn create(
         device: &ID3D11Device,
         screen_size: Vector2<f32>,
+        premultiplied: bool,
+        render_scale: f32,
     ) -> anyhow::Result<WorldRenderContext> {
-        let viewport = Viewport::new(screen_size, Vector2::new(0.0, 1.0));
-        let blend_state = BlendState::alpha_blend_state(device)?;
+        let viewport = Viewport::fullscreen(screen_size);
+        let blend_state = match premultiplied {
+            true => BlendState::premultiplied_alpha_state(device)?,
+            false => BlendState::alpha_blend_state(device)?,
+        };
+        let opaque_blend_state = BlendState::opaque_state(device)?;
+        let rasterizer_state = RasterizerState::no_cull(device)?;
+        let scissor_state = RasterizerState::scissor(device)?;
 
         Ok(WorldRenderContext {
             screen_size,
-            viewport,
+            viewports: vec![viewport],
             blend_state,
+            opaque_blend_state,
+            premultiplied,
+            render_scale,
+            rasterizer_state,
+            scissor_state,
+            forces: SceneForces::default(),
+            subpixel_edges: false,
         })
     }
 }
 
+impl WorldRenderContext {
+    /// Adds a stage rendering into the provided sub-rectangle of the render
+    /// target (pixels), returns the index of the stage
+    pub fn add_stage(&mut self, position: Vector2<f32>, size: Vector2<f32>) -> usize {
+        self.viewports.push(Viewport::region(position, size));
+        self.viewports.len() - 1
+    }
+
+    /// Size of the provided stage in pixels, [None] if the stage doesn't exist
+    pub fn stage_size(&self, stage: usize) -> Option<Vector2<f32>> {
+        self.viewports.get(stage).map(Viewport::size)
+    }
+
+    /// Binds the viewport of a stage mapped onto the scene render target
+    pub fn bind_viewport(&self, ctx: &ID3D11DeviceContext, viewport: &Viewport) {
+        viewport.scaled(self.render_scale).bind(ctx);
+    }
+}
+
+/// The supersampled scene target may use at most 1/N of the adapter's
+/// dedicated video memory
+const MAX_SCENE_MEMORY_DIVISOR: usize = 2;
+
 static CLEAR_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.0];
 
-pub fn setup_render_world(render_ctx: &mut RenderContext) {
+/// Binds all the state needed to render the world, called at the start
+/// of every frame by [render] so passes that change state mid-frame
+/// don't affect the next frame. `dirty_rect` limits drawing to the part
+/// of the target being redrawn, see [DirtyTracker].
+///
+/// Per frame lifecycle:
+/// 1. [setup_render_world] binds the render target, viewport, blend,
+///    rasterizer, and the item pipeline using
+///    [ItemRenderContext::prepare_render]
+/// 2. Items bind their geometry, textures (t0-t3) and sampler (s0) per
+///    draw
+/// 3. [RenderContext::end_frame] unbinds the geometry, textures, samplers,
+///    and render target so nothing is left bound as both an input and an
+///    output
+///
+/// `prepare_render` and `end_frame` bracket the item pass, passes that
+/// run between them must restore the item pipeline with `prepare_render`
+pub fn setup_render_world(render_ctx: &mut RenderContext, dirty_rect: Option<&ScissorRect>) {
     let ctx = &mut render_ctx.ctx;
     let world = &mut render_ctx.world;
     let item_ctx = &mut render_ctx.item;
 
     // Bind the render texture
-    render_ctx.rtv.bind(ctx);
+    scene_target(&mut render_ctx.rtv, &mut render_ctx.supersample).bind(ctx);
 
-    // Setup viewport
-    world.viewport.bind(ctx);
+    // Setup viewport for the primary stage
+    world.bind_viewport(ctx, &world.viewports[0]);
 
     // Setup blending for layers
     world.blend_state.bind(ctx);
 
+    // Setup rasterizer and clipping region, the dirty region further
+    // limits drawing to the area that changed
+    let scissor = [render_ctx.clip_region.as_ref(), dirty_rect]
+        .into_iter()
+        .flatten()
+        .map(|rect| rect.scaled(world.render_scale))
+        .reduce(|clip, rect| clip.intersection(&rect));
+    match scissor {
+        Some(scissor) => {
+            world.scissor_state.bind(ctx);
+            scissor.bind(ctx);
+        }
+        None => world.rasterizer_state.bind(ctx),
+    }
+
     // Prepare for rendering items
     item_ctx.prepare_render(ctx);
 
@@ -86,29 +818,310 @@ pub fn setup_render_world(render_ctx: &mut RenderContext) {
     item_ctx.bind_constants(ctx);
 }
 
+/// Render target the scene is drawn into, the supersampled target when
+/// rendering above the output resolution
+fn scene_target<'a>(
+    rtv: &'a mut RenderTargetTexture,
+    supersample: &'a mut Option<Supersample>,
+) -> &'a mut RenderTargetTexture {
+    match supersample {
+        Some(supersample) => &mut supersample.target,
+        None => rtv,
+    }
+}
+
+/// Advances the items and emitters to the current scene time, must be
+/// called before [render] each frame
+pub fn update(
+    ctx: &ID3D11DeviceContext,
+    items: &mut [RenderItemDefinition],
+    emitters: &mut [Emitter],
+    clock: &SceneClock,
+) -> anyhow::Result<()> {
+    // Update item data
+    for item in items.iter_mut() {
+        item.update(clock)?;
+    }
+
+    for emitter in emitters.iter_mut() {
+        emitter.update(ctx, clock)?;
+    }
+
+    // Move attached items to follow their parents
+    resolve_attachments(items);
+
+    Ok(())
+}
+
 pub fn render(
     render_ctx: &mut RenderContext,
     items: &mut Vec<RenderItemDefinition>,
+    emitters: &mut [Emitter],
+    clock: &SceneClock,
 ) -> anyhow::Result<()> {
+    // With dirty tracking only the area that changed is redrawn, the
+    // previous frame is kept as is when nothing changed. Particles aren't
+    // tracked so the whole target is redrawn while any are alive
+    let force_full = render_ctx.debug_overlay || !emitters.is_empty();
+    let dirty_rect = match render_ctx.dirty.as_mut() {
+        Some(dirty) => match dirty.update(items, &render_ctx.world, force_full) {
+            DirtyRegion::Full => None,
+            DirtyRegion::Partial(rect) => Some(rect),
+            DirtyRegion::Clean => return Ok(()),
+        },
+        None => None,
+    };
+
+    setup_render_world(render_ctx, dirty_rect.as_ref());
+
     let ctx = &mut render_ctx.ctx;
     let item_ctx = &mut render_ctx.item;
+    let annotator = render_ctx.annotator.as_ref();
 
     // Clear background color
-    render_ctx.rtv.clear(ctx, &CLEAR_COLOR);
+    {
+        let _scope = annotator.map(|annotator| annotator.scope("Clear"));
+        match render_ctx.dirty.as_mut().filter(|_| dirty_rect.is_some()) {
+            Some(dirty) => {
+                // Clears within the scissor, then restores the blend state
+                // and item pipeline the clear replaced
+                dirty.clear(ctx);
+                render_ctx.world.blend_state.bind(ctx);
+                item_ctx.prepare_render(ctx);
+            }
+            None => scene_target(&mut render_ctx.rtv, &mut render_ctx.supersample)
+                .clear(ctx, &CLEAR_COLOR),
+        }
+    }
 
-    for item in items {
-        // Update item data
-        item.update()?;
+    let scene_time = clock.now().as_secs_f32();
+    let random_seed = rand::random();
 
-        // Update the constant buffer using the current data
-        item_ctx.set_current_data(ctx, &item.item_data)?;
+    // Background covers the whole target using the primary stage
+    if let Some(background) = render_ctx.background.as_mut() {
+        let _scope = annotator.map(|annotator| annotator.scope("Background"));
+        let viewport = &render_ctx.world.viewports[0];
+        render_ctx.world.bind_viewport(ctx, viewport);
+        item_ctx.set_globals(
+            ctx,
+            &stage_globals(&render_ctx.world, viewport, scene_time, random_seed),
+        )?;
+        background.render(ctx, item_ctx)?;
+    }
 
-        // Set current sampler for pixelation
-        item_ctx.set_sampler(ctx, item.pixelate);
+    for (stage, viewport) in render_ctx.world.viewports.iter().enumerate() {
+        let _scope = annotator.map(|annotator| annotator.scope(&format!("Stage {stage}")));
+        render_ctx.world.bind_viewport(ctx, viewport);
 
-        // Render item
-        item.render(ctx);
+        // Globals are relative to the stage being rendered
+        let globals = stage_globals(&render_ctx.world, viewport, scene_time, random_seed);
+        item_ctx.set_globals(ctx, &globals)?;
+
+        // The pipeline state is bound once per bucket instead of per item
+        for bucket in draw_buckets(items, stage) {
+            let key = bucket.key;
+            let cutout = !key.blended;
+            item_ctx.set_sampler(ctx, key.pixelate);
+            item_ctx.set_effect(ctx, key.effect);
+
+            // Cutouts replace the pixels they keep
+            if cutout {
+                render_ctx.world.opaque_blend_state.bind(ctx);
+            }
+
+            for index in bucket.items {
+                let item = &mut items[index];
+
+                // Shadow and item share the deformed mesh
+                item.deform_mesh(ctx)?;
+
+                // Draw the shadow first so the item is drawn over it,
+                // shadows are always blended
+                if item.item_data.shadow_enabled != 0 {
+                    if cutout {
+                        render_ctx.world.blend_state.bind(ctx);
+                    }
+
+                    item.item_data.shadow_pass = 1;
+                    item_ctx.set_current_data(ctx, &item.item_data)?;
+                    item.render(ctx);
+                    item.item_data.shadow_pass = 0;
+
+                    if cutout {
+                        render_ctx.world.opaque_blend_state.bind(ctx);
+                    }
+                }
+
+                // Update the constant buffer using the current data
+                item_ctx.set_current_data(ctx, &item.item_data)?;
+                item.render(ctx);
+            }
+
+            if cutout {
+                render_ctx.world.blend_state.bind(ctx);
+            }
+        }
+
+        // Particles are drawn over the items of their stage
+        if emitters.iter().any(|emitter| emitter.stage == stage) {
+            let _scope = annotator.map(|annotator| annotator.scope("Particles"));
+            render_ctx.emitter.render(
+                ctx,
+                emitters.iter_mut().filter(|emitter| emitter.stage == stage),
+            );
+
+            // Restore the item pipeline for the next stage
+            render_ctx.world.blend_state.bind(ctx);
+            item_ctx.prepare_render(ctx);
+        }
+
+        if render_ctx.debug_overlay {
+            let _scope = annotator.map(|annotator| annotator.scope("Debug overlay"));
+            render_ctx.debug.render(
+                &render_ctx.device,
+                ctx,
+                items
+                    .iter()
+                    .filter(|item| item.stage == stage && !item.delayed),
+                globals.screen_aspect,
+            )?;
+
+            // Restore the item pipeline for the next stage
+            item_ctx.prepare_render(ctx);
+        }
+    }
+
+    render_ctx.end_frame();
+
+    // Report any misuse (e.g. resources bound as both input and output)
+    // the debug layer caught during the frame
+    if let Some(info_queue) = render_ctx.info_queue.as_mut() {
+        for message in info_queue.take_warnings() {
+            eprintln!("d3d11 debug layer: {message}");
+        }
     }
 
     Ok(())
 }
+
+/// Pipeline state an item is drawn with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DrawKey {
+    /// Alpha tested cutouts are drawn without blending
+    blended: bool,
+    effect: Option<ItemEffectId>,
+    pixelate: bool,
+}
+
+impl DrawKey {
+    fn of(item: &RenderItemDefinition) -> DrawKey {
+        DrawKey {
+            blended: item.item_data.alpha_cutoff <= 0.0,
+            effect: item.effect,
+            pixelate: item.pixelate,
+        }
+    }
+}
+
+/// Run of items drawn with the same pipeline state
+struct DrawBucket {
+    key: DrawKey,
+    /// Indices of the items in draw order
+    items: Vec<usize>,
+}
+
+/// Groups the visible items of a stage into buckets sharing a pipeline
+/// state. Items are drawn by layer then spawn order (items stay stored in
+/// spawn order as eviction relies on it), only consecutive items sharing
+/// a state are merged so blended items such as labels still overlap in
+/// order
+fn draw_buckets(items: &[RenderItemDefinition], stage: usize) -> Vec<DrawBucket> {
+    let mut draw_order: Vec<(i32, usize)> = items
+        .iter()
+        .enumerate()
+        .filter(|(_, item)| item.stage == stage && !item.delayed)
+        .map(|(index, item)| (item.order, index))
+        .collect();
+
+    // Stable so items sharing a layer keep their spawn order
+    draw_order.sort_by_key(|&(order, _)| order);
+
+    let mut buckets: Vec<DrawBucket> = Vec::new();
+    for (_, index) in draw_order {
+        let key = DrawKey::of(&items[index]);
+        match buckets.last_mut() {
+            Some(bucket) if bucket.key == key => bucket.items.push(index),
+            _ => buckets.push(DrawBucket {
+                key,
+                items: vec![index],
+            }),
+        }
+    }
+
+    buckets
+}
+
+/// Per-frame globals for rendering into the provided stage viewport
+fn stage_globals(
+    world: &WorldRenderContext,
+    viewport: &Viewport,
+    scene_time: f32,
+    random_seed: u32,
+) -> GlobalsBuffer {
+    let screen_size = viewport.size();
+
+    GlobalsBuffer {
+        screen_size,
+        scene_time,
+        random_seed,
+        screen_aspect: screen_size.x / screen_size.y,
+        premultiplied_output: world.premultiplied as u32,
+        scene_gravity: world.forces.gravity,
+        scene_wind: world.forces.wind,
+        subpixel_edges: world.subpixel_edges as u32,
+    }
+}
+
+/// Positions attached items relative to their parent's current position,
+/// parents are always resolved before their children so chains of
+/// attachments work regardless of the order items were spawned in
+fn resolve_attachments(items: &mut [RenderItemDefinition]) {
+    let mut positions: HashMap<ItemId, Vector2<f32>> = items
+        .iter()
+        .filter(|item| item.attachment.is_none())
+        .map(|item| (item.id, item.item_data.current_position()))
+        .collect();
+
+    let mut pending: Vec<usize> = items
+        .iter()
+        .enumerate()
+        .filter(|(_, item)| item.attachment.is_some())
+        .map(|(index, _)| index)
+        .collect();
+
+    while !pending.is_empty() {
+        let pending_count = pending.len();
+
+        pending.retain(|&index| {
+            let item = &mut items[index];
+            let Some(attachment) = item.attachment else {
+                return false;
+            };
+
+            let Some(parent_position) = positions.get(&attachment.parent) else {
+                return true;
+            };
+
+            let position = parent_position + attachment.offset;
+            item.set_fixed_position(position);
+            positions.insert(item.id, position);
+            false
+        });
+
+        // Remaining items have parents that no longer exist, they stay
+        // where they were last positioned
+        if pending.len() == pending_count {
+            break;
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use nalgebra::Vector2;
+use winapi::um::d3d11::{ID3D11Device, ID3D11DeviceContext};
+
+use crate::{
+    dx::{shader::ShaderResourceView, texture::Texture},
+    item::{ItemDataBuffer, ItemRenderContext, FRONT_TEXTURE_SLOT},
+};
+
+/// How the background is fit to the render target when the aspect
+/// ratios differ
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackgroundFit {
+    /// Scale to cover the whole target, cropping the overflowing edges
+    Cover,
+    /// Scale to fit within the target, leaving the clear color visible
+    /// around the edges
+    Contain,
+    /// Stretch to fill the target ignoring the aspect ratio
+    Stretch,
+}
+
+/// Static image drawn behind all items, drawn using the item pipeline
+/// as a centered quad that doesn't move
+pub struct Background {
+    texture: Texture,
+    shader_resource_view: ShaderResourceView,
+    item_data: ItemDataBuffer,
+}
+
+impl Background {
+    /// Creates a background from the provided texture
+    pub fn create(
+        device: &ID3D11Device,
+        mut texture: Texture,
+        fit: BackgroundFit,
+        screen_size: Vector2<f32>,
+    ) -> anyhow::Result<Background> {
+        let shader_resource_view =
+            ShaderResourceView::create_from_texture(device, texture.texture.cast_as_mut())?;
+
+        let item_data = ItemDataBuffer {
+            norm_texture_size: fit_size(texture.size.cast::<f32>(), screen_size, fit),
+            // Duration must be non zero to avoid dividing by zero in the shader
+            duration: 1.0,
+            ..Default::default()
+        };
+
+        Ok(Background {
+            texture,
+            shader_resource_view,
+            item_data,
+        })
+    }
+
+    /// Texture drawn as the background
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    /// Draws the background, must be drawn before any items
+    pub fn render(
+        &mut self,
+        ctx: &ID3D11DeviceContext,
+        item_ctx: &mut ItemRenderContext,
+    ) -> anyhow::Result<()> {
+        item_ctx.set_current_data(ctx, &self.item_data)?;
+        item_ctx.set_sampler(ctx, false);
+
+        // Both faces use the same texture
+        let view = Some(&self.shader_resource_view);
+        ShaderResourceView::bind_many(ctx, FRONT_TEXTURE_SLOT, &[view, view, None, None, None]);
+
+        item_ctx.quad.bind(ctx);
+        item_ctx.quad.draw(ctx);
+
+        Ok(())
+    }
+}
+
+/// Size of the background quad (Normalized device coordinates, the full
+/// target is 2 units across)
+fn fit_size(
+    texture_size: Vector2<f32>,
+    screen_size: Vector2<f32>,
+    fit: BackgroundFit,
+) -> Vector2<f32> {
+    let scale = screen_size.component_div(&texture_size);
+    let scale = match fit {
+        BackgroundFit::Cover => Vector2::repeat(scale.max()),
+        BackgroundFit::Contain => Vector2::repeat(scale.min()),
+        BackgroundFit::Stretch => scale,
+    };
+
+    texture_size
+        .component_mul(&scale)
+        .component_div(&screen_size)
+        * 2.0
+}
@@ -0,0 +1,147 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// Source of time for the scene clock
+pub trait Clock: Send {
+    /// Time since an arbitrary fixed point, must never decrease
+    fn now(&self) -> Duration;
+}
+
+/// Clock backed by the system monotonic clock
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// Clock that only advances when told to, allows deterministic
+/// control over time. Clones share the same time
+#[derive(Clone, Default)]
+pub struct MockClock {
+    /// Current time in nanoseconds
+    time: Arc<AtomicU64>,
+}
+
+impl MockClock {
+    /// Advances the clock by the provided duration
+    pub fn advance(&self, duration: Duration) {
+        self.time
+            .fetch_add(duration.as_nanos() as u64, Ordering::SeqCst);
+    }
+
+    /// Sets the current time of the clock
+    pub fn set(&self, time: Duration) {
+        self.time.store(time.as_nanos() as u64, Ordering::SeqCst);
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Duration {
+        Duration::from_nanos(self.time.load(Ordering::SeqCst))
+    }
+}
+
+/// Central clock for the scene, items derive their animation time from
+/// the scene clock rather than real time so the scene can be paused,
+/// stepped, and slowed down or sped up
+pub struct SceneClock {
+    /// Source of real time
+    source: Box<dyn Clock>,
+    /// Real time of the last tick
+    last_tick: Duration,
+    /// Current time within the scene
+    scene_time: Duration,
+    /// Multiplier applied to real time as it passes
+    time_scale: f64,
+    /// Whether time is currently paused
+    paused: bool,
+    /// Time to advance on the next tick regardless of pausing
+    pending_step: Duration,
+}
+
+impl Default for SceneClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SceneClock {
+    pub fn new() -> SceneClock {
+        Self::with_source(SystemClock::default())
+    }
+
+    /// Creates a scene clock using the provided source of real time
+    pub fn with_source<C: Clock + 'static>(source: C) -> SceneClock {
+        SceneClock {
+            last_tick: source.now(),
+            source: Box::new(source),
+            scene_time: Duration::ZERO,
+            time_scale: 1.0,
+            paused: false,
+            pending_step: Duration::ZERO,
+        }
+    }
+
+    /// Advances the scene time by the real time passed since the last
+    /// tick, should be called once at the start of each frame
+    pub fn tick(&mut self) {
+        let now = self.source.now();
+        let real_elapsed = now.saturating_sub(self.last_tick);
+        self.last_tick = now;
+
+        if !self.paused {
+            self.scene_time += real_elapsed.mul_f64(self.time_scale);
+        }
+
+        self.scene_time += std::mem::take(&mut self.pending_step);
+    }
+
+    /// Current time within the scene
+    pub fn now(&self) -> Duration {
+        self.scene_time
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Sets the speed time passes at (0.5 for half speed, 2.0 for double)
+    pub fn set_time_scale(&mut self, time_scale: f64) {
+        self.time_scale = time_scale.max(0.0);
+    }
+
+    pub fn time_scale(&self) -> f64 {
+        self.time_scale
+    }
+
+    /// Advances the scene by a fixed amount on the next tick, used for
+    /// stepping frame-by-frame while paused
+    pub fn step(&mut self, duration: Duration) {
+        self.pending_step += duration;
+    }
+}
@@ -0,0 +1,157 @@
+use nalgebra::{Vector2, Vector4};
+use winapi::{
+    shared::dxgiformat::{DXGI_FORMAT_R32G32B32A32_FLOAT, DXGI_FORMAT_R32G32_FLOAT},
+    um::{
+        d3d11::{
+            ID3D11Device, ID3D11DeviceContext, D3D11_INPUT_ELEMENT_DESC,
+            D3D11_INPUT_PER_VERTEX_DATA,
+        },
+        d3dcommon::D3D11_PRIMITIVE_TOPOLOGY_LINELIST,
+    },
+};
+
+use crate::{
+    dx::{
+        buffer::VertexBuffer,
+        shader::{PixelShader, ShaderInputLayout, VertexShader},
+    },
+    item::RenderItemDefinition,
+    load_shader,
+};
+
+/// Color of item bounding boxes
+const BOUNDS_COLOR: Vector4<f32> = Vector4::new(0.0, 1.0, 0.0, 1.0);
+/// Color of item trajectories
+const TRAJECTORY_COLOR: Vector4<f32> = Vector4::new(1.0, 0.0, 1.0, 1.0);
+/// Number of line segments used to draw each trajectory
+const TRAJECTORY_SEGMENTS: usize = 32;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct DebugVertex {
+    pos: Vector2<f32>,
+    color: Vector4<f32>,
+}
+
+/// Context for drawing the debug overlay, draws item bounding boxes
+/// and the trajectory each item travels along
+pub struct DebugRenderContext {
+    input_layout: ShaderInputLayout,
+    vertex: VertexShader,
+    pixel: PixelShader,
+}
+
+impl DebugRenderContext {
+    pub fn create(device: &ID3D11Device) -> anyhow::Result<DebugRenderContext> {
+        let vertex_shader_blob = load_shader!("debug_vertex_shader.hlsl", "vs_5_0", "VSMain")?;
+        let pixel_shader_blob = load_shader!("debug_fragment_shader.hlsl", "ps_5_0", "PSMain")?;
+
+        let vertex = VertexShader::create(device, vertex_shader_blob.clone())?;
+        let pixel = PixelShader::create(device, pixel_shader_blob)?;
+
+        let input_layout = ShaderInputLayout::create(
+            device,
+            &[
+                D3D11_INPUT_ELEMENT_DESC {
+                    SemanticName: "POSITION\0".as_ptr() as _,
+                    SemanticIndex: 0,
+                    Format: DXGI_FORMAT_R32G32_FLOAT,
+                    InputSlot: 0,
+                    AlignedByteOffset: 0,
+                    InputSlotClass: D3D11_INPUT_PER_VERTEX_DATA,
+                    InstanceDataStepRate: 0,
+                },
+                D3D11_INPUT_ELEMENT_DESC {
+                    SemanticName: "COLOR\0".as_ptr() as _,
+                    SemanticIndex: 0,
+                    Format: DXGI_FORMAT_R32G32B32A32_FLOAT,
+                    InputSlot: 0,
+                    AlignedByteOffset: 8,
+                    InputSlotClass: D3D11_INPUT_PER_VERTEX_DATA,
+                    InstanceDataStepRate: 0,
+                },
+            ],
+            vertex_shader_blob,
+        )?;
+
+        Ok(DebugRenderContext {
+            input_layout,
+            vertex,
+            pixel,
+        })
+    }
+
+    /// Draws the debug overlay for the provided items. This replaces the
+    /// bound shaders and buffers, the item pipeline must be prepared again
+    /// before rendering more items
+    pub fn render<'a>(
+        &mut self,
+        device: &ID3D11Device,
+        ctx: &ID3D11DeviceContext,
+        items: impl IntoIterator<Item = &'a RenderItemDefinition>,
+        screen_aspect: f32,
+    ) -> anyhow::Result<()> {
+        let vertices = build_debug_lines(items, screen_aspect);
+        if vertices.is_empty() {
+            return Ok(());
+        }
+
+        // Debug lines change every frame, the buffer is recreated rather
+        // than kept around since the overlay is only used for debugging
+        let mut vertex_buffer = VertexBuffer::create_from_array(device, &vertices)?;
+
+        self.input_layout.bind(ctx);
+        self.vertex.set_shader(ctx);
+        self.pixel.set_shader(ctx);
+        vertex_buffer.bind(ctx);
+
+        unsafe {
+            ctx.IASetPrimitiveTopology(D3D11_PRIMITIVE_TOPOLOGY_LINELIST);
+            ctx.Draw(vertices.len() as u32, 0);
+        }
+
+        Ok(())
+    }
+}
+
+/// Creates the line list vertices for the item bounds and trajectories
+fn build_debug_lines<'a>(
+    items: impl IntoIterator<Item = &'a RenderItemDefinition>,
+    screen_aspect: f32,
+) -> Vec<DebugVertex> {
+    let mut vertices = Vec::new();
+    let mut push_line = |from: Vector2<f32>, to: Vector2<f32>, color: Vector4<f32>| {
+        vertices.push(DebugVertex { pos: from, color });
+        vertices.push(DebugVertex { pos: to, color });
+    };
+
+    for item in items {
+        // Axis aligned bounding box of the rotated quad
+        let corners = item.item_data.current_corners(screen_aspect);
+        let min = corners
+            .iter()
+            .fold(corners[0], |min, corner| min.inf(corner));
+        let max = corners
+            .iter()
+            .fold(corners[0], |max, corner| max.sup(corner));
+
+        let top_left = Vector2::new(min.x, max.y);
+        let bottom_right = Vector2::new(max.x, min.y);
+        push_line(min, top_left, BOUNDS_COLOR);
+        push_line(top_left, max, BOUNDS_COLOR);
+        push_line(max, bottom_right, BOUNDS_COLOR);
+        push_line(bottom_right, min, BOUNDS_COLOR);
+
+        // Trajectory over the lifetime of the item
+        let duration = item.item_data.duration;
+        let mut previous = item.item_data.position_at(0.0);
+        for segment in 1..=TRAJECTORY_SEGMENTS {
+            let elapsed_time = duration * segment as f32 / TRAJECTORY_SEGMENTS as f32;
+            let next = item.item_data.position_at(elapsed_time);
+            push_line(previous, next, TRAJECTORY_COLOR);
+            previous = next;
+        }
+    }
+
+    vertices
+}
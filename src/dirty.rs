@@ -0,0 +1,277 @@
+//! Dirty rectangle rendering, redraws only the part of the render target
+//! covered by items that moved or changed since the previous frame.
+//! Overlays are idle most of the time, an unchanged frame skips drawing
+//! entirely and a few moving items only redraw the area around them
+
+use std::collections::HashMap;
+
+use nalgebra::{Vector2, Vector4};
+use winapi::um::{
+    d3d11::{ID3D11Device, ID3D11DeviceContext},
+    d3dcommon::D3D11_PRIMITIVE_TOPOLOGY_TRIANGLELIST,
+};
+
+use crate::{
+    app::WorldRenderContext,
+    dx::{
+        buffer::ConstantBuffer,
+        device::{ScissorRect, Viewport},
+        shader::{PixelShader, VertexShader},
+        texture::BlendState,
+    },
+    item::{ItemId, RenderItemDefinition},
+    load_shader,
+};
+
+/// Pixels added around each item, covers the filtering and antialiasing
+/// that reaches just past the item quad
+const DIRTY_RECT_PADDING: f32 = 2.0;
+
+/// Part of the render target that must be redrawn this frame
+pub enum DirtyRegion {
+    /// Everything must be redrawn
+    Full,
+    /// Only the area within the rectangle (render target pixels) changed
+    Partial(ScissorRect),
+    /// Nothing changed, the previous frame can be sent again
+    Clean,
+}
+
+/// Pixel rectangle covered by an item
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct PixelBounds {
+    min: Vector2<f32>,
+    max: Vector2<f32>,
+}
+
+impl PixelBounds {
+    fn union(&self, other: &PixelBounds) -> PixelBounds {
+        PixelBounds {
+            min: self.min.inf(&other.min),
+            max: self.max.sup(&other.max),
+        }
+    }
+
+    /// Scissor rectangle covering the bounds, rounded outwards to whole
+    /// pixels
+    fn scissor(&self) -> ScissorRect {
+        let min = self.min.map(|edge| edge.floor() as i32);
+        let max = self.max.map(|edge| edge.ceil() as i32);
+        ScissorRect::new(min, max - min)
+    }
+}
+
+/// State of an item that affects the pixels it draws
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ItemSnapshot {
+    /// Area the item covers, [None] when the item is outside its stage
+    bounds: Option<PixelBounds>,
+    hue_phase: f32,
+    sprite_frame: u32,
+    wobble_phase: f32,
+}
+
+impl ItemSnapshot {
+    fn capture(item: &RenderItemDefinition, viewport: &Viewport) -> ItemSnapshot {
+        ItemSnapshot {
+            bounds: item_bounds(item, viewport),
+            hue_phase: item.item_data.hue_phase,
+            sprite_frame: item.item_data.sprite_frame,
+            wobble_phase: item.wobble_phase,
+        }
+    }
+}
+
+/// Tracks the items drawn in the previous frame to find the area that
+/// changed, see [DirtyTracker::update]
+pub struct DirtyTracker {
+    clear: ClearPass,
+    snapshots: HashMap<ItemId, ItemSnapshot>,
+    /// Whether the next frame must be redrawn in full
+    full_redraw: bool,
+}
+
+impl DirtyTracker {
+    /// Creates a tracker whose partial clears fill the region with
+    /// `clear_color`, the first frame is always redrawn in full
+    pub fn create(device: &ID3D11Device, clear_color: [f32; 4]) -> anyhow::Result<DirtyTracker> {
+        Ok(DirtyTracker {
+            clear: ClearPass::create(device, clear_color)?,
+            snapshots: HashMap::new(),
+            full_redraw: true,
+        })
+    }
+
+    /// Forces the next frame to be redrawn in full, needed when something
+    /// other than the items changes (e.g. the background or clip region)
+    pub fn invalidate(&mut self) {
+        self.full_redraw = true;
+    }
+
+    /// Compares the items against the previous frame and returns the region
+    /// to redraw, the items must already be updated for this frame.
+    /// `force_full` redraws everything this frame and the next (e.g. while
+    /// the debug overlay is shown)
+    pub fn update(
+        &mut self,
+        items: &[RenderItemDefinition],
+        world: &WorldRenderContext,
+        force_full: bool,
+    ) -> DirtyRegion {
+        let mut snapshots = HashMap::with_capacity(items.len());
+        let mut dirty: Option<PixelBounds> = None;
+        let mut add_dirty = |bounds: Option<PixelBounds>| {
+            if let Some(bounds) = bounds {
+                dirty = Some(match dirty {
+                    Some(dirty) => dirty.union(&bounds),
+                    None => bounds,
+                });
+            }
+        };
+
+        for item in items.iter().filter(|item| !item.delayed) {
+            let Some(viewport) = world.viewports.get(item.stage) else {
+                continue;
+            };
+
+            let snapshot = ItemSnapshot::capture(item, viewport);
+            match self.snapshots.remove(&item.id) {
+                // Custom shaders may animate in ways the snapshot can't see
+                Some(previous) if previous == snapshot && item.effect.is_none() => {}
+                Some(previous) => {
+                    add_dirty(previous.bounds);
+                    add_dirty(snapshot.bounds);
+                }
+                None => add_dirty(snapshot.bounds),
+            }
+
+            snapshots.insert(item.id, snapshot);
+        }
+
+        // Items gone since the previous frame leave their area dirty
+        for previous in self.snapshots.values() {
+            add_dirty(previous.bounds);
+        }
+        self.snapshots = snapshots;
+
+        let full_redraw = std::mem::replace(&mut self.full_redraw, force_full);
+        if full_redraw || force_full {
+            return DirtyRegion::Full;
+        }
+
+        match dirty {
+            Some(dirty) => DirtyRegion::Partial(dirty.scissor()),
+            None => DirtyRegion::Clean,
+        }
+    }
+
+    /// Fills the bound scissor region with the clear color, leaves the
+    /// clear shaders and blend state bound
+    pub fn clear(&mut self, ctx: &ID3D11DeviceContext) {
+        self.clear.draw(ctx);
+    }
+}
+
+/// Area an item covers within its stage viewport (render target pixels)
+/// including its drop shadow and wobble, [None] when the item is outside
+/// the viewport
+fn item_bounds(item: &RenderItemDefinition, viewport: &Viewport) -> Option<PixelBounds> {
+    let position = viewport.position();
+    let size = viewport.size();
+    let stage = PixelBounds {
+        min: position,
+        max: position + size,
+    };
+
+    // Custom vertex shaders can place the item anywhere in the stage
+    if item.effect.is_some() {
+        return Some(stage);
+    }
+
+    let data = &item.item_data;
+    let corners = data.current_corners(size.x / size.y);
+    let shadow_offset = match data.shadow_enabled != 0 {
+        true => data.shadow_offset,
+        false => Vector2::zeros(),
+    };
+
+    let mut min = Vector2::repeat(f32::INFINITY);
+    let mut max = Vector2::repeat(f32::NEG_INFINITY);
+    for corner in corners
+        .iter()
+        .chain(&corners.map(|corner| corner + shadow_offset))
+    {
+        min = min.inf(corner);
+        max = max.sup(corner);
+    }
+
+    // Wobble bends the edges by up to the amplitude of the item size
+    if let Some(wobble) = &item.wobble {
+        let extent = max - min;
+        let pad = extent.x.max(extent.y) * wobble.amplitude;
+        min -= Vector2::repeat(pad);
+        max += Vector2::repeat(pad);
+    }
+
+    // Normalized device coordinates to pixels, y points down in pixels
+    let to_pixels = |x: f32, y: f32| {
+        position + Vector2::new((x + 1.0) * 0.5, (1.0 - y) * 0.5).component_mul(&size)
+    };
+    let padding = Vector2::repeat(DIRTY_RECT_PADDING);
+    let min_pixels = to_pixels(min.x, max.y) - padding;
+    let max_pixels = to_pixels(max.x, min.y) + padding;
+
+    let bounds = PixelBounds {
+        min: min_pixels.sup(&stage.min),
+        max: max_pixels.inf(&stage.max),
+    };
+
+    (bounds.min.x < bounds.max.x && bounds.min.y < bounds.max.y).then_some(bounds)
+}
+
+#[repr(C, align(16))]
+struct ClearParams {
+    clear_color: Vector4<f32>,
+}
+
+/// Clears the scissor region by drawing a fullscreen triangle,
+/// ClearRenderTargetView ignores the scissor
+struct ClearPass {
+    vertex: VertexShader,
+    pixel: PixelShader,
+    params: ConstantBuffer<ClearParams>,
+    blend_state: BlendState,
+}
+
+impl ClearPass {
+    fn create(device: &ID3D11Device, clear_color: [f32; 4]) -> anyhow::Result<ClearPass> {
+        let vertex_blob = load_shader!("clear.hlsl", "vs_5_0", "VSMain")?;
+        let pixel_blob = load_shader!("clear.hlsl", "ps_5_0", "PSMain")?;
+
+        Ok(ClearPass {
+            vertex: VertexShader::create(device, vertex_blob)?,
+            pixel: PixelShader::create(device, pixel_blob)?,
+            params: ConstantBuffer::create(
+                device,
+                ClearParams {
+                    clear_color: Vector4::from(clear_color),
+                },
+            )?,
+            blend_state: BlendState::opaque_state(device)?,
+        })
+    }
+
+    fn draw(&mut self, ctx: &ID3D11DeviceContext) {
+        self.vertex.set_shader(ctx);
+        self.pixel.set_shader(ctx);
+        self.blend_state.bind(ctx);
+
+        unsafe {
+            // Vertices come from the vertex id
+            ctx.IASetInputLayout(std::ptr::null_mut());
+            ctx.IASetPrimitiveTopology(D3D11_PRIMITIVE_TOPOLOGY_TRIANGLELIST);
+            ctx.PSSetConstantBuffers(0, 1, &self.params.buffer.as_ptr());
+            ctx.Draw(3, 0);
+        }
+    }
+}
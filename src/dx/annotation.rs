@@ -0,0 +1,67 @@
+use std::{ffi::OsStr, os::windows::ffi::OsStrExt};
+
+use winapi::um::{d3d11::ID3D11DeviceContext, d3d11_1::ID3DUserDefinedAnnotation};
+
+use crate::com::ComPtr;
+
+/// GPU debug markers shown by graphics debuggers such as RenderDoc and
+/// PIX, groups the calls made for each pass so captures are readable
+pub struct Annotator {
+    annotation: ComPtr<ID3DUserDefinedAnnotation>,
+}
+
+impl Annotator {
+    /// Gets the annotation interface of the context, [None] when the
+    /// runtime doesn't support annotations (Before Windows 8)
+    pub fn from_context(ctx: &ComPtr<ID3D11DeviceContext>) -> Option<Annotator> {
+        ctx.query_interface::<ID3DUserDefinedAnnotation>()
+            .map(|annotation| Annotator { annotation })
+    }
+
+    /// Starts a named group of calls, must be paired with
+    /// [Annotator::end_event]
+    pub fn begin_event(&self, name: &str) {
+        let name = wide_string(name);
+        unsafe {
+            self.annotation.BeginEvent(name.as_ptr());
+        }
+    }
+
+    /// Ends the most recently started group
+    pub fn end_event(&self) {
+        unsafe {
+            self.annotation.EndEvent();
+        }
+    }
+
+    /// Inserts a single named marker at the current point
+    pub fn set_marker(&self, name: &str) {
+        let name = wide_string(name);
+        unsafe {
+            self.annotation.SetMarker(name.as_ptr());
+        }
+    }
+
+    /// Starts a named group of calls that ends when the returned scope
+    /// is dropped
+    pub fn scope(&self, name: &str) -> AnnotationScope<'_> {
+        self.begin_event(name);
+        AnnotationScope { annotator: self }
+    }
+}
+
+/// Group of calls started by [Annotator::scope], ends the group on drop
+pub struct AnnotationScope<'a> {
+    annotator: &'a Annotator,
+}
+
+impl Drop for AnnotationScope<'_> {
+    fn drop(&mut self) {
+        self.annotator.end_event();
+    }
+}
+
+/// Null terminated UTF-16 copy of the string
+fn wide_string(value: &str) -> Vec<u16> {
+    OsStr::new(value).encode_wide().chain([0]).collect()
+}
@@ -9,9 +9,10 @@ use winapi::{
     },
     um::d3d11::{
         ID3D11Buffer, ID3D11Device, ID3D11DeviceContext, D3D11_BIND_CONSTANT_BUFFER,
-        D3D11_BIND_INDEX_BUFFER, D3D11_BIND_VERTEX_BUFFER, D3D11_BUFFER_DESC,
-        D3D11_CPU_ACCESS_WRITE, D3D11_MAP_WRITE_DISCARD, D3D11_SUBRESOURCE_DATA,
-        D3D11_USAGE_DEFAULT, D3D11_USAGE_DYNAMIC,
+        D3D11_BIND_INDEX_BUFFER, D3D11_BIND_SHADER_RESOURCE, D3D11_BIND_VERTEX_BUFFER,
+        D3D11_BUFFER_DESC, D3D11_CPU_ACCESS_WRITE, D3D11_MAP_WRITE_DISCARD,
+        D3D11_RESOURCE_MISC_BUFFER_STRUCTURED, D3D11_SUBRESOURCE_DATA, D3D11_USAGE_DEFAULT,
+        D3D11_USAGE_DYNAMIC, D3D11_USAGE_IMMUTABLE,
     },
 };
 
@@ -35,11 +36,14 @@ where
     }
 
     pub fn create(device: &ID3D11Device, initial_data: T) -> anyhow::Result<ConstantBuffer<T>> {
-        // Const buffers must be aligned to 16 byte boundary
-        debug_assert!(
-            std::mem::size_of::<T>() % 16 == 0,
-            "constant buffer not aligned to 16 byte boundaries"
-        );
+        // Const buffers must be aligned to 16 byte boundary, checked at compile
+        // time so misaligned types can't ship in release builds
+        const {
+            assert!(
+                std::mem::size_of::<T>().is_multiple_of(16),
+                "constant buffer not aligned to 16 byte boundaries"
+            );
+        }
 
         let buffer_desc = D3D11_BUFFER_DESC {
             ByteWidth: std::mem::size_of::<T>() as u32,
@@ -93,6 +97,7 @@ where
     }
 }
 
+#[derive(Clone)]
 pub struct IndexBuffer {
     pub buffer: ComPtr<ID3D11Buffer>,
     pub format: DXGI_FORMAT,
@@ -125,6 +130,13 @@ impl IndexBuffer {
         }
     }
 
+    /// Clears the bound index buffer
+    pub fn unbind(ctx: &ID3D11DeviceContext) {
+        unsafe {
+            ctx.IASetIndexBuffer(std::ptr::null_mut(), DXGI_FORMAT_UNKNOWN, 0);
+        }
+    }
+
     pub unsafe fn create(
         device: &ID3D11Device,
         data: *const c_void,
@@ -161,6 +173,7 @@ impl IndexBuffer {
     }
 }
 
+#[derive(Clone)]
 pub struct VertexBuffer {
     pub buffer: ComPtr<ID3D11Buffer>,
     pub stride: u32,
@@ -190,6 +203,17 @@ impl VertexBuffer {
         }
     }
 
+    /// Clears the vertex buffer bound to slot 0, also used for dynamic
+    /// vertex buffers
+    pub fn unbind(ctx: &ID3D11DeviceContext) {
+        let null_buffer: *mut ID3D11Buffer = std::ptr::null_mut();
+        let (stride, offset) = (0, 0);
+
+        unsafe {
+            ctx.IASetVertexBuffers(0, 1, &null_buffer, &stride, &offset);
+        }
+    }
+
     pub unsafe fn create(
         device: &ID3D11Device,
         data: *const c_void,
@@ -225,3 +249,145 @@ impl VertexBuffer {
         })
     }
 }
+
+/// Vertex buffer the CPU rewrites each frame, used for meshes that deform
+/// over time
+#[derive(Clone)]
+pub struct DynamicVertexBuffer {
+    pub buffer: ComPtr<ID3D11Buffer>,
+    pub stride: u32,
+    /// Number of vertices the buffer can hold
+    pub capacity: u32,
+}
+
+impl DynamicVertexBuffer {
+    /// Creates the buffer holding the initial vertices, the vertex count
+    /// can't grow past the initial count
+    pub fn create<T>(device: &ID3D11Device, vertices: &[T]) -> anyhow::Result<Self>
+    where
+        T: Sized,
+    {
+        let buffer_desc = D3D11_BUFFER_DESC {
+            ByteWidth: std::mem::size_of_val(vertices) as u32,
+            Usage: D3D11_USAGE_DYNAMIC,
+            BindFlags: D3D11_BIND_VERTEX_BUFFER,
+            CPUAccessFlags: D3D11_CPU_ACCESS_WRITE,
+            MiscFlags: 0,
+            StructureByteStride: 0,
+        };
+
+        let init_data = D3D11_SUBRESOURCE_DATA {
+            pSysMem: vertices.as_ptr().cast(),
+            SysMemPitch: 0,
+            SysMemSlicePitch: 0,
+        };
+
+        let mut buffer = std::ptr::null_mut();
+        let hr = unsafe { device.CreateBuffer(&buffer_desc, &init_data, &mut buffer) };
+        hr_bail!(hr, "failed to create dynamic vertex buffer");
+
+        Ok(DynamicVertexBuffer {
+            buffer: buffer.into(),
+            stride: std::mem::size_of::<T>() as u32,
+            capacity: vertices.len() as u32,
+        })
+    }
+
+    /// Replaces the vertices, the vertex type must match the type the
+    /// buffer was created with
+    pub fn update<T>(&mut self, ctx: &ID3D11DeviceContext, vertices: &[T]) -> anyhow::Result<()>
+    where
+        T: Sized,
+    {
+        if std::mem::size_of::<T>() as u32 != self.stride {
+            anyhow::bail!(
+                "vertex size {} does not match the buffer stride {}",
+                std::mem::size_of::<T>(),
+                self.stride
+            );
+        }
+
+        if vertices.len() > self.capacity as usize {
+            anyhow::bail!(
+                "{} vertices exceed the buffer capacity of {}",
+                vertices.len(),
+                self.capacity
+            );
+        }
+
+        unsafe {
+            let mut mapped_resource = std::mem::zeroed();
+            let resource = self.buffer.cast_as_mut();
+
+            let hr = ctx.Map(
+                resource,
+                0,
+                D3D11_MAP_WRITE_DISCARD,
+                0,
+                &mut mapped_resource,
+            );
+            hr_bail!(hr, "failed to map dynamic vertex buffer");
+
+            std::ptr::copy_nonoverlapping(
+                vertices.as_ptr(),
+                mapped_resource.pData.cast(),
+                vertices.len(),
+            );
+
+            ctx.Unmap(resource, 0);
+        }
+
+        Ok(())
+    }
+
+    pub fn bind(&mut self, ctx: &ID3D11DeviceContext) {
+        let offset = 0;
+        unsafe {
+            ctx.IASetVertexBuffers(0, 1, &self.buffer.as_ptr(), &self.stride, &offset);
+        }
+    }
+}
+
+/// Read only buffer of structures, read by shaders through a
+/// [ShaderResourceView](crate::dx::shader::ShaderResourceView)
+#[derive(Clone)]
+pub struct StructuredBuffer {
+    pub buffer: ComPtr<ID3D11Buffer>,
+    /// Number of elements in the buffer
+    pub len: u32,
+}
+
+impl StructuredBuffer {
+    pub fn create_from_array<T>(device: &ID3D11Device, data: &[T]) -> anyhow::Result<Self>
+    where
+        T: Sized,
+    {
+        if data.is_empty() {
+            anyhow::bail!("structured buffer must contain at least one element");
+        }
+
+        let buffer_desc = D3D11_BUFFER_DESC {
+            ByteWidth: std::mem::size_of_val(data) as u32,
+            Usage: D3D11_USAGE_IMMUTABLE,
+            BindFlags: D3D11_BIND_SHADER_RESOURCE,
+            CPUAccessFlags: 0,
+            MiscFlags: D3D11_RESOURCE_MISC_BUFFER_STRUCTURED,
+            StructureByteStride: std::mem::size_of::<T>() as u32,
+        };
+
+        let init_data = D3D11_SUBRESOURCE_DATA {
+            pSysMem: data.as_ptr().cast(),
+            SysMemPitch: 0,
+            SysMemSlicePitch: 0,
+        };
+
+        let mut buffer = std::ptr::null_mut();
+        let hr = unsafe { device.CreateBuffer(&buffer_desc, &init_data, &mut buffer) };
+        hr_bail!(hr, "failed to create structured buffer");
+
+        Ok(StructuredBuffer {
+            buffer: buffer.into(),
+            len: data.len() as u32,
+        })
+    }
+}
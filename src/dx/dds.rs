@@ -0,0 +1,258 @@
+use winapi::shared::dxgiformat::{
+    DXGI_FORMAT, DXGI_FORMAT_B8G8R8A8_UNORM, DXGI_FORMAT_BC1_UNORM, DXGI_FORMAT_BC1_UNORM_SRGB,
+    DXGI_FORMAT_BC2_UNORM, DXGI_FORMAT_BC2_UNORM_SRGB, DXGI_FORMAT_BC3_UNORM,
+    DXGI_FORMAT_BC3_UNORM_SRGB, DXGI_FORMAT_BC4_SNORM, DXGI_FORMAT_BC4_UNORM,
+    DXGI_FORMAT_BC5_SNORM, DXGI_FORMAT_BC5_UNORM, DXGI_FORMAT_BC7_UNORM,
+    DXGI_FORMAT_BC7_UNORM_SRGB, DXGI_FORMAT_R8G8B8A8_UNORM,
+};
+
+/// Magic number at the start of every DDS file ("DDS ")
+const DDS_MAGIC: u32 = 0x2053_4444;
+/// Size of the DDS header following the magic number
+const DDS_HEADER_SIZE: usize = 124;
+/// Size of the DX10 extended header
+const DDS_DX10_HEADER_SIZE: usize = 20;
+
+/// Pixel format flag indicating the format is described by a FourCC
+const DDPF_FOURCC: u32 = 0x4;
+/// Pixel format flag indicating uncompressed RGB data
+const DDPF_RGB: u32 = 0x40;
+
+/// Header flag indicating the mip map count is valid
+const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+
+/// Caps2 flag indicating the file is a cube map
+const DDSCAPS2_CUBEMAP: u32 = 0x200;
+
+/// Resource dimension for 2D textures in the DX10 header
+const DDS_DIMENSION_TEXTURE2D: u32 = 3;
+
+/// Maximum number of mip levels of a D3D11 2D texture
+/// (D3D11_REQ_MIP_LEVELS)
+const MAX_MIP_LEVELS: u32 = 15;
+
+/// Parsed 2D DDS image, the data is left in its native format so
+/// it can be uploaded directly
+pub struct DdsImage<'a> {
+    pub width: u32,
+    pub height: u32,
+    pub format: DXGI_FORMAT,
+    pub mip_levels: u32,
+    /// Data for every mip level, largest first
+    pub data: &'a [u8],
+}
+
+impl<'a> DdsImage<'a> {
+    /// Parses a DDS file, only single 2D textures in block compressed or
+    /// 32-bit RGBA formats are supported
+    pub fn parse(data: &'a [u8]) -> anyhow::Result<DdsImage<'a>> {
+        if data.len() < 4 + DDS_HEADER_SIZE {
+            anyhow::bail!("dds data is too small to contain a header");
+        }
+
+        if read_u32(data, 0) != DDS_MAGIC {
+            anyhow::bail!("data is not a dds file");
+        }
+
+        let header = &data[4..4 + DDS_HEADER_SIZE];
+        let flags = read_u32(header, 4);
+        let height = read_u32(header, 8);
+        let width = read_u32(header, 12);
+        let mip_levels = if flags & DDSD_MIPMAPCOUNT != 0 {
+            read_u32(header, 24).max(1)
+        } else {
+            1
+        };
+
+        if width == 0 || height == 0 {
+            anyhow::bail!("dds texture has no pixels ({width}x{height})");
+        }
+
+        // Each level halves the largest dimension until it reaches 1
+        let full_chain = u32::BITS - width.max(height).leading_zeros();
+        if mip_levels > full_chain || mip_levels > MAX_MIP_LEVELS {
+            anyhow::bail!(
+                "dds texture has {mip_levels} mip levels, at most {} are possible \
+                 for {width}x{height}",
+                full_chain.min(MAX_MIP_LEVELS)
+            );
+        }
+
+        if read_u32(header, 108) & DDSCAPS2_CUBEMAP != 0 {
+            anyhow::bail!("dds cube maps are not supported");
+        }
+
+        // Pixel format starts at offset 72 in the header
+        let pf_flags = read_u32(header, 76);
+        let four_cc = &header[80..84];
+
+        let mut offset = 4 + DDS_HEADER_SIZE;
+
+        let format = if pf_flags & DDPF_FOURCC != 0 {
+            match four_cc {
+                b"DXT1" => DXGI_FORMAT_BC1_UNORM,
+                b"DXT2" | b"DXT3" => DXGI_FORMAT_BC2_UNORM,
+                b"DXT4" | b"DXT5" => DXGI_FORMAT_BC3_UNORM,
+                b"ATI1" | b"BC4U" => DXGI_FORMAT_BC4_UNORM,
+                b"BC4S" => DXGI_FORMAT_BC4_SNORM,
+                b"ATI2" | b"BC5U" => DXGI_FORMAT_BC5_UNORM,
+                b"BC5S" => DXGI_FORMAT_BC5_SNORM,
+                b"DX10" => {
+                    let dx10 =
+                        data.get(offset..offset + DDS_DX10_HEADER_SIZE)
+                            .ok_or_else(|| {
+                                anyhow::anyhow!("dds data is too small to contain the dx10 header")
+                            })?;
+                    offset += DDS_DX10_HEADER_SIZE;
+
+                    if read_u32(dx10, 4) != DDS_DIMENSION_TEXTURE2D {
+                        anyhow::bail!("only 2D dds textures are supported");
+                    }
+
+                    if read_u32(dx10, 12) > 1 {
+                        anyhow::bail!("dds texture arrays are not supported");
+                    }
+
+                    read_u32(dx10, 0)
+                }
+                _ => anyhow::bail!(
+                    "unsupported dds format {}",
+                    String::from_utf8_lossy(four_cc)
+                ),
+            }
+        } else if pf_flags & DDPF_RGB != 0 && read_u32(header, 84) == 32 {
+            // Uncompressed 32-bit, determine channel order from the masks
+            match (read_u32(header, 88), read_u32(header, 96)) {
+                (0x0000_00ff, 0x00ff_0000) => DXGI_FORMAT_R8G8B8A8_UNORM,
+                (0x00ff_0000, 0x0000_00ff) => DXGI_FORMAT_B8G8R8A8_UNORM,
+                _ => anyhow::bail!("unsupported uncompressed dds channel layout"),
+            }
+        } else {
+            anyhow::bail!("unsupported dds pixel format");
+        };
+
+        if block_size(format).is_none()
+            && format != DXGI_FORMAT_R8G8B8A8_UNORM
+            && format != DXGI_FORMAT_B8G8R8A8_UNORM
+        {
+            anyhow::bail!("unsupported dds format {format}");
+        }
+
+        let image = DdsImage {
+            width,
+            height,
+            format,
+            mip_levels,
+            data: &data[offset..],
+        };
+
+        // Ensure the data covers every mip level before it's uploaded
+        let expected: usize = (0..mip_levels)
+            .map(|level| image.level_layout(level).1)
+            .sum();
+        if image.data.len() < expected {
+            anyhow::bail!(
+                "dds data is truncated (expected {expected} bytes, got {})",
+                image.data.len()
+            );
+        }
+
+        Ok(image)
+    }
+
+    /// Row pitch and total size in bytes of the provided mip level
+    pub fn level_layout(&self, level: u32) -> (usize, usize) {
+        let width = (self.width >> level).max(1) as usize;
+        let height = (self.height >> level).max(1) as usize;
+
+        match block_size(self.format) {
+            // Block compressed formats store 4x4 blocks of pixels
+            Some(block_size) => {
+                let row_pitch = width.div_ceil(4) * block_size;
+                (row_pitch, row_pitch * height.div_ceil(4))
+            }
+            None => {
+                let row_pitch = width * 4;
+                (row_pitch, row_pitch * height)
+            }
+        }
+    }
+}
+
+/// Size in bytes of a 4x4 block for block compressed formats
+fn block_size(format: DXGI_FORMAT) -> Option<usize> {
+    match format {
+        DXGI_FORMAT_BC1_UNORM
+        | DXGI_FORMAT_BC1_UNORM_SRGB
+        | DXGI_FORMAT_BC4_UNORM
+        | DXGI_FORMAT_BC4_SNORM => Some(8),
+        DXGI_FORMAT_BC2_UNORM
+        | DXGI_FORMAT_BC2_UNORM_SRGB
+        | DXGI_FORMAT_BC3_UNORM
+        | DXGI_FORMAT_BC3_UNORM_SRGB
+        | DXGI_FORMAT_BC5_UNORM
+        | DXGI_FORMAT_BC5_SNORM
+        | DXGI_FORMAT_BC7_UNORM
+        | DXGI_FORMAT_BC7_UNORM_SRGB => Some(16),
+        _ => None,
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Uncompressed RGBA DDS file with the provided header values and
+    /// enough data for a single level
+    fn rgba_dds(width: u32, height: u32, mip_levels: u32) -> Vec<u8> {
+        let mut data = vec![0u8; 4 + DDS_HEADER_SIZE];
+        let mut write = |offset: usize, value: u32| {
+            data[offset..offset + 4].copy_from_slice(&value.to_le_bytes())
+        };
+
+        write(0, DDS_MAGIC);
+        write(4 + 4, DDSD_MIPMAPCOUNT);
+        write(4 + 8, height);
+        write(4 + 12, width);
+        write(4 + 24, mip_levels);
+        write(4 + 76, DDPF_RGB);
+        write(4 + 84, 32);
+        write(4 + 88, 0x0000_00ff);
+        write(4 + 96, 0x00ff_0000);
+
+        data.resize(data.len() + width as usize * height as usize * 4 * 2, 0);
+        data
+    }
+
+    #[test]
+    fn parses_full_mip_chain() {
+        let data = rgba_dds(4, 2, 3);
+        let image = DdsImage::parse(&data).unwrap();
+        assert_eq!(image.mip_levels, 3);
+    }
+
+    #[test]
+    fn rejects_zero_size() {
+        assert!(DdsImage::parse(&rgba_dds(0, 4, 1)).is_err());
+        assert!(DdsImage::parse(&rgba_dds(4, 0, 1)).is_err());
+    }
+
+    #[test]
+    fn rejects_excess_mip_levels() {
+        // 4x2 has levels 4x2, 2x1 and 1x1
+        assert!(DdsImage::parse(&rgba_dds(4, 2, 4)).is_err());
+        // Would shift the size by 32 or more bits
+        assert!(DdsImage::parse(&rgba_dds(4, 2, 40)).is_err());
+        // Over the D3D11 limit even though the size allows more levels
+        assert!(DdsImage::parse(&rgba_dds(1 << 16, 1, 16)).is_err());
+    }
+}
@@ -1,38 +1,249 @@
 use nalgebra::Vector2;
-use winapi::um::{
-    d3d11::{
-        D3D11CreateDevice, ID3D11Device, ID3D11DeviceContext, D3D11_SDK_VERSION, D3D11_VIEWPORT,
+use winapi::{
+    shared::{
+        dxgi::{IDXGIAdapter, IDXGIDevice},
+        dxgiformat::DXGI_FORMAT,
+        minwindef::FALSE,
+        windef::RECT,
+        winerror::E_INVALIDARG,
+    },
+    um::{
+        d3d11::{
+            D3D11CreateDevice, ID3D11Device, ID3D11DeviceContext, D3D11_CREATE_DEVICE_DEBUG,
+            D3D11_FEATURE_DATA_THREADING, D3D11_FEATURE_THREADING, D3D11_FORMAT_SUPPORT_BLENDABLE,
+            D3D11_FORMAT_SUPPORT_RENDER_TARGET, D3D11_FORMAT_SUPPORT_SHADER_SAMPLE,
+            D3D11_FORMAT_SUPPORT_TEXTURE2D, D3D11_FORMAT_SUPPORT_TYPED_UNORDERED_ACCESS_VIEW,
+            D3D11_REQ_MAXANISOTROPY, D3D11_REQ_TEXTURE2D_U_OR_V_DIMENSION, D3D11_SDK_VERSION,
+            D3D11_VIEWPORT,
+        },
+        d3dcommon::{
+            D3D_DRIVER_TYPE, D3D_DRIVER_TYPE_HARDWARE, D3D_DRIVER_TYPE_WARP, D3D_FEATURE_LEVEL,
+            D3D_FEATURE_LEVEL_10_0, D3D_FEATURE_LEVEL_11_0, D3D_FEATURE_LEVEL_11_1,
+            D3D_FEATURE_LEVEL_9_2,
+        },
     },
-    d3dcommon::{D3D_DRIVER_TYPE_HARDWARE, D3D_FEATURE_LEVEL_11_0},
 };
 
 use crate::{com::ComPtr, hr_bail};
 
+/// Feature levels requested by default, the item shaders require shader
+/// model 5 (feature level 11_0)
+pub const DEFAULT_FEATURE_LEVELS: [D3D_FEATURE_LEVEL; 2] =
+    [D3D_FEATURE_LEVEL_11_1, D3D_FEATURE_LEVEL_11_0];
+
+/// Creates the device and immediate context using the first supported
+/// feature level from `feature_levels` (most preferred first), returns
+/// the feature level obtained. `debug_layer` enables the D3D11 debug layer
+/// which reports API misuse (Requires the graphics tools to be installed).
+///
+/// Falls back to the WARP software rasterizer when no hardware device can
+/// be created (e.g. CI runners and remote sessions without a GPU), WARP
+/// supports feature level 11_1 but renders far slower
 pub fn create_device_and_context(
-) -> anyhow::Result<(ComPtr<ID3D11Device>, ComPtr<ID3D11DeviceContext>)> {
-    let feature_level = D3D_FEATURE_LEVEL_11_0;
+    debug_layer: bool,
+    feature_levels: &[D3D_FEATURE_LEVEL],
+) -> anyhow::Result<(
+    ComPtr<ID3D11Device>,
+    ComPtr<ID3D11DeviceContext>,
+    D3D_FEATURE_LEVEL,
+)> {
+    if feature_levels.is_empty() {
+        anyhow::bail!("at least one feature level must be requested");
+    }
+
+    let flags = if debug_layer {
+        D3D11_CREATE_DEVICE_DEBUG
+    } else {
+        0
+    };
 
     let mut device: *mut ID3D11Device = std::ptr::null_mut();
     let mut context: *mut ID3D11DeviceContext = std::ptr::null_mut();
+    let mut feature_level: D3D_FEATURE_LEVEL = 0;
 
-    let hr = unsafe {
+    let mut create = |driver_type: D3D_DRIVER_TYPE, feature_levels: &[D3D_FEATURE_LEVEL]| unsafe {
         D3D11CreateDevice(
             std::ptr::null_mut(),
-            D3D_DRIVER_TYPE_HARDWARE,
+            driver_type,
             std::ptr::null_mut(),
-            0,
-            &feature_level,
-            1,
+            flags,
+            feature_levels.as_ptr(),
+            feature_levels.len() as u32,
             D3D11_SDK_VERSION,
             &mut device,
-            std::ptr::null_mut(),
+            &mut feature_level,
             &mut context,
         )
     };
 
+    // Runtimes without D3D11.1 reject lists containing 11_1 outright rather
+    // than skipping it, retry without it
+    let without_11_1: Vec<D3D_FEATURE_LEVEL> = feature_levels
+        .iter()
+        .copied()
+        .filter(|&level| level != D3D_FEATURE_LEVEL_11_1)
+        .collect();
+
+    let mut create_with_fallback = |driver_type: D3D_DRIVER_TYPE| {
+        let hr = create(driver_type, feature_levels);
+        if hr == E_INVALIDARG
+            && without_11_1.len() != feature_levels.len()
+            && !without_11_1.is_empty()
+        {
+            create(driver_type, &without_11_1)
+        } else {
+            hr
+        }
+    };
+
+    let mut hr = create_with_fallback(D3D_DRIVER_TYPE_HARDWARE);
+    if hr < 0 {
+        eprintln!("failed to create hardware D3D11 device (HRESULT {hr:#010x}), using WARP");
+        hr = create_with_fallback(D3D_DRIVER_TYPE_WARP);
+    }
+
     hr_bail!(hr, "failed to create D3D11 device and context");
 
-    Ok((device.into(), context.into()))
+    Ok((device.into(), context.into(), feature_level))
+}
+
+/// Support for a texture format on the device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormatSupport {
+    pub format: DXGI_FORMAT,
+    /// Can be used for 2D textures
+    pub texture2d: bool,
+    /// Can be sampled in shaders
+    pub shader_sample: bool,
+    /// Can be rendered into
+    pub render_target: bool,
+    /// Can be blended into when used as a render target
+    pub blendable: bool,
+    /// Can be written from compute shaders through a typed UAV
+    pub typed_unordered_access: bool,
+}
+
+/// Capabilities of the device, used to gate optional features on what the
+/// hardware actually supports
+#[derive(Debug, Clone)]
+pub struct DeviceCapabilities {
+    /// Feature level obtained when creating the device
+    pub feature_level: D3D_FEATURE_LEVEL,
+    /// Largest width or height of a 2D texture
+    pub max_texture_dimension: u32,
+    /// Largest anisotropy supported by samplers
+    pub max_anisotropy: u32,
+    /// Whether the driver supports creating resources from multiple threads
+    pub concurrent_creates: bool,
+    /// Whether the driver natively supports deferred command lists
+    pub command_lists: bool,
+    /// Support for each of the queried formats
+    pub formats: Vec<FormatSupport>,
+}
+
+impl DeviceCapabilities {
+    /// Queries the capabilities of the device along with the support for
+    /// each of the provided formats
+    pub fn query(device: &ID3D11Device, formats: &[DXGI_FORMAT]) -> DeviceCapabilities {
+        let feature_level = unsafe { device.GetFeatureLevel() };
+
+        // Maximum texture size is fixed by the feature level
+        let max_texture_dimension = if feature_level >= D3D_FEATURE_LEVEL_11_0 {
+            D3D11_REQ_TEXTURE2D_U_OR_V_DIMENSION
+        } else if feature_level >= D3D_FEATURE_LEVEL_10_0 {
+            8192
+        } else {
+            2048
+        };
+
+        let max_anisotropy = supported_max_anisotropy(feature_level);
+
+        // Left unsupported if the query fails
+        let mut threading = D3D11_FEATURE_DATA_THREADING {
+            DriverConcurrentCreates: FALSE,
+            DriverCommandLists: FALSE,
+        };
+        unsafe {
+            device.CheckFeatureSupport(
+                D3D11_FEATURE_THREADING,
+                (&mut threading as *mut D3D11_FEATURE_DATA_THREADING).cast(),
+                std::mem::size_of::<D3D11_FEATURE_DATA_THREADING>() as u32,
+            );
+        }
+
+        let formats = formats
+            .iter()
+            .map(|&format| {
+                let mut support = 0;
+                let hr = unsafe { device.CheckFormatSupport(format, &mut support) };
+
+                // Formats the device doesn't know about fail the check
+                if hr < 0 {
+                    support = 0;
+                }
+
+                FormatSupport {
+                    format,
+                    texture2d: support & D3D11_FORMAT_SUPPORT_TEXTURE2D != 0,
+                    shader_sample: support & D3D11_FORMAT_SUPPORT_SHADER_SAMPLE != 0,
+                    render_target: support & D3D11_FORMAT_SUPPORT_RENDER_TARGET != 0,
+                    blendable: support & D3D11_FORMAT_SUPPORT_BLENDABLE != 0,
+                    typed_unordered_access: support
+                        & D3D11_FORMAT_SUPPORT_TYPED_UNORDERED_ACCESS_VIEW
+                        != 0,
+                }
+            })
+            .collect();
+
+        DeviceCapabilities {
+            feature_level,
+            max_texture_dimension,
+            max_anisotropy,
+            concurrent_creates: threading.DriverConcurrentCreates != FALSE,
+            command_lists: threading.DriverCommandLists != FALSE,
+            formats,
+        }
+    }
+
+    /// Support for the format, [None] if the format wasn't queried
+    pub fn format_support(&self, format: DXGI_FORMAT) -> Option<&FormatSupport> {
+        self.formats.iter().find(|support| support.format == format)
+    }
+}
+
+/// Dedicated video memory of the adapter the device was created on in
+/// bytes, [None] if the adapter can't be queried. Integrated adapters
+/// may report little or no dedicated memory
+pub fn adapter_video_memory(device: &ComPtr<ID3D11Device>) -> Option<usize> {
+    let dxgi_device = device.query_interface::<IDXGIDevice>()?;
+
+    unsafe {
+        let mut adapter: *mut IDXGIAdapter = std::ptr::null_mut();
+        if dxgi_device.GetAdapter(&mut adapter) < 0 {
+            return None;
+        }
+        let adapter: ComPtr<IDXGIAdapter> = adapter.into();
+
+        let mut desc = std::mem::zeroed();
+        if adapter.GetDesc(&mut desc) < 0 {
+            return None;
+        }
+
+        Some(desc.DedicatedVideoMemory)
+    }
+}
+
+/// Largest anisotropy at feature level 9_1 (D3D_FL9_1_DEFAULT_MAX_ANISOTROPY)
+const FL9_1_MAX_ANISOTROPY: u32 = 2;
+
+/// Largest sampler anisotropy supported at the feature level, D3D11 has
+/// no query for this so it comes from the feature level requirements
+pub fn supported_max_anisotropy(feature_level: D3D_FEATURE_LEVEL) -> u32 {
+    if feature_level >= D3D_FEATURE_LEVEL_9_2 {
+        D3D11_REQ_MAXANISOTROPY
+    } else {
+        FL9_1_MAX_ANISOTROPY
+    }
 }
 
 pub struct Viewport {
@@ -40,11 +251,13 @@ pub struct Viewport {
 }
 
 impl Viewport {
-    pub fn new(size: Vector2<f32>, depth: Vector2<f32>) -> Viewport {
+    /// Creates a viewport covering `size` pixels from the `position` top
+    /// left offset within the render target
+    pub fn new(position: Vector2<f32>, size: Vector2<f32>, depth: Vector2<f32>) -> Viewport {
         Viewport {
             inner: D3D11_VIEWPORT {
-                TopLeftX: 0.0,
-                TopLeftY: 0.0,
+                TopLeftX: position.x,
+                TopLeftY: position.y,
                 Width: size.x,
                 Height: size.y,
                 MinDepth: depth.x,
@@ -53,9 +266,111 @@ impl Viewport {
         }
     }
 
+    /// Creates a viewport covering a render target of `size` pixels using
+    /// the full depth range
+    pub fn fullscreen(size: Vector2<f32>) -> Viewport {
+        Self::region(Vector2::zeros(), size)
+    }
+
+    /// Creates a viewport covering `size` pixels from the `position` top
+    /// left offset using the full depth range
+    pub fn region(position: Vector2<f32>, size: Vector2<f32>) -> Viewport {
+        Self::new(position, size, Vector2::new(0.0, 1.0))
+    }
+
+    /// Creates a viewport covering a render target of `size` pixels that
+    /// maps depth into the `min` to `max` range (within 0 to 1)
+    pub fn with_depth(size: Vector2<f32>, min: f32, max: f32) -> anyhow::Result<Viewport> {
+        if min > max {
+            anyhow::bail!("viewport min depth must not exceed max depth (got {min} > {max})");
+        }
+
+        if !(0.0..=1.0).contains(&min) || !(0.0..=1.0).contains(&max) {
+            anyhow::bail!("viewport depth must be within 0 to 1 (got {min} to {max})");
+        }
+
+        Ok(Self::new(Vector2::zeros(), size, Vector2::new(min, max)))
+    }
+
+    /// Top left offset of the viewport in pixels
+    pub fn position(&self) -> Vector2<f32> {
+        Vector2::new(self.inner.TopLeftX, self.inner.TopLeftY)
+    }
+
+    /// Size of the viewport in pixels
+    pub fn size(&self) -> Vector2<f32> {
+        Vector2::new(self.inner.Width, self.inner.Height)
+    }
+
+    /// Copy of the viewport with the position and size multiplied by
+    /// `scale`, maps a logical viewport onto a supersampled render target
+    pub fn scaled(&self, scale: f32) -> Viewport {
+        Self::new(
+            self.position() * scale,
+            self.size() * scale,
+            Vector2::new(self.inner.MinDepth, self.inner.MaxDepth),
+        )
+    }
+
     pub fn bind(&self, ctx: &ID3D11DeviceContext) {
         unsafe {
             ctx.RSSetViewports(1, &self.inner);
         }
     }
 }
+
+/// Scissor rectangle, pixels outside the rectangle are discarded
+/// when a rasterizer state with scissor enabled is bound
+pub struct ScissorRect {
+    inner: RECT,
+}
+
+impl ScissorRect {
+    pub fn new(position: Vector2<i32>, size: Vector2<i32>) -> ScissorRect {
+        ScissorRect {
+            inner: RECT {
+                left: position.x,
+                top: position.y,
+                right: position.x + size.x,
+                bottom: position.y + size.y,
+            },
+        }
+    }
+
+    /// Copy of the rectangle with the edges multiplied by `scale`, rounded
+    /// outwards to whole pixels
+    pub fn scaled(&self, scale: f32) -> ScissorRect {
+        let scale_edge = |edge: i32, round: fn(f32) -> f32| round(edge as f32 * scale) as i32;
+
+        ScissorRect {
+            inner: RECT {
+                left: scale_edge(self.inner.left, f32::floor),
+                top: scale_edge(self.inner.top, f32::floor),
+                right: scale_edge(self.inner.right, f32::ceil),
+                bottom: scale_edge(self.inner.bottom, f32::ceil),
+            },
+        }
+    }
+
+    /// Area covered by both rectangles, empty (nothing passes the
+    /// scissor test) when they don't overlap
+    pub fn intersection(&self, other: &ScissorRect) -> ScissorRect {
+        let left = self.inner.left.max(other.inner.left);
+        let top = self.inner.top.max(other.inner.top);
+
+        ScissorRect {
+            inner: RECT {
+                left,
+                top,
+                right: self.inner.right.min(other.inner.right).max(left),
+                bottom: self.inner.bottom.min(other.inner.bottom).max(top),
+            },
+        }
+    }
+
+    pub fn bind(&self, ctx: &ID3D11DeviceContext) {
+        unsafe {
+            ctx.RSSetScissorRects(1, &self.inner);
+        }
+    }
+}
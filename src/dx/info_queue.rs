@@ -0,0 +1,64 @@
+use std::ffi::CStr;
+
+use winapi::um::{
+    d3d11::ID3D11Device,
+    d3d11sdklayers::{ID3D11InfoQueue, D3D11_MESSAGE, D3D11_MESSAGE_SEVERITY_WARNING},
+};
+
+use crate::com::ComPtr;
+
+/// Messages reported by the D3D11 debug layer, only available when the
+/// device was created with the debug layer enabled
+pub struct InfoQueue {
+    queue: ComPtr<ID3D11InfoQueue>,
+}
+
+impl InfoQueue {
+    /// Gets the info queue for the device, [None] when the device was
+    /// created without the debug layer
+    pub fn from_device(device: &ComPtr<ID3D11Device>) -> Option<InfoQueue> {
+        device
+            .query_interface::<ID3D11InfoQueue>()
+            .map(|queue| InfoQueue { queue })
+    }
+
+    /// Takes the stored warning and error messages, the queue is cleared
+    /// so each message is only reported once
+    pub fn take_warnings(&mut self) -> Vec<String> {
+        let mut messages = Vec::new();
+
+        unsafe {
+            let count = self.queue.GetNumStoredMessagesAllowedByRetrievalFilter();
+            for index in 0..count {
+                // Query the size of the message first
+                let mut length = 0;
+                let hr = self
+                    .queue
+                    .GetMessage(index, std::ptr::null_mut(), &mut length);
+                if hr < 0 || length == 0 {
+                    continue;
+                }
+
+                // Message is followed by its description, use an aligned buffer
+                let mut buffer = vec![0u64; length.div_ceil(8)];
+                let message = buffer.as_mut_ptr().cast::<D3D11_MESSAGE>();
+                let hr = self.queue.GetMessage(index, message, &mut length);
+                if hr < 0 {
+                    continue;
+                }
+
+                let message = &*message;
+                if message.Severity > D3D11_MESSAGE_SEVERITY_WARNING {
+                    continue;
+                }
+
+                let description = CStr::from_ptr(message.pDescription);
+                messages.push(description.to_string_lossy().into_owned());
+            }
+
+            self.queue.ClearStoredMessages();
+        }
+
+        messages
+    }
+}
@@ -0,0 +1,149 @@
+use winapi::{
+    shared::dxgiformat::DXGI_FORMAT_R32_UINT,
+    um::{
+        d3d11::{ID3D11Device, ID3D11DeviceContext},
+        d3dcommon::D3D_PRIMITIVE_TOPOLOGY,
+    },
+};
+
+use crate::dx::buffer::{DynamicVertexBuffer, IndexBuffer, VertexBuffer};
+
+/// Vertex storage of a mesh
+#[derive(Clone)]
+enum MeshVertices {
+    /// Vertices fixed at creation
+    Static(VertexBuffer),
+    /// Vertices the CPU can rewrite, see [Mesh::update_vertices]
+    Dynamic(DynamicVertexBuffer),
+}
+
+/// Geometry made up of vertices and 32-bit indices into them, drawn using
+/// the primitive topology. Clones share the same buffers
+#[derive(Clone)]
+pub struct Mesh {
+    vertices: MeshVertices,
+    index_buffer: IndexBuffer,
+    index_count: u32,
+    topology: D3D_PRIMITIVE_TOPOLOGY,
+}
+
+impl Mesh {
+    /// Creates a mesh from the provided vertices and indices, the vertex
+    /// type must match the input layout of the shader drawing the mesh
+    pub fn create<V>(
+        device: &ID3D11Device,
+        vertices: &[V],
+        indices: &[u32],
+        topology: D3D_PRIMITIVE_TOPOLOGY,
+    ) -> anyhow::Result<Mesh> {
+        validate(vertices, indices)?;
+
+        let vertex_buffer = VertexBuffer::create_from_array(device, vertices)?;
+        let index_buffer = IndexBuffer::create_from_array(device, indices, DXGI_FORMAT_R32_UINT)?;
+
+        Ok(Mesh {
+            vertices: MeshVertices::Static(vertex_buffer),
+            index_buffer,
+            index_count: indices.len() as u32,
+            topology,
+        })
+    }
+
+    /// Creates a mesh whose vertices can be replaced each frame using
+    /// [Mesh::update_vertices], the indices stay fixed
+    pub fn create_dynamic<V>(
+        device: &ID3D11Device,
+        vertices: &[V],
+        indices: &[u32],
+        topology: D3D_PRIMITIVE_TOPOLOGY,
+    ) -> anyhow::Result<Mesh> {
+        validate(vertices, indices)?;
+
+        let vertex_buffer = DynamicVertexBuffer::create(device, vertices)?;
+        let index_buffer = IndexBuffer::create_from_array(device, indices, DXGI_FORMAT_R32_UINT)?;
+
+        Ok(Mesh {
+            vertices: MeshVertices::Dynamic(vertex_buffer),
+            index_buffer,
+            index_count: indices.len() as u32,
+            topology,
+        })
+    }
+
+    /// Replaces the vertices of a dynamic mesh, the count and type must
+    /// match the vertices the mesh was created with
+    pub fn update_vertices<V>(
+        &mut self,
+        ctx: &ID3D11DeviceContext,
+        vertices: &[V],
+    ) -> anyhow::Result<()> {
+        let MeshVertices::Dynamic(vertex_buffer) = &mut self.vertices else {
+            anyhow::bail!("mesh vertices are static, create the mesh with Mesh::create_dynamic");
+        };
+
+        if vertices.len() != vertex_buffer.capacity as usize {
+            anyhow::bail!(
+                "mesh has {} vertices but {} were provided",
+                vertex_buffer.capacity,
+                vertices.len()
+            );
+        }
+
+        vertex_buffer.update(ctx, vertices)
+    }
+
+    /// Number of indices drawn
+    pub fn index_count(&self) -> u32 {
+        self.index_count
+    }
+
+    /// Topology the indices are drawn with
+    pub fn topology(&self) -> D3D_PRIMITIVE_TOPOLOGY {
+        self.topology
+    }
+
+    /// Binds the buffers and topology for drawing
+    pub fn bind(&mut self, ctx: &ID3D11DeviceContext) {
+        match &mut self.vertices {
+            MeshVertices::Static(vertex_buffer) => vertex_buffer.bind(ctx),
+            MeshVertices::Dynamic(vertex_buffer) => vertex_buffer.bind(ctx),
+        }
+        self.index_buffer.bind(ctx);
+
+        unsafe {
+            ctx.IASetPrimitiveTopology(self.topology);
+        }
+    }
+
+    /// Clears the bound vertex and index buffers
+    pub fn unbind(ctx: &ID3D11DeviceContext) {
+        VertexBuffer::unbind(ctx);
+        IndexBuffer::unbind(ctx);
+    }
+
+    /// Draws the mesh, [Mesh::bind] must be called first
+    pub fn draw(&self, ctx: &ID3D11DeviceContext) {
+        unsafe {
+            ctx.DrawIndexed(self.index_count, 0, 0);
+        }
+    }
+}
+
+/// Checks the mesh has geometry and every index refers to a vertex
+fn validate<V>(vertices: &[V], indices: &[u32]) -> anyhow::Result<()> {
+    if vertices.is_empty() || indices.is_empty() {
+        anyhow::bail!("mesh must have at least one vertex and index");
+    }
+
+    if let Some(index) = indices
+        .iter()
+        .find(|&&index| index as usize >= vertices.len())
+    {
+        anyhow::bail!(
+            "mesh index {index} is out of range for {} vertices",
+            vertices.len()
+        );
+    }
+
+    Ok(())
+}
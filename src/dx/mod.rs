@@ -1,6 +1,11 @@
+pub mod annotation;
 pub mod buffer;
+pub mod dds;
 pub mod device;
 pub mod error;
+pub mod info_queue;
+pub mod mesh;
+pub mod rasterizer;
 pub mod sampler;
 pub mod shader;
 pub mod texture;
@@ -0,0 +1,80 @@
+use winapi::{
+    shared::minwindef::{FALSE, TRUE},
+    um::d3d11::{
+        ID3D11Device, ID3D11DeviceContext, ID3D11RasterizerState, D3D11_CULL_BACK, D3D11_CULL_MODE,
+        D3D11_CULL_NONE, D3D11_FILL_MODE, D3D11_FILL_SOLID, D3D11_FILL_WIREFRAME,
+        D3D11_RASTERIZER_DESC,
+    },
+};
+
+use crate::{com::ComPtr, hr_bail};
+
+/// Rasterizer state (Defines how primitives are rasterized)
+pub struct RasterizerState {
+    state: ComPtr<ID3D11RasterizerState>,
+}
+
+impl RasterizerState {
+    /// Rasterizer state matching the D3D11 defaults (back-face culling, solid fill)
+    pub fn default(device: &ID3D11Device) -> anyhow::Result<RasterizerState> {
+        Self::create(device, D3D11_FILL_SOLID, D3D11_CULL_BACK, false)
+    }
+
+    /// Rasterizer state without culling, used for double-sided sprites
+    /// that remain visible when flipped past 90°
+    pub fn no_cull(device: &ID3D11Device) -> anyhow::Result<RasterizerState> {
+        Self::create(device, D3D11_FILL_SOLID, D3D11_CULL_NONE, false)
+    }
+
+    /// Rasterizer state that only draws primitive edges, for debugging
+    pub fn wireframe(device: &ID3D11Device) -> anyhow::Result<RasterizerState> {
+        Self::create(device, D3D11_FILL_WIREFRAME, D3D11_CULL_NONE, false)
+    }
+
+    /// Rasterizer state that clips anything outside the bound scissor rect
+    pub fn scissor(device: &ID3D11Device) -> anyhow::Result<RasterizerState> {
+        Self::create(device, D3D11_FILL_SOLID, D3D11_CULL_NONE, true)
+    }
+
+    fn create(
+        device: &ID3D11Device,
+        fill_mode: D3D11_FILL_MODE,
+        cull_mode: D3D11_CULL_MODE,
+        scissor: bool,
+    ) -> anyhow::Result<RasterizerState> {
+        let rasterizer_desc = D3D11_RASTERIZER_DESC {
+            FillMode: fill_mode,
+            CullMode: cull_mode,
+            FrontCounterClockwise: FALSE,
+            DepthBias: 0,
+            DepthBiasClamp: 0.0,
+            SlopeScaledDepthBias: 0.0,
+            DepthClipEnable: TRUE,
+            ScissorEnable: if scissor { TRUE } else { FALSE },
+            MultisampleEnable: FALSE,
+            AntialiasedLineEnable: FALSE,
+        };
+
+        let mut state = std::ptr::null_mut();
+        let hr = unsafe { device.CreateRasterizerState(&rasterizer_desc, &mut state) };
+
+        hr_bail!(hr, "failed to create rasterizer state");
+
+        Ok(Self {
+            state: state.into(),
+        })
+    }
+
+    pub fn bind(&mut self, ctx: &ID3D11DeviceContext) {
+        unsafe {
+            ctx.RSSetState(self.state.as_ptr());
+        }
+    }
+
+    /// Restores the default rasterizer state
+    pub fn unbind(ctx: &ID3D11DeviceContext) {
+        unsafe {
+            ctx.RSSetState(std::ptr::null_mut());
+        }
+    }
+}
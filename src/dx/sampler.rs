@@ -1,10 +1,33 @@
 use winapi::um::d3d11::{
-    ID3D11Device, ID3D11DeviceContext, ID3D11SamplerState, D3D11_COMPARISON_ALWAYS,
-    D3D11_COMPARISON_NEVER, D3D11_FILTER_MIN_MAG_MIP_LINEAR, D3D11_FILTER_MIN_MAG_MIP_POINT,
-    D3D11_FLOAT32_MAX, D3D11_SAMPLER_DESC, D3D11_TEXTURE_ADDRESS_WRAP,
+    ID3D11Device, ID3D11DeviceContext, ID3D11SamplerState, D3D11_COMPARISON_NEVER, D3D11_FILTER,
+    D3D11_FILTER_ANISOTROPIC, D3D11_FILTER_MIN_MAG_MIP_LINEAR, D3D11_FILTER_MIN_MAG_MIP_POINT,
+    D3D11_FLOAT32_MAX, D3D11_SAMPLER_DESC, D3D11_TEXTURE_ADDRESS_BORDER,
+    D3D11_TEXTURE_ADDRESS_MODE, D3D11_TEXTURE_ADDRESS_WRAP,
 };
 
-use crate::{com::ComPtr, hr_bail};
+use crate::{com::ComPtr, dx::device::supported_max_anisotropy, hr_bail};
+
+/// Level of detail settings controlling which mip levels are sampled
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SamplerLod {
+    /// Offset added to the computed mip level, negative values sharpen
+    /// scaled down textures and positive values soften them
+    pub bias: f32,
+    /// Most detailed mip level that can be sampled (0 is the full size)
+    pub min: f32,
+    /// Least detailed mip level that can be sampled
+    pub max: f32,
+}
+
+impl Default for SamplerLod {
+    fn default() -> Self {
+        Self {
+            bias: 0.0,
+            min: 0.0,
+            max: D3D11_FLOAT32_MAX,
+        }
+    }
+}
 
 /// Texture sampler (Defines how textures are sampled and filtered)
 pub struct SamplerState {
@@ -13,47 +36,105 @@ pub struct SamplerState {
 
 impl SamplerState {
     pub fn linear(device: &ID3D11Device) -> anyhow::Result<SamplerState> {
-        let sampler_desc = D3D11_SAMPLER_DESC {
-            Filter: D3D11_FILTER_MIN_MAG_MIP_LINEAR,
-            AddressU: D3D11_TEXTURE_ADDRESS_WRAP,
-            AddressV: D3D11_TEXTURE_ADDRESS_WRAP,
-            AddressW: D3D11_TEXTURE_ADDRESS_WRAP,
-            MipLODBias: 0.0,
-            MaxAnisotropy: 1,
-            ComparisonFunc: D3D11_COMPARISON_ALWAYS,
-            BorderColor: [0.0, 0.0, 0.0, 0.0],
-            MinLOD: 0.0,
-            MaxLOD: D3D11_FLOAT32_MAX,
-        };
+        Self::with_lod_bias(
+            device,
+            D3D11_FILTER_MIN_MAG_MIP_LINEAR,
+            SamplerLod::default(),
+        )
+    }
 
-        let mut state = std::ptr::null_mut();
-        let hr = unsafe { device.CreateSamplerState(&sampler_desc, &mut state) };
+    pub fn pixelate(device: &ID3D11Device) -> anyhow::Result<SamplerState> {
+        Self::with_lod_bias(
+            device,
+            D3D11_FILTER_MIN_MAG_MIP_POINT,
+            SamplerLod::default(),
+        )
+    }
 
-        hr_bail!(hr, "failed to create linear sampler");
+    /// Creates a wrapping sampler using the provided filter and level of
+    /// detail settings
+    pub fn with_lod_bias(
+        device: &ID3D11Device,
+        filter: D3D11_FILTER,
+        lod: SamplerLod,
+    ) -> anyhow::Result<SamplerState> {
+        Self::create(device, filter, lod, 1, D3D11_TEXTURE_ADDRESS_WRAP, [0.0; 4])
+    }
 
-        Ok(Self {
-            state: state.into(),
-        })
+    /// Creates a linear sampler that returns `color` (RGBA, 0-1) for
+    /// coordinates outside the texture instead of wrapping or clamping to
+    /// the edge texel, gives a colored frame around rotated sprites
+    pub fn border(device: &ID3D11Device, color: [f32; 4]) -> anyhow::Result<SamplerState> {
+        Self::create(
+            device,
+            D3D11_FILTER_MIN_MAG_MIP_LINEAR,
+            SamplerLod::default(),
+            1,
+            D3D11_TEXTURE_ADDRESS_BORDER,
+            color,
+        )
     }
 
-    pub fn pixelate(device: &ID3D11Device) -> anyhow::Result<SamplerState> {
+    /// Creates a wrapping anisotropic sampler, `max_anisotropy` is clamped
+    /// to the range supported by the device (logging a warning when it
+    /// is), returns the sampler along with the effective anisotropy
+    pub fn anisotropic(
+        device: &ID3D11Device,
+        max_anisotropy: u32,
+        lod: SamplerLod,
+    ) -> anyhow::Result<(SamplerState, u32)> {
+        let supported = supported_max_anisotropy(unsafe { device.GetFeatureLevel() });
+        let effective = max_anisotropy.clamp(1, supported);
+        if effective != max_anisotropy {
+            eprintln!(
+                "requested anisotropy {max_anisotropy}x is not supported by the device, using {effective}x"
+            );
+        }
+
+        let sampler = Self::create(
+            device,
+            D3D11_FILTER_ANISOTROPIC,
+            lod,
+            effective,
+            D3D11_TEXTURE_ADDRESS_WRAP,
+            [0.0; 4],
+        )?;
+        Ok((sampler, effective))
+    }
+
+    fn create(
+        device: &ID3D11Device,
+        filter: D3D11_FILTER,
+        lod: SamplerLod,
+        max_anisotropy: u32,
+        address: D3D11_TEXTURE_ADDRESS_MODE,
+        border_color: [f32; 4],
+    ) -> anyhow::Result<SamplerState> {
+        if lod.min > lod.max {
+            anyhow::bail!(
+                "sampler min lod must not exceed max lod (got {} > {})",
+                lod.min,
+                lod.max
+            );
+        }
+
         let sampler_desc = D3D11_SAMPLER_DESC {
-            Filter: D3D11_FILTER_MIN_MAG_MIP_POINT,
-            AddressU: D3D11_TEXTURE_ADDRESS_WRAP,
-            AddressV: D3D11_TEXTURE_ADDRESS_WRAP,
-            AddressW: D3D11_TEXTURE_ADDRESS_WRAP,
-            MipLODBias: 0.0,
-            MaxAnisotropy: 0,
+            Filter: filter,
+            AddressU: address,
+            AddressV: address,
+            AddressW: address,
+            MipLODBias: lod.bias,
+            MaxAnisotropy: max_anisotropy,
             ComparisonFunc: D3D11_COMPARISON_NEVER,
-            BorderColor: [0.0, 0.0, 0.0, 0.0],
-            MinLOD: 0.0,
-            MaxLOD: D3D11_FLOAT32_MAX,
+            BorderColor: border_color,
+            MinLOD: lod.min,
+            MaxLOD: lod.max,
         };
 
         let mut state = std::ptr::null_mut();
         let hr = unsafe { device.CreateSamplerState(&sampler_desc, &mut state) };
 
-        hr_bail!(hr, "failed to create pixelate sampler");
+        hr_bail!(hr, "failed to create sampler");
 
         Ok(Self {
             state: state.into(),
@@ -66,9 +147,12 @@ impl SamplerState {
         }
     }
 
+    /// Unbinds the sampler from slot 0
     pub fn unbind(&self, ctx: &ID3D11DeviceContext) {
+        let null_sampler: *mut ID3D11SamplerState = std::ptr::null_mut();
+
         unsafe {
-            ctx.PSSetSamplers(0, 0, std::ptr::null());
+            ctx.PSSetSamplers(0, 1, &null_sampler);
         }
     }
 }
@@ -1,27 +1,48 @@
+#[cfg(runtime_shaders)]
 use std::ffi::CString;
+use std::{borrow::Cow, path::Path};
+
+use nalgebra::Vector2;
+#[cfg(runtime_shaders)]
+use winapi::um::d3dcompiler::{
+    D3DCompile, D3DCOMPILE_ENABLE_STRICTNESS, D3D_COMPILE_STANDARD_FILE_INCLUDE,
+};
 use winapi::{
-    shared::winerror::FAILED,
+    shared::{dxgiformat::DXGI_FORMAT_UNKNOWN, winerror::FAILED},
     um::{
         d3d11::{
-            ID3D11Device, ID3D11DeviceContext, ID3D11InputLayout, ID3D11PixelShader,
-            ID3D11Resource, ID3D11ShaderResourceView, ID3D11VertexShader, D3D11_INPUT_ELEMENT_DESC,
+            ID3D11ComputeShader, ID3D11Device, ID3D11DeviceContext, ID3D11InputLayout,
+            ID3D11PixelShader, ID3D11Resource, ID3D11ShaderResourceView, ID3D11UnorderedAccessView,
+            ID3D11VertexShader, D3D11_INPUT_ELEMENT_DESC, D3D11_SHADER_RESOURCE_VIEW_DESC,
         },
-        d3dcommon::ID3D10Blob,
-        d3dcompiler::{D3DCompile, D3DCOMPILE_ENABLE_STRICTNESS},
+        d3dcommon::{ID3D10Blob, D3D11_SRV_DIMENSION_BUFFER},
     },
 };
 
-use crate::{com::ComPtr, hr_bail};
+use crate::{com::ComPtr, dx::buffer::StructuredBuffer, hr_bail};
 
 /// Compiled shader blob
 #[derive(Clone)]
-#[repr(transparent)]
-pub struct ShaderBlob(pub ComPtr<ID3D10Blob>);
+pub enum ShaderBlob {
+    /// Shader compiled at runtime
+    Compiled(ComPtr<ID3D10Blob>),
+    /// Precompiled shader bytecode
+    Bytecode(Cow<'static, [u8]>),
+}
 
 impl ShaderBlob {
-    pub fn compile(src: &[u8], target: &str, entrypoint: &str) -> anyhow::Result<ShaderBlob> {
+    /// Compiles shader source, `source_path` is used to resolve `#include`
+    /// directives relative to the shader file
+    #[cfg(runtime_shaders)]
+    pub fn compile(
+        src: &[u8],
+        source_path: &str,
+        target: &str,
+        entrypoint: &str,
+    ) -> anyhow::Result<ShaderBlob> {
         let mut blob = std::ptr::null_mut();
 
+        let source_path_c = CString::new(source_path)?;
         let target_c = CString::new(target)?;
         let entrypoint_c = CString::new(entrypoint)?;
 
@@ -29,9 +50,9 @@ impl ShaderBlob {
             D3DCompile(
                 src.as_ptr().cast(),
                 src.len(),
+                source_path_c.as_ptr(),
                 std::ptr::null(),
-                std::ptr::null(),
-                std::ptr::null_mut(),
+                D3D_COMPILE_STANDARD_FILE_INCLUDE,
                 entrypoint_c.as_ptr(),
                 target_c.as_ptr(),
                 D3DCOMPILE_ENABLE_STRICTNESS,
@@ -46,8 +67,95 @@ impl ShaderBlob {
             ));
         }
 
-        Ok(ShaderBlob(blob.into()))
+        Ok(ShaderBlob::Compiled(blob.into()))
+    }
+
+    /// Creates a shader blob from precompiled shader bytecode (.cso)
+    /// without compiling, the bytecode is validated before use
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<ShaderBlob> {
+        validate_bytecode(bytes)?;
+        Ok(ShaderBlob::Bytecode(Cow::Owned(bytes.to_vec())))
     }
+
+    /// Creates a shader blob from bytecode embedded in the binary
+    pub fn from_static(bytes: &'static [u8]) -> anyhow::Result<ShaderBlob> {
+        validate_bytecode(bytes)?;
+        Ok(ShaderBlob::Bytecode(Cow::Borrowed(bytes)))
+    }
+
+    /// Loads precompiled shader bytecode from a file
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> anyhow::Result<ShaderBlob> {
+        let bytes = std::fs::read(path)?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Saves the compiled shader bytecode to a file
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        std::fs::write(path, self.as_bytes())?;
+        Ok(())
+    }
+
+    /// Compiled shader bytecode
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            ShaderBlob::Compiled(blob) => unsafe {
+                std::slice::from_raw_parts(
+                    blob.GetBufferPointer().cast::<u8>(),
+                    blob.GetBufferSize(),
+                )
+            },
+            ShaderBlob::Bytecode(bytes) => bytes,
+        }
+    }
+}
+
+/// Loads a shader from `src/shaders`. By default the shader is compiled
+/// ahead of time by build.rs and embedded, with the `runtime-shaders`
+/// feature (or when build.rs can't find fxc) the shader is compiled from
+/// source at runtime instead
+#[macro_export]
+macro_rules! load_shader {
+    ($file:literal, $target:literal, $entrypoint:literal) => {{
+        #[cfg(runtime_shaders)]
+        let blob = $crate::dx::shader::ShaderBlob::compile(
+            include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/shaders/", $file)),
+            concat!(env!("CARGO_MANIFEST_DIR"), "/src/shaders/", $file),
+            $target,
+            $entrypoint,
+        );
+
+        #[cfg(not(runtime_shaders))]
+        let blob = $crate::dx::shader::ShaderBlob::from_static(include_bytes!(concat!(
+            env!("OUT_DIR"),
+            "/shaders/",
+            $file,
+            ".",
+            $entrypoint,
+            ".cso"
+        )));
+
+        blob
+    }};
+}
+
+/// Checks that the bytes are a complete DXBC shader container
+fn validate_bytecode(bytes: &[u8]) -> anyhow::Result<()> {
+    // DXBC header: magic (4), checksum (16), version (4), total size (4), chunk count (4)
+    const HEADER_SIZE: usize = 32;
+
+    if bytes.len() < HEADER_SIZE || &bytes[0..4] != b"DXBC" {
+        anyhow::bail!("shader bytecode is not a DXBC container");
+    }
+
+    let total_size = u32::from_le_bytes([bytes[24], bytes[25], bytes[26], bytes[27]]) as usize;
+    if total_size != bytes.len() {
+        anyhow::bail!(
+            "shader bytecode size mismatch (header {total_size}, actual {})",
+            bytes.len()
+        );
+    }
+
+    Ok(())
 }
 
 pub struct PixelShader {
@@ -58,12 +166,12 @@ pub struct PixelShader {
 impl PixelShader {
     pub fn create(device: &ID3D11Device, blob: ShaderBlob) -> anyhow::Result<PixelShader> {
         let mut shader = std::ptr::null_mut();
-        let blob_ref = blob.0.as_ref();
+        let bytecode = blob.as_bytes();
 
         let hr = unsafe {
             device.CreatePixelShader(
-                blob_ref.GetBufferPointer(),
-                blob_ref.GetBufferSize(),
+                bytecode.as_ptr().cast(),
+                bytecode.len(),
                 std::ptr::null_mut(),
                 &mut shader,
             )
@@ -94,11 +202,11 @@ pub struct VertexShader {
 impl VertexShader {
     pub fn create(device: &ID3D11Device, blob: ShaderBlob) -> anyhow::Result<VertexShader> {
         let mut shader = std::ptr::null_mut();
-        let blob_ref = blob.0.as_ref();
+        let bytecode = blob.as_bytes();
         let hr = unsafe {
             device.CreateVertexShader(
-                blob_ref.GetBufferPointer(),
-                blob_ref.GetBufferSize(),
+                bytecode.as_ptr().cast(),
+                bytecode.len(),
                 std::ptr::null_mut(),
                 &mut shader,
             )
@@ -119,6 +227,85 @@ impl VertexShader {
     }
 }
 
+pub struct ComputeShader {
+    pub blob: ShaderBlob,
+    pub shader: ComPtr<ID3D11ComputeShader>,
+}
+
+impl ComputeShader {
+    pub fn create(device: &ID3D11Device, blob: ShaderBlob) -> anyhow::Result<ComputeShader> {
+        let mut shader = std::ptr::null_mut();
+        let bytecode = blob.as_bytes();
+        let hr = unsafe {
+            device.CreateComputeShader(
+                bytecode.as_ptr().cast(),
+                bytecode.len(),
+                std::ptr::null_mut(),
+                &mut shader,
+            )
+        };
+
+        hr_bail!(hr, "failed to create compute shader");
+
+        Ok(ComputeShader {
+            blob,
+            shader: shader.into(),
+        })
+    }
+
+    pub fn set_shader(&mut self, ctx: &ID3D11DeviceContext) {
+        unsafe {
+            ctx.CSSetShader(self.shader.as_mut(), std::ptr::null_mut(), 0);
+        }
+    }
+
+    /// Runs the bound compute shader over enough thread groups of
+    /// `group_size` threads to cover `size` (rounded up)
+    pub fn dispatch(ctx: &ID3D11DeviceContext, size: Vector2<u32>, group_size: u32) {
+        let groups_x = size.x.div_ceil(group_size);
+        let groups_y = size.y.div_ceil(group_size);
+
+        unsafe {
+            ctx.Dispatch(groups_x, groups_y, 1);
+        }
+    }
+}
+
+/// View allowing compute shaders to write into a texture
+pub struct UnorderedAccessView {
+    view: ComPtr<ID3D11UnorderedAccessView>,
+}
+
+impl UnorderedAccessView {
+    /// Creates a view of the texture, the texture must have been created
+    /// with the unordered access bind flag
+    pub fn create_from_texture(
+        device: &ID3D11Device,
+        texture: &mut ID3D11Resource,
+    ) -> anyhow::Result<UnorderedAccessView> {
+        let mut uav = std::ptr::null_mut();
+        let hr = unsafe { device.CreateUnorderedAccessView(texture, std::ptr::null(), &mut uav) };
+        hr_bail!(hr, "failed to create unordered access view");
+        Ok(Self { view: uav.into() })
+    }
+
+    /// Binds the view to a compute shader output slot
+    pub fn bind_compute(&mut self, ctx: &ID3D11DeviceContext, slot: u32) {
+        unsafe {
+            ctx.CSSetUnorderedAccessViews(slot, 1, &self.view.as_ptr(), std::ptr::null());
+        }
+    }
+
+    /// Clears a compute shader output slot, the texture can't be read by
+    /// other shaders while it is still bound for writing
+    pub fn unbind_compute(ctx: &ID3D11DeviceContext, slot: u32) {
+        unsafe {
+            ctx.CSSetUnorderedAccessViews(slot, 1, &std::ptr::null_mut(), std::ptr::null());
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct ShaderResourceView {
     view: ComPtr<ID3D11ShaderResourceView>,
 }
@@ -134,15 +321,98 @@ impl ShaderResourceView {
         Ok(Self { view: srv.into() })
     }
 
+    /// Creates a view of every element in a structured buffer
+    pub fn create_from_structured_buffer(
+        device: &ID3D11Device,
+        buffer: &mut StructuredBuffer,
+    ) -> anyhow::Result<ShaderResourceView> {
+        let mut desc: D3D11_SHADER_RESOURCE_VIEW_DESC = unsafe { std::mem::zeroed() };
+        desc.Format = DXGI_FORMAT_UNKNOWN;
+        desc.ViewDimension = D3D11_SRV_DIMENSION_BUFFER;
+        unsafe {
+            *desc.u.Buffer_mut().u2.NumElements_mut() = buffer.len;
+        }
+
+        let mut srv = std::ptr::null_mut();
+        let hr = unsafe {
+            device.CreateShaderResourceView(buffer.buffer.cast_as_mut(), &desc, &mut srv)
+        };
+        hr_bail!(hr, "failed to create structured buffer view");
+        Ok(Self { view: srv.into() })
+    }
+
     pub fn bind(&mut self, ctx: &ID3D11DeviceContext) {
+        self.bind_slot(ctx, 0);
+    }
+
+    /// Binds the view to a specific pixel shader texture slot
+    pub fn bind_slot(&mut self, ctx: &ID3D11DeviceContext, slot: u32) {
+        unsafe {
+            ctx.PSSetShaderResources(slot, 1, &self.view.as_ptr());
+        }
+    }
+
+    /// Binds views to consecutive pixel shader texture slots starting from
+    /// `start_slot`, [None] entries clear their slot
+    pub fn bind_many(
+        ctx: &ID3D11DeviceContext,
+        start_slot: u32,
+        views: &[Option<&ShaderResourceView>],
+    ) {
+        let raw_views: Vec<*mut ID3D11ShaderResourceView> = views
+            .iter()
+            .map(|view| match view {
+                Some(view) => view.view.as_ref() as *const _ as *mut _,
+                None => std::ptr::null_mut(),
+            })
+            .collect();
+
         unsafe {
-            ctx.PSSetShaderResources(0, 1, &self.view.as_ptr());
+            ctx.PSSetShaderResources(start_slot, raw_views.len() as u32, raw_views.as_ptr());
         }
     }
 
+    /// Unbinds the view from slot 0, a null view must be bound to clear
+    /// the slot (passing a null array leaves the slot bound)
     pub fn unbind(&mut self, ctx: &ID3D11DeviceContext) {
+        Self::unbind_slots(ctx, 0, 1);
+    }
+
+    /// Binds the view to a compute shader texture slot
+    pub fn bind_compute(&mut self, ctx: &ID3D11DeviceContext, slot: u32) {
+        unsafe {
+            ctx.CSSetShaderResources(slot, 1, &self.view.as_ptr());
+        }
+    }
+
+    /// Binds the view to a vertex shader resource slot
+    pub fn bind_vertex(&mut self, ctx: &ID3D11DeviceContext, slot: u32) {
+        unsafe {
+            ctx.VSSetShaderResources(slot, 1, &self.view.as_ptr());
+        }
+    }
+
+    /// Clears a vertex shader resource slot
+    pub fn unbind_vertex(ctx: &ID3D11DeviceContext, slot: u32) {
+        unsafe {
+            ctx.VSSetShaderResources(slot, 1, &std::ptr::null_mut());
+        }
+    }
+
+    /// Clears a compute shader texture slot
+    pub fn unbind_compute(ctx: &ID3D11DeviceContext, slot: u32) {
+        unsafe {
+            ctx.CSSetShaderResources(slot, 1, &std::ptr::null_mut());
+        }
+    }
+
+    /// Clears `count` pixel shader texture slots starting from `start_slot`
+    pub fn unbind_slots(ctx: &ID3D11DeviceContext, start_slot: u32, count: u32) {
+        let null_views: Vec<*mut ID3D11ShaderResourceView> =
+            vec![std::ptr::null_mut(); count as usize];
+
         unsafe {
-            ctx.PSSetShaderResources(0, 1, std::ptr::null());
+            ctx.PSSetShaderResources(start_slot, count, null_views.as_ptr());
         }
     }
 }
@@ -160,14 +430,14 @@ impl ShaderInputLayout {
         // Create input layout
         let mut layout = std::ptr::null_mut();
 
-        let blob = shader_blob.0.as_ref();
+        let bytecode = shader_blob.as_bytes();
 
         let hr = unsafe {
             device.CreateInputLayout(
                 layout_desc.as_ptr(),
                 layout_desc.len() as _,
-                blob.GetBufferPointer(),
-                blob.GetBufferSize(),
+                bytecode.as_ptr().cast(),
+                bytecode.len(),
                 &mut layout,
             )
         };
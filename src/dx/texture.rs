@@ -1,66 +1,311 @@
-use std::path::Path;
+use std::{path::Path, time::Duration};
 
 use image::{EncodableLayout, GenericImageView};
 use nalgebra::Vector2;
 use winapi::{
     shared::{
         basetsd::UINT8,
-        dxgiformat::{DXGI_FORMAT_B8G8R8A8_UNORM, DXGI_FORMAT_R8G8B8A8_UNORM},
+        dxgi::{IDXGIKeyedMutex, IDXGIResource},
+        dxgiformat::{
+            DXGI_FORMAT, DXGI_FORMAT_B8G8R8A8_UNORM, DXGI_FORMAT_B8G8R8A8_UNORM_SRGB,
+            DXGI_FORMAT_R10G10B10A2_UNORM, DXGI_FORMAT_R16G16B16A16_FLOAT,
+            DXGI_FORMAT_R16G16B16A16_UNORM, DXGI_FORMAT_R32G32B32A32_FLOAT,
+            DXGI_FORMAT_R8G8B8A8_UNORM, DXGI_FORMAT_R8G8B8A8_UNORM_SRGB,
+        },
         dxgitype::DXGI_SAMPLE_DESC,
         minwindef::{FALSE, TRUE},
+        winerror::WAIT_TIMEOUT,
     },
-    um::d3d11::{
-        ID3D11BlendState, ID3D11Device, ID3D11DeviceContext, ID3D11RenderTargetView,
-        ID3D11Texture2D, D3D11_BIND_RENDER_TARGET, D3D11_BIND_SHADER_RESOURCE, D3D11_BLEND_DESC,
-        D3D11_BLEND_INV_SRC_ALPHA, D3D11_BLEND_ONE, D3D11_BLEND_OP_ADD, D3D11_BLEND_SRC_ALPHA,
-        D3D11_BLEND_ZERO, D3D11_COLOR_WRITE_ENABLE_ALL, D3D11_RENDER_TARGET_BLEND_DESC,
-        D3D11_RESOURCE_MISC_SHARED, D3D11_SUBRESOURCE_DATA, D3D11_TEXTURE2D_DESC,
-        D3D11_USAGE_DEFAULT,
+    um::{
+        d3d11::{
+            ID3D11BlendState, ID3D11Device, ID3D11DeviceContext, ID3D11RenderTargetView,
+            ID3D11Texture2D, D3D11_BIND_RENDER_TARGET, D3D11_BIND_SHADER_RESOURCE,
+            D3D11_BIND_UNORDERED_ACCESS, D3D11_BLEND_DESC, D3D11_BLEND_INV_SRC_ALPHA,
+            D3D11_BLEND_ONE, D3D11_BLEND_OP_ADD, D3D11_BLEND_SRC_ALPHA, D3D11_BLEND_ZERO,
+            D3D11_COLOR_WRITE_ENABLE_ALL, D3D11_CPU_ACCESS_READ, D3D11_MAP_READ,
+            D3D11_RENDER_TARGET_BLEND_DESC, D3D11_REQ_TEXTURE2D_ARRAY_AXIS_DIMENSION,
+            D3D11_RESOURCE_MISC_SHARED, D3D11_RESOURCE_MISC_SHARED_KEYEDMUTEX,
+            D3D11_SUBRESOURCE_DATA, D3D11_TEXTURE2D_DESC, D3D11_USAGE_DEFAULT, D3D11_USAGE_STAGING,
+        },
+        winnt::HANDLE,
     },
 };
 
-use crate::{com::ComPtr, hr_bail};
+use crate::{
+    com::ComPtr,
+    dx::dds::DdsImage,
+    hr_bail,
+    texture_loader::{GradientDirection, TextureData},
+};
+
+/// Default format used by render targets, most supported format for Spout2
+pub const RENDER_TARGET_FORMAT: DXGI_FORMAT = DXGI_FORMAT_B8G8R8A8_UNORM;
 
 /// Texture and render target combined, the referenced texture
 /// is the render target itself
 pub struct RenderTargetTexture {
     pub texture: ComPtr<ID3D11Texture2D>,
     view: ComPtr<ID3D11RenderTargetView>,
+    /// Size of the render target
+    pub size: Vector2<u32>,
+    /// Format of the render target texture
+    pub format: DXGI_FORMAT,
+    /// Shareable copy of the texture in its non sRGB format, only
+    /// present for sRGB render targets
+    output: Option<ComPtr<ID3D11Texture2D>>,
+    /// Keyed mutex guarding the shared texture, only present when created
+    /// with a keyed mutex
+    keyed_mutex: Option<ComPtr<IDXGIKeyedMutex>>,
 }
 
+/// Key used by both the renderer and consumers when acquiring the keyed
+/// mutex, the mutex only provides exclusive access and doesn't hand the
+/// texture back and forth
+pub const KEYED_MUTEX_KEY: u64 = 0;
+
+/// Time [RenderTargetTexture::read_pixels] waits for a consumer to release
+/// the keyed mutex before giving up
+const READBACK_SYNC_TIMEOUT: Duration = Duration::from_millis(100);
+
 impl RenderTargetTexture {
-    /// Creates a render target thats backed by a texture
-    pub fn create(device: &ID3D11Device, width: u32, height: u32) -> anyhow::Result<Self> {
-        let texture_desc = D3D11_TEXTURE2D_DESC {
+    /// Creates a render target thats backed by a texture, use
+    /// [RENDER_TARGET_FORMAT] unless a higher precision format such as
+    /// DXGI_FORMAT_R16G16B16A16_FLOAT is needed for HDR. `keyed_mutex`
+    /// shares the texture with a keyed mutex, see
+    /// [RenderTargetTexture::acquire_sync]
+    pub fn create(
+        device: &ID3D11Device,
+        width: u32,
+        height: u32,
+        format: DXGI_FORMAT,
+        keyed_mutex: bool,
+    ) -> anyhow::Result<Self> {
+        let (texture, view) =
+            create_render_target(device, width, height, format, share_flags(keyed_mutex))?;
+        let keyed_mutex = query_keyed_mutex(&texture, keyed_mutex)?;
+
+        Ok(Self {
+            texture,
+            view,
+            size: Vector2::new(width, height),
+            format,
+            output: None,
+            keyed_mutex,
+        })
+    }
+
+    /// Creates a render target that is only used on the device and never
+    /// shared with receivers, `format` may be an sRGB format
+    pub fn create_offscreen(
+        device: &ID3D11Device,
+        width: u32,
+        height: u32,
+        format: DXGI_FORMAT,
+    ) -> anyhow::Result<Self> {
+        let (texture, view) = create_render_target(device, width, height, format, 0)?;
+
+        Ok(Self {
+            texture,
+            view,
+            size: Vector2::new(width, height),
+            format,
+            output: None,
+            keyed_mutex: None,
+        })
+    }
+
+    /// Creates a render target that renders and blends in linear space
+    /// using the sRGB variant of `format`.
+    ///
+    /// Spout receivers expect the non sRGB format so each frame is copied
+    /// into a shareable `format` texture by [RenderTargetTexture::output_texture],
+    /// the bytes are unchanged by the copy so receivers get sRGB encoded
+    /// values. This costs a full frame copy per frame. `keyed_mutex`
+    /// shares the copy with a keyed mutex
+    pub fn create_srgb(
+        device: &ID3D11Device,
+        width: u32,
+        height: u32,
+        format: DXGI_FORMAT,
+        keyed_mutex: bool,
+    ) -> anyhow::Result<Self> {
+        let srgb = srgb_format(format)
+            .ok_or_else(|| anyhow::anyhow!("format {format} has no sRGB variant"))?;
+
+        let (texture, view) = create_render_target(device, width, height, srgb, 0)?;
+        let output =
+            create_shared_texture(device, width, height, format, share_flags(keyed_mutex))?;
+        let keyed_mutex = query_keyed_mutex(&output, keyed_mutex)?;
+
+        Ok(Self {
+            texture,
+            view,
+            size: Vector2::new(width, height),
+            format: srgb,
+            output: Some(output),
+            keyed_mutex,
+        })
+    }
+
+    /// Texture to share with receivers, for sRGB render targets this
+    /// copies the current frame into the shareable output texture
+    pub fn output_texture(&mut self, ctx: &ID3D11DeviceContext) -> &mut ComPtr<ID3D11Texture2D> {
+        match self.output.as_mut() {
+            Some(output) => {
+                unsafe {
+                    ctx.CopyResource(output.cast_as_mut(), self.texture.cast_as_mut());
+                }
+                output
+            }
+            None => &mut self.texture,
+        }
+    }
+
+    /// Legacy DXGI shared handle of the texture shared with receivers, lets
+    /// another process or engine open the frame directly with
+    /// `ID3D11Device::OpenSharedResource` without going through Spout.
+    ///
+    /// The handle is owned by the texture and must not be closed, it stays
+    /// valid while the render target exists. When the texture is created
+    /// with a keyed mutex the consumer must `AcquireSync` the mutex through
+    /// `IDXGIKeyedMutex` using [KEYED_MUTEX_KEY] before reading and
+    /// `ReleaseSync` it with the same key afterwards so reads never overlap
+    /// a frame being rendered. Without one access is not synchronized and
+    /// consumers should copy the texture promptly after each frame is
+    /// signalled
+    pub fn shared_handle(&self) -> anyhow::Result<HANDLE> {
+        let texture = self.output.as_ref().unwrap_or(&self.texture);
+        let resource = texture
+            .query_interface::<IDXGIResource>()
+            .ok_or_else(|| anyhow::anyhow!("render target texture is not a DXGI resource"))?;
+
+        let mut handle: HANDLE = std::ptr::null_mut();
+        let hr = unsafe { resource.GetSharedHandle(&mut handle) };
+        hr_bail!(
+            hr,
+            "failed to get shared handle, the render target is not shared: HRESULT {hr:#010x}"
+        );
+
+        Ok(handle)
+    }
+
+    /// Acquires the keyed mutex before rendering into or sending the shared
+    /// texture, waiting up to `timeout` for a consumer to release it.
+    /// Returns false if the mutex is still held by a consumer, the frame
+    /// must then be skipped. Always succeeds without a keyed mutex
+    pub fn acquire_sync(&self, timeout: Duration) -> anyhow::Result<bool> {
+        let Some(keyed_mutex) = self.keyed_mutex.as_ref() else {
+            return Ok(true);
+        };
+
+        let timeout = timeout.as_millis().min(u32::MAX as u128) as u32;
+        let hr = unsafe { keyed_mutex.AcquireSync(KEYED_MUTEX_KEY, timeout) };
+        if hr == WAIT_TIMEOUT as i32 {
+            return Ok(false);
+        }
+        hr_bail!(
+            hr,
+            "failed to acquire render target keyed mutex: HRESULT {hr:#010x}"
+        );
+
+        Ok(true)
+    }
+
+    /// Releases the keyed mutex acquired by [RenderTargetTexture::acquire_sync]
+    /// once the frame has been rendered and sent
+    pub fn release_sync(&self) -> anyhow::Result<()> {
+        let Some(keyed_mutex) = self.keyed_mutex.as_ref() else {
+            return Ok(());
+        };
+
+        let hr = unsafe { keyed_mutex.ReleaseSync(KEYED_MUTEX_KEY) };
+        hr_bail!(
+            hr,
+            "failed to release render target keyed mutex: HRESULT {hr:#010x}"
+        );
+
+        Ok(())
+    }
+
+    /// Reads back the current pixels of the render target, returns the
+    /// tightly packed pixel data (Row padding is removed). Acquires the
+    /// keyed mutex while copying when it guards the render target itself,
+    /// so this must not be called while the mutex is already held
+    pub fn read_pixels(
+        &mut self,
+        device: &ID3D11Device,
+        ctx: &ID3D11DeviceContext,
+    ) -> anyhow::Result<Vec<u8>> {
+        let (width, height) = (self.size.x, self.size.y);
+        let bytes_per_pixel = format_bytes_per_pixel(self.format)
+            .ok_or_else(|| anyhow::anyhow!("cannot read back format {}", self.format))?;
+
+        // Staging texture the GPU copies into for the CPU to read
+        let staging_desc = D3D11_TEXTURE2D_DESC {
             Width: width,
             Height: height,
             MipLevels: 1,
             ArraySize: 1,
-            // Most supported format for Spout2
-            Format: DXGI_FORMAT_B8G8R8A8_UNORM,
+            Format: self.format,
             SampleDesc: DXGI_SAMPLE_DESC {
                 Count: 1,
                 Quality: 0,
             },
-            Usage: D3D11_USAGE_DEFAULT,
-            BindFlags: D3D11_BIND_RENDER_TARGET | D3D11_BIND_SHADER_RESOURCE,
-            CPUAccessFlags: 0,
-            MiscFlags: D3D11_RESOURCE_MISC_SHARED,
+            Usage: D3D11_USAGE_STAGING,
+            BindFlags: 0,
+            CPUAccessFlags: D3D11_CPU_ACCESS_READ,
+            MiscFlags: 0,
         };
 
-        let mut texture: *mut ID3D11Texture2D = std::ptr::null_mut();
-        let hr = unsafe { device.CreateTexture2D(&texture_desc, std::ptr::null(), &mut texture) };
-        hr_bail!(hr, "failed to create texture for render target");
+        let mut staging: *mut ID3D11Texture2D = std::ptr::null_mut();
+        let hr = unsafe { device.CreateTexture2D(&staging_desc, std::ptr::null(), &mut staging) };
+        hr_bail!(
+            hr,
+            "failed to create staging texture ({width}x{height}, format {}): HRESULT {hr:#010x}",
+            self.format
+        );
+        let mut staging: ComPtr<ID3D11Texture2D> = staging.into();
 
-        let mut view: *mut ID3D11RenderTargetView = std::ptr::null_mut();
-        let hr =
-            unsafe { device.CreateRenderTargetView(texture.cast(), std::ptr::null(), &mut view) };
-        hr_bail!(hr, "failed to create render target view");
+        let row_size = (width * bytes_per_pixel) as usize;
+        let mut pixels = Vec::with_capacity(row_size * height as usize);
 
-        Ok(Self {
-            texture: texture.into(),
-            view: view.into(),
-        })
+        // Without a separate output texture the keyed mutex is on the render
+        // target, D3D11 only allows copying from it while the mutex is held
+        let locked = self.output.is_none() && self.keyed_mutex.is_some();
+        if locked && !self.acquire_sync(READBACK_SYNC_TIMEOUT)? {
+            anyhow::bail!("render target keyed mutex is held by a consumer");
+        }
+
+        unsafe {
+            ctx.CopyResource(staging.cast_as_mut(), self.texture.cast_as_mut());
+        }
+
+        if locked {
+            self.release_sync()?;
+        }
+
+        unsafe {
+            let mut mapped_resource = std::mem::zeroed();
+            let hr = ctx.Map(
+                staging.cast_as_mut(),
+                0,
+                D3D11_MAP_READ,
+                0,
+                &mut mapped_resource,
+            );
+            hr_bail!(hr, "failed to map staging texture");
+
+            // Rows may be padded, copy each row individually
+            let data = mapped_resource.pData.cast::<u8>();
+            for row in 0..height as usize {
+                let row_start = data.add(row * mapped_resource.RowPitch as usize);
+                pixels.extend_from_slice(std::slice::from_raw_parts(row_start, row_size));
+            }
+
+            ctx.Unmap(staging.cast_as_mut(), 0);
+        }
+
+        Ok(pixels)
     }
 
     pub fn bind(&mut self, ctx: &ID3D11DeviceContext) {
@@ -69,6 +314,14 @@ impl RenderTargetTexture {
         }
     }
 
+    /// Unbinds all render targets, required before the texture can be
+    /// read from a shader
+    pub fn unbind(&mut self, ctx: &ID3D11DeviceContext) {
+        unsafe {
+            ctx.OMSetRenderTargets(0, std::ptr::null(), std::ptr::null_mut());
+        }
+    }
+
     pub fn clear(&mut self, ctx: &ID3D11DeviceContext, color: &[f32; 4]) {
         unsafe {
             ctx.ClearRenderTargetView(self.view.as_ptr(), color);
@@ -76,6 +329,130 @@ impl RenderTargetTexture {
     }
 }
 
+/// Creates the texture and view for a render target
+fn create_render_target(
+    device: &ID3D11Device,
+    width: u32,
+    height: u32,
+    format: DXGI_FORMAT,
+    misc_flags: u32,
+) -> anyhow::Result<(ComPtr<ID3D11Texture2D>, ComPtr<ID3D11RenderTargetView>)> {
+    let texture_desc = D3D11_TEXTURE2D_DESC {
+        Width: width,
+        Height: height,
+        MipLevels: 1,
+        ArraySize: 1,
+        Format: format,
+        SampleDesc: DXGI_SAMPLE_DESC {
+            Count: 1,
+            Quality: 0,
+        },
+        Usage: D3D11_USAGE_DEFAULT,
+        BindFlags: D3D11_BIND_RENDER_TARGET | D3D11_BIND_SHADER_RESOURCE,
+        CPUAccessFlags: 0,
+        MiscFlags: misc_flags,
+    };
+
+    let mut texture: *mut ID3D11Texture2D = std::ptr::null_mut();
+    let hr = unsafe { device.CreateTexture2D(&texture_desc, std::ptr::null(), &mut texture) };
+    hr_bail!(
+        hr,
+        "failed to create texture for render target ({width}x{height}, format {format}): HRESULT {hr:#010x}"
+    );
+    let mut texture: ComPtr<ID3D11Texture2D> = texture.into();
+
+    let mut view: *mut ID3D11RenderTargetView = std::ptr::null_mut();
+    let hr = unsafe {
+        device.CreateRenderTargetView(texture.as_ptr().cast(), std::ptr::null(), &mut view)
+    };
+    hr_bail!(hr, "failed to create render target view");
+
+    Ok((texture, view.into()))
+}
+
+/// Creates a texture that can be shared with Spout receivers
+fn create_shared_texture(
+    device: &ID3D11Device,
+    width: u32,
+    height: u32,
+    format: DXGI_FORMAT,
+    misc_flags: u32,
+) -> anyhow::Result<ComPtr<ID3D11Texture2D>> {
+    let texture_desc = D3D11_TEXTURE2D_DESC {
+        Width: width,
+        Height: height,
+        MipLevels: 1,
+        ArraySize: 1,
+        Format: format,
+        SampleDesc: DXGI_SAMPLE_DESC {
+            Count: 1,
+            Quality: 0,
+        },
+        Usage: D3D11_USAGE_DEFAULT,
+        BindFlags: D3D11_BIND_SHADER_RESOURCE,
+        CPUAccessFlags: 0,
+        MiscFlags: misc_flags,
+    };
+
+    let mut texture: *mut ID3D11Texture2D = std::ptr::null_mut();
+    let hr = unsafe { device.CreateTexture2D(&texture_desc, std::ptr::null(), &mut texture) };
+    hr_bail!(
+        hr,
+        "failed to create shared texture ({width}x{height}, format {format}): HRESULT {hr:#010x}"
+    );
+
+    Ok(texture.into())
+}
+
+/// Resource flags for a texture shared with receivers, legacy shared
+/// textures and keyed mutex textures can't be combined
+fn share_flags(keyed_mutex: bool) -> u32 {
+    match keyed_mutex {
+        true => D3D11_RESOURCE_MISC_SHARED_KEYEDMUTEX,
+        false => D3D11_RESOURCE_MISC_SHARED,
+    }
+}
+
+/// Keyed mutex of a texture created with [share_flags], [None] when
+/// `keyed_mutex` is false
+fn query_keyed_mutex(
+    texture: &ComPtr<ID3D11Texture2D>,
+    keyed_mutex: bool,
+) -> anyhow::Result<Option<ComPtr<IDXGIKeyedMutex>>> {
+    if !keyed_mutex {
+        return Ok(None);
+    }
+
+    texture
+        .query_interface::<IDXGIKeyedMutex>()
+        .map(Some)
+        .ok_or_else(|| anyhow::anyhow!("shared texture has no keyed mutex"))
+}
+
+/// sRGB variant of a format, the GPU converts to linear when sampling
+/// and back to sRGB when writing
+pub fn srgb_format(format: DXGI_FORMAT) -> Option<DXGI_FORMAT> {
+    match format {
+        DXGI_FORMAT_B8G8R8A8_UNORM => Some(DXGI_FORMAT_B8G8R8A8_UNORM_SRGB),
+        DXGI_FORMAT_R8G8B8A8_UNORM => Some(DXGI_FORMAT_R8G8B8A8_UNORM_SRGB),
+        _ => None,
+    }
+}
+
+/// Number of bytes per pixel for formats that support read back
+pub fn format_bytes_per_pixel(format: DXGI_FORMAT) -> Option<u32> {
+    match format {
+        DXGI_FORMAT_B8G8R8A8_UNORM
+        | DXGI_FORMAT_B8G8R8A8_UNORM_SRGB
+        | DXGI_FORMAT_R8G8B8A8_UNORM
+        | DXGI_FORMAT_R8G8B8A8_UNORM_SRGB
+        | DXGI_FORMAT_R10G10B10A2_UNORM => Some(4),
+        DXGI_FORMAT_R16G16B16A16_FLOAT | DXGI_FORMAT_R16G16B16A16_UNORM => Some(8),
+        DXGI_FORMAT_R32G32B32A32_FLOAT => Some(16),
+        _ => None,
+    }
+}
+
 pub struct BlendState {
     state: ComPtr<ID3D11BlendState>,
 }
@@ -108,6 +485,89 @@ impl BlendState {
         })
     }
 
+    /// Blend state for colors already multiplied by their alpha
+    pub fn premultiplied_alpha_state(device: &ID3D11Device) -> anyhow::Result<BlendState> {
+        let blend_desc = D3D11_BLEND_DESC {
+            AlphaToCoverageEnable: FALSE,
+            IndependentBlendEnable: FALSE,
+            RenderTarget: [D3D11_RENDER_TARGET_BLEND_DESC {
+                BlendEnable: TRUE,
+                SrcBlend: D3D11_BLEND_ONE,
+                DestBlend: D3D11_BLEND_INV_SRC_ALPHA,
+                BlendOp: D3D11_BLEND_OP_ADD,
+                SrcBlendAlpha: D3D11_BLEND_ONE,
+                DestBlendAlpha: D3D11_BLEND_INV_SRC_ALPHA,
+                BlendOpAlpha: D3D11_BLEND_OP_ADD,
+                RenderTargetWriteMask: D3D11_COLOR_WRITE_ENABLE_ALL as UINT8,
+            }; 8],
+        };
+
+        let mut state = std::ptr::null_mut();
+        let hr = unsafe { device.CreateBlendState(&blend_desc, &mut state) };
+
+        hr_bail!(hr, "failed to create premultiplied alpha blend state");
+
+        Ok(Self {
+            state: state.into(),
+        })
+    }
+
+    /// Blend state that overwrites the target without blending, used for
+    /// alpha tested cutouts
+    pub fn opaque_state(device: &ID3D11Device) -> anyhow::Result<BlendState> {
+        let blend_desc = D3D11_BLEND_DESC {
+            AlphaToCoverageEnable: FALSE,
+            IndependentBlendEnable: FALSE,
+            RenderTarget: [D3D11_RENDER_TARGET_BLEND_DESC {
+                BlendEnable: FALSE,
+                SrcBlend: D3D11_BLEND_ONE,
+                DestBlend: D3D11_BLEND_ZERO,
+                BlendOp: D3D11_BLEND_OP_ADD,
+                SrcBlendAlpha: D3D11_BLEND_ONE,
+                DestBlendAlpha: D3D11_BLEND_ZERO,
+                BlendOpAlpha: D3D11_BLEND_OP_ADD,
+                RenderTargetWriteMask: D3D11_COLOR_WRITE_ENABLE_ALL as UINT8,
+            }; 8],
+        };
+
+        let mut state = std::ptr::null_mut();
+        let hr = unsafe { device.CreateBlendState(&blend_desc, &mut state) };
+
+        hr_bail!(hr, "failed to create opaque blend state");
+
+        Ok(Self {
+            state: state.into(),
+        })
+    }
+
+    /// Blend state that adds the premultiplied color onto the target, used
+    /// for glowing particles
+    pub fn additive_state(device: &ID3D11Device) -> anyhow::Result<BlendState> {
+        let blend_desc = D3D11_BLEND_DESC {
+            AlphaToCoverageEnable: FALSE,
+            IndependentBlendEnable: FALSE,
+            RenderTarget: [D3D11_RENDER_TARGET_BLEND_DESC {
+                BlendEnable: TRUE,
+                SrcBlend: D3D11_BLEND_ONE,
+                DestBlend: D3D11_BLEND_ONE,
+                BlendOp: D3D11_BLEND_OP_ADD,
+                SrcBlendAlpha: D3D11_BLEND_ONE,
+                DestBlendAlpha: D3D11_BLEND_ONE,
+                BlendOpAlpha: D3D11_BLEND_OP_ADD,
+                RenderTargetWriteMask: D3D11_COLOR_WRITE_ENABLE_ALL as UINT8,
+            }; 8],
+        };
+
+        let mut state = std::ptr::null_mut();
+        let hr = unsafe { device.CreateBlendState(&blend_desc, &mut state) };
+
+        hr_bail!(hr, "failed to create additive blend state");
+
+        Ok(Self {
+            state: state.into(),
+        })
+    }
+
     pub fn bind(&mut self, ctx: &ID3D11DeviceContext) {
         let blend_factor = [0.0f32; 4];
         let sample_mask = 0xffffffff;
@@ -118,6 +578,7 @@ impl BlendState {
     }
 }
 
+#[derive(Clone)]
 pub struct Texture {
     pub texture: ComPtr<ID3D11Texture2D>,
     pub size: Vector2<u32>,
@@ -136,11 +597,135 @@ impl Texture {
         Self::create_from_data(device, width, height, img.as_bytes())
     }
 
+    /// Creates a texture from tightly packed RGBA data
     pub fn create_from_data(
         device: &ID3D11Device,
         width: u32,
         height: u32,
         data: &[u8],
+    ) -> anyhow::Result<Self> {
+        Self::create_from_data_with_pitch(device, width, height, data, width * 4)
+    }
+
+    /// Creates a texture from RGBA data whose rows are `row_pitch` bytes
+    /// apart, for decoders that pad each row. The padding is skipped
+    pub fn create_from_data_with_pitch(
+        device: &ID3D11Device,
+        width: u32,
+        height: u32,
+        data: &[u8],
+        row_pitch: u32,
+    ) -> anyhow::Result<Self> {
+        Self::create_from_data_with_format(
+            device,
+            width,
+            height,
+            data,
+            row_pitch,
+            DXGI_FORMAT_R8G8B8A8_UNORM,
+        )
+    }
+
+    /// Creates a texture filled with a single color (RGBA)
+    pub fn solid_color(
+        device: &ID3D11Device,
+        width: u32,
+        height: u32,
+        color: [u8; 4],
+    ) -> anyhow::Result<Texture> {
+        let data = TextureData::solid_color(width, height, color);
+        Self::create_from_data(device, width, height, &data.buffer)
+    }
+
+    /// Creates a texture blending linearly between two colors (RGBA)
+    pub fn linear_gradient(
+        device: &ID3D11Device,
+        width: u32,
+        height: u32,
+        from: [u8; 4],
+        to: [u8; 4],
+        direction: GradientDirection,
+    ) -> anyhow::Result<Texture> {
+        let data = TextureData::linear_gradient(width, height, from, to, direction);
+        Self::create_from_data(device, width, height, &data.buffer)
+    }
+
+    /// Creates a texture from the contents of a DDS file, block compressed
+    /// formats (BC1-BC7) are uploaded as-is and stay compressed in VRAM
+    pub fn load_dds_from_bytes(device: &ID3D11Device, data: &[u8]) -> anyhow::Result<Texture> {
+        let image = DdsImage::parse(data)?;
+        let (width, height) = (image.width, image.height);
+
+        let texture_desc = D3D11_TEXTURE2D_DESC {
+            Width: width,
+            Height: height,
+            MipLevels: image.mip_levels,
+            ArraySize: 1,
+            Format: image.format,
+            SampleDesc: DXGI_SAMPLE_DESC {
+                Count: 1,
+                Quality: 0,
+            },
+            Usage: D3D11_USAGE_DEFAULT,
+            BindFlags: D3D11_BIND_SHADER_RESOURCE,
+            CPUAccessFlags: 0,
+            MiscFlags: 0,
+        };
+
+        // Initial data for each mip level
+        let mut offset = 0;
+        let init_data: Vec<D3D11_SUBRESOURCE_DATA> = (0..image.mip_levels)
+            .map(|level| {
+                let (row_pitch, size) = image.level_layout(level);
+                let level_data = D3D11_SUBRESOURCE_DATA {
+                    pSysMem: image.data[offset..].as_ptr().cast(),
+                    SysMemPitch: row_pitch as u32,
+                    SysMemSlicePitch: 0,
+                };
+                offset += size;
+                level_data
+            })
+            .collect();
+
+        let mut texture = std::ptr::null_mut();
+        let hr = unsafe { device.CreateTexture2D(&texture_desc, init_data.as_ptr(), &mut texture) };
+        hr_bail!(
+            hr,
+            "failed to create dds texture ({width}x{height}, format {}): HRESULT {hr:#010x}",
+            texture_desc.Format
+        );
+
+        Ok(Texture {
+            texture: texture.into(),
+            size: Vector2::new(width, height),
+        })
+    }
+
+    /// Creates a texture from sRGB encoded RGBA data, the GPU converts
+    /// the texels to linear when sampling. Use with an sRGB render target
+    /// so blending happens in linear space
+    pub fn create_from_data_srgb(
+        device: &ID3D11Device,
+        width: u32,
+        height: u32,
+        data: &[u8],
+    ) -> anyhow::Result<Self> {
+        Self::create_from_data_with_format(
+            device,
+            width,
+            height,
+            data,
+            width * 4,
+            DXGI_FORMAT_R8G8B8A8_UNORM_SRGB,
+        )
+    }
+
+    /// Creates an empty RGBA texture that compute shaders can write into
+    /// through an unordered access view and other shaders can sample
+    pub fn create_unordered(
+        device: &ID3D11Device,
+        width: u32,
+        height: u32,
     ) -> anyhow::Result<Self> {
         let texture_desc = D3D11_TEXTURE2D_DESC {
             Width: width,
@@ -153,6 +738,62 @@ impl Texture {
                 Quality: 0,
             },
             Usage: D3D11_USAGE_DEFAULT,
+            BindFlags: D3D11_BIND_SHADER_RESOURCE | D3D11_BIND_UNORDERED_ACCESS,
+            CPUAccessFlags: 0,
+            MiscFlags: 0,
+        };
+
+        let mut texture = std::ptr::null_mut();
+        let hr = unsafe { device.CreateTexture2D(&texture_desc, std::ptr::null(), &mut texture) };
+        hr_bail!(
+            hr,
+            "failed to create unordered access texture ({width}x{height}): HRESULT {hr:#010x}"
+        );
+
+        Ok(Texture {
+            texture: texture.into(),
+            size: Vector2::new(width, height),
+        })
+    }
+
+    /// Creates a texture from 4 byte per pixel RGBA data
+    fn create_from_data_with_format(
+        device: &ID3D11Device,
+        width: u32,
+        height: u32,
+        data: &[u8],
+        row_pitch: u32,
+        format: DXGI_FORMAT,
+    ) -> anyhow::Result<Self> {
+        if width == 0 || height == 0 {
+            anyhow::bail!("texture must be at least 1x1 (got {width}x{height})");
+        }
+
+        // Rows hold 4 bytes per pixel plus any padding
+        if (row_pitch as u64) < width as u64 * 4 {
+            anyhow::bail!("row pitch {row_pitch} is too small for {width} RGBA pixels");
+        }
+
+        // A short buffer would be read past its end
+        let expected_len = row_pitch as usize * height as usize;
+        if data.len() != expected_len {
+            anyhow::bail!(
+                "texture data for {width}x{height} with a row pitch of {row_pitch} should be {expected_len} bytes (got {})",
+                data.len()
+            );
+        }
+
+        let texture_desc = D3D11_TEXTURE2D_DESC {
+            Width: width,
+            Height: height,
+            MipLevels: 1,
+            ArraySize: 1,
+            Format: format,
+            SampleDesc: DXGI_SAMPLE_DESC {
+                Count: 1,
+                Quality: 0,
+            },
+            Usage: D3D11_USAGE_DEFAULT,
             BindFlags: D3D11_BIND_SHADER_RESOURCE,
             CPUAccessFlags: 0,
             MiscFlags: 0,
@@ -160,13 +801,17 @@ impl Texture {
 
         let init_data = D3D11_SUBRESOURCE_DATA {
             pSysMem: data.as_ptr().cast(),
-            SysMemPitch: width * 4, /* R8G8B8A8 = 4 bytes */
+            SysMemPitch: row_pitch,
             SysMemSlicePitch: 0,
         };
 
         let mut texture = std::ptr::null_mut();
         let hr = unsafe { device.CreateTexture2D(&texture_desc, &init_data, &mut texture) };
-        hr_bail!(hr, "failed to create texture");
+        hr_bail!(
+            hr,
+            "failed to create texture ({width}x{height}, format {}): HRESULT {hr:#010x}",
+            texture_desc.Format
+        );
 
         Ok(Texture {
             texture: texture.into(),
@@ -174,3 +819,89 @@ impl Texture {
         })
     }
 }
+
+/// Array of same sized RGBA textures stored in a single texture, a shader
+/// selects the layer to sample using the third texture coordinate
+#[derive(Clone)]
+pub struct TextureArray {
+    pub texture: ComPtr<ID3D11Texture2D>,
+    pub size: Vector2<u32>,
+    /// Number of textures in the array
+    pub layers: u32,
+}
+
+impl TextureArray {
+    /// Uploads every slice (RGBA, `width` x `height`) into one texture
+    /// using a single create call, cheaper than creating a texture per
+    /// slice when many same sized textures arrive at once
+    pub fn create_from_slices(
+        device: &ID3D11Device,
+        width: u32,
+        height: u32,
+        slices: &[&[u8]],
+    ) -> anyhow::Result<TextureArray> {
+        if slices.is_empty() {
+            anyhow::bail!("texture array must have at least one slice");
+        }
+
+        if slices.len() > D3D11_REQ_TEXTURE2D_ARRAY_AXIS_DIMENSION as usize {
+            anyhow::bail!(
+                "texture array has {} slices, the limit is {}",
+                slices.len(),
+                D3D11_REQ_TEXTURE2D_ARRAY_AXIS_DIMENSION
+            );
+        }
+
+        let slice_len = width as usize * height as usize * 4;
+        if let Some((index, slice)) = slices
+            .iter()
+            .enumerate()
+            .find(|(_, slice)| slice.len() != slice_len)
+        {
+            anyhow::bail!(
+                "texture array slice {index} is {} bytes, expected {slice_len} for {width}x{height}",
+                slice.len()
+            );
+        }
+
+        let layers = slices.len() as u32;
+        let texture_desc = D3D11_TEXTURE2D_DESC {
+            Width: width,
+            Height: height,
+            MipLevels: 1,
+            ArraySize: layers,
+            Format: DXGI_FORMAT_R8G8B8A8_UNORM,
+            SampleDesc: DXGI_SAMPLE_DESC {
+                Count: 1,
+                Quality: 0,
+            },
+            Usage: D3D11_USAGE_DEFAULT,
+            BindFlags: D3D11_BIND_SHADER_RESOURCE,
+            CPUAccessFlags: 0,
+            MiscFlags: 0,
+        };
+
+        // One subresource per slice as there is a single mip level
+        let init_data: Vec<D3D11_SUBRESOURCE_DATA> = slices
+            .iter()
+            .map(|slice| D3D11_SUBRESOURCE_DATA {
+                pSysMem: slice.as_ptr().cast(),
+                SysMemPitch: width * 4,
+                SysMemSlicePitch: 0,
+            })
+            .collect();
+
+        let mut texture = std::ptr::null_mut();
+        let hr = unsafe { device.CreateTexture2D(&texture_desc, init_data.as_ptr(), &mut texture) };
+        hr_bail!(
+            hr,
+            "failed to create texture array ({width}x{height}, {layers} slices): HRESULT {hr:#010x}"
+        );
+
+        Ok(TextureArray {
+            texture: texture.into(),
+            size: Vector2::new(width, height),
+            layers,
+        })
+    }
+}
@@ -0,0 +1,308 @@
+//! Particle emitters, a single definition spawns many short-lived particles
+//! (confetti, sparks) from a point. Particles are simulated in the vertex
+//! shader from a per-particle seed so the CPU only uploads the emitter time
+//! each frame
+
+use std::time::Duration;
+
+use nalgebra::{Vector2, Vector4};
+use rand::Rng;
+use winapi::um::{
+    d3d11::{ID3D11Device, ID3D11DeviceContext},
+    d3dcommon::D3D11_PRIMITIVE_TOPOLOGY_TRIANGLELIST,
+};
+
+use crate::{
+    clock::SceneClock,
+    dx::{
+        buffer::{ConstantBuffer, StructuredBuffer},
+        shader::{PixelShader, ShaderResourceView, VertexShader},
+        texture::BlendState,
+    },
+    item::SceneForces,
+    load_shader,
+};
+
+/// Vertices drawn per particle, two triangles
+const PARTICLE_VERTICES: u32 = 6;
+
+/// Shape particles are drawn as
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ParticleShape {
+    /// Tumbling square, suits confetti
+    #[default]
+    Square = 0,
+    /// Soft edged dot, suits sparks
+    Circle = 1,
+}
+
+/// Describes a burst or stream of particles
+#[derive(Debug, Clone)]
+pub struct EmitterDefinition {
+    /// Point particles are emitted from (Normalized to the stage size, 0-1)
+    pub position: Vector2<f32>,
+    /// Stage the particles are rendered into
+    pub stage: usize,
+    /// Total number of particles emitted
+    pub particle_count: u32,
+    /// Time the particles are emitted over (ms), 0 emits every particle at
+    /// once
+    pub emission_time: f32,
+    /// Time each particle lives for (ms)
+    pub lifetime: f32,
+    /// Direction particles are launched in (radians), 0 points right and
+    /// -PI/2 points up
+    pub direction: f32,
+    /// Range of launch directions centered on `direction` (radians)
+    pub spread: f32,
+    /// Slowest launch speed (pixels per second)
+    pub min_speed: f32,
+    /// Fastest launch speed (pixels per second)
+    pub max_speed: f32,
+    /// Gravity and wind moving the particles, [None] follows the scene
+    /// forces
+    pub forces: Option<SceneForces>,
+    /// Width and height of each particle (pixels)
+    pub particle_size: f32,
+    /// Each particle picks a random color between the two
+    pub colors: [Vector4<f32>; 2],
+    pub shape: ParticleShape,
+}
+
+impl Default for EmitterDefinition {
+    /// Burst of confetti launched upwards from the center of the stage
+    fn default() -> Self {
+        Self {
+            position: Vector2::new(0.5, 0.5),
+            stage: 0,
+            particle_count: 200,
+            emission_time: 0.0,
+            lifetime: 2500.0,
+            direction: -std::f32::consts::FRAC_PI_2,
+            spread: std::f32::consts::FRAC_PI_2,
+            min_speed: 300.0,
+            max_speed: 700.0,
+            forces: Some(SceneForces {
+                gravity: Vector2::new(0.0, 600.0),
+                wind: Vector2::zeros(),
+            }),
+            particle_size: 10.0,
+            colors: [
+                Vector4::new(1.0, 0.2, 0.4, 1.0),
+                Vector4::new(0.2, 0.6, 1.0, 1.0),
+            ],
+            shape: ParticleShape::Square,
+        }
+    }
+}
+
+impl EmitterDefinition {
+    /// Checks the definition describes particles that can be drawn
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.particle_count == 0 {
+            anyhow::bail!("emitter must emit at least one particle");
+        }
+
+        if !self.lifetime.is_finite() || self.lifetime <= 0.0 {
+            anyhow::bail!(
+                "particle lifetime must be greater than zero (got {})",
+                self.lifetime
+            );
+        }
+
+        if !self.emission_time.is_finite() || self.emission_time < 0.0 {
+            anyhow::bail!(
+                "emission time can't be negative (got {})",
+                self.emission_time
+            );
+        }
+
+        if !self.particle_size.is_finite() || self.particle_size <= 0.0 {
+            anyhow::bail!(
+                "particle size must be greater than zero (got {})",
+                self.particle_size
+            );
+        }
+
+        if !(self.min_speed >= 0.0 && self.min_speed <= self.max_speed) {
+            anyhow::bail!("particle speed range must be positive and ordered");
+        }
+
+        Ok(())
+    }
+
+    /// Total time the emitter is visible for (ms), the last particle is
+    /// emitted at the end of the emission time then lives out its lifetime
+    pub fn total_duration(&self) -> f32 {
+        self.emission_time + self.lifetime
+    }
+}
+
+/// Per particle data, the path of the particle is derived from the seed
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct Particle {
+    seed: u32,
+    /// Seconds after the emitter started the particle is emitted
+    spawn_time: f32,
+}
+
+#[repr(C, align(16))]
+#[derive(Debug, Clone, Copy)]
+struct EmitterParams {
+    color_a: Vector4<f32>,
+    color_b: Vector4<f32>,
+    /// Emitter position within the stage (pixels)
+    origin: Vector2<f32>,
+    stage_size: Vector2<f32>,
+    /// Acceleration (pixels per second squared)
+    gravity: Vector2<f32>,
+    /// Drift (pixels per second)
+    wind: Vector2<f32>,
+    /// Seconds since the emitter started
+    elapsed_time: f32,
+    /// Particle lifetime (seconds)
+    lifetime: f32,
+    direction: f32,
+    spread: f32,
+    min_speed: f32,
+    max_speed: f32,
+    particle_size: f32,
+    shape: u32,
+    /// Whether the scene forces replace `gravity` and `wind`
+    scene_forces: u32,
+}
+
+/// Spawned emitter along with the GPU resources for its particles
+pub struct Emitter {
+    pub stage: usize,
+    /// Scene time the emitter started
+    pub start_time: Duration,
+    /// Time the emitter is alive for (ms)
+    duration: f32,
+    params: EmitterParams,
+    params_buffer: ConstantBuffer<EmitterParams>,
+    particles: StructuredBuffer,
+    particles_view: ShaderResourceView,
+}
+
+impl Emitter {
+    /// Creates the particles for the definition, `stage_size` is the size of
+    /// the stage the emitter renders into (pixels)
+    pub fn create(
+        device: &ID3D11Device,
+        definition: &EmitterDefinition,
+        stage_size: Vector2<f32>,
+        start_time: Duration,
+        rng: &mut impl Rng,
+    ) -> anyhow::Result<Emitter> {
+        definition.validate()?;
+
+        // Particles are spread evenly over the emission time
+        let emission_time = definition.emission_time / 1000.0;
+        let particles: Vec<Particle> = (0..definition.particle_count)
+            .map(|index| Particle {
+                seed: rng.gen(),
+                spawn_time: emission_time * index as f32 / definition.particle_count as f32,
+            })
+            .collect();
+
+        let forces = definition.forces.unwrap_or_default();
+        let params = EmitterParams {
+            color_a: definition.colors[0],
+            color_b: definition.colors[1],
+            origin: definition.position.component_mul(&stage_size),
+            stage_size,
+            gravity: forces.gravity,
+            wind: forces.wind,
+            elapsed_time: 0.0,
+            lifetime: definition.lifetime / 1000.0,
+            direction: definition.direction,
+            spread: definition.spread,
+            min_speed: definition.min_speed,
+            max_speed: definition.max_speed,
+            particle_size: definition.particle_size,
+            shape: definition.shape as u32,
+            scene_forces: definition.forces.is_none() as u32,
+        };
+
+        let mut particles = StructuredBuffer::create_from_array(device, &particles)?;
+        let particles_view =
+            ShaderResourceView::create_from_structured_buffer(device, &mut particles)?;
+
+        Ok(Emitter {
+            stage: definition.stage,
+            start_time,
+            duration: definition.total_duration(),
+            params_buffer: ConstantBuffer::create(device, params)?,
+            params,
+            particles,
+            particles_view,
+        })
+    }
+
+    /// Advances the emitter to the current scene time
+    pub fn update(&mut self, ctx: &ID3D11DeviceContext, clock: &SceneClock) -> anyhow::Result<()> {
+        let elapsed = clock.now().saturating_sub(self.start_time).as_secs_f64();
+        self.params.elapsed_time = elapsed as f32;
+        self.params_buffer.replace(ctx, &self.params)
+    }
+
+    /// Whether every particle has finished its lifetime
+    pub fn is_expired(&self) -> bool {
+        self.params.elapsed_time * 1000.0 > self.duration
+    }
+}
+
+/// Shaders and state shared by every emitter
+pub struct EmitterRenderContext {
+    vertex: VertexShader,
+    pixel: PixelShader,
+    blend_state: BlendState,
+}
+
+impl EmitterRenderContext {
+    pub fn create(device: &ID3D11Device) -> anyhow::Result<EmitterRenderContext> {
+        let vertex_blob = load_shader!("particle.hlsl", "vs_5_0", "VSMain")?;
+        let pixel_blob = load_shader!("particle.hlsl", "ps_5_0", "PSMain")?;
+
+        Ok(EmitterRenderContext {
+            vertex: VertexShader::create(device, vertex_blob)?,
+            pixel: PixelShader::create(device, pixel_blob)?,
+            blend_state: BlendState::additive_state(device)?,
+        })
+    }
+
+    /// Draws the particles of each emitter, replaces the bound shaders and
+    /// blend state so the caller must restore its own pipeline afterwards.
+    /// Emitters following the scene forces read them from the item globals
+    /// which must be bound for the stage
+    pub fn render<'a>(
+        &mut self,
+        ctx: &ID3D11DeviceContext,
+        emitters: impl Iterator<Item = &'a mut Emitter>,
+    ) {
+        self.vertex.set_shader(ctx);
+        self.pixel.set_shader(ctx);
+        self.blend_state.bind(ctx);
+
+        unsafe {
+            // Vertices come from the vertex and instance ids
+            ctx.IASetInputLayout(std::ptr::null_mut());
+            ctx.IASetPrimitiveTopology(D3D11_PRIMITIVE_TOPOLOGY_TRIANGLELIST);
+        }
+
+        for emitter in emitters {
+            emitter.particles_view.bind_vertex(ctx, 0);
+
+            unsafe {
+                let params = emitter.params_buffer.buffer.as_ptr();
+                ctx.VSSetConstantBuffers(0, 1, &params);
+                ctx.PSSetConstantBuffers(0, 1, &params);
+                ctx.DrawInstanced(PARTICLE_VERTICES, emitter.particles.len, 0, 0);
+            }
+        }
+
+        ShaderResourceView::unbind_vertex(ctx, 0);
+    }
+}
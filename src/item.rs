@@ -1,86 +1,1714 @@
-use std::{path::PathBuf, time::Instant};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    time::Duration,
+};
 
-use nalgebra::{Vector2, Vector3};
+use anyhow::Context;
+use crossbeam::queue::SegQueue;
+use nalgebra::{Vector2, Vector3, Vector4};
+use rand::Rng;
+use tokio::sync::mpsc;
+use uuid::Uuid;
 use winapi::{
-    shared::dxgiformat::{
-        DXGI_FORMAT_R32G32B32_FLOAT, DXGI_FORMAT_R32G32_FLOAT, DXGI_FORMAT_R32_UINT,
-    },
+    shared::dxgiformat::{DXGI_FORMAT_R32G32B32_FLOAT, DXGI_FORMAT_R32G32_FLOAT},
     um::{
         d3d11::{
-            ID3D11Device, ID3D11DeviceContext, D3D11_INPUT_ELEMENT_DESC,
-            D3D11_INPUT_PER_VERTEX_DATA,
+            ID3D11Device, ID3D11DeviceContext, D3D11_FILTER_MIN_MAG_MIP_LINEAR,
+            D3D11_FILTER_MIN_MAG_MIP_POINT, D3D11_INPUT_ELEMENT_DESC, D3D11_INPUT_PER_VERTEX_DATA,
         },
         d3dcommon::D3D11_PRIMITIVE_TOPOLOGY_TRIANGLELIST,
     },
 };
 
 use crate::{
+    clock::SceneClock,
     dx::{
-        buffer::{ConstantBuffer, IndexBuffer, VertexBuffer},
-        sampler::SamplerState,
+        buffer::ConstantBuffer,
+        device::Viewport,
+        mesh::Mesh,
+        sampler::{SamplerLod, SamplerState},
         shader::{PixelShader, ShaderBlob, ShaderInputLayout, ShaderResourceView, VertexShader},
         texture::Texture,
     },
-    texture_loader::TextureData,
+    load_shader,
+    texture_loader::{
+        load_texture_data, load_texture_data_color_keyed, ColorKey, GradientDirection, TextureData,
+        DEFAULT_MAX_TEXTURE_DIMENSION,
+    },
 };
 
+/// Source of the texture for an item
+#[derive(Debug, Clone)]
+pub enum ItemTexture {
+    /// Image file loaded from disk
+    Path(PathBuf),
+    /// Generated texture of a single color (RGBA)
+    SolidColor { size: Vector2<u32>, color: [u8; 4] },
+    /// Generated texture blending between two colors (RGBA)
+    LinearGradient {
+        size: Vector2<u32>,
+        from: [u8; 4],
+        to: [u8; 4],
+        direction: GradientDirection,
+    },
+}
+
+impl ItemTexture {
+    /// Loads or generates the texture data, the color key only applies
+    /// to textures loaded from disk
+    async fn load(&self, color_key: Option<ColorKey>) -> anyhow::Result<TextureData> {
+        match self {
+            ItemTexture::Path(path) => load_item_texture(path, color_key).await,
+            ItemTexture::SolidColor { size, color } => {
+                Ok(TextureData::solid_color(size.x, size.y, *color))
+            }
+            ItemTexture::LinearGradient {
+                size,
+                from,
+                to,
+                direction,
+            } => Ok(TextureData::linear_gradient(
+                size.x, size.y, *from, *to, *direction,
+            )),
+        }
+    }
+
+    /// Size of generated textures, [None] for textures loaded from disk
+    fn generated_size(&self) -> Option<Vector2<u32>> {
+        match self {
+            ItemTexture::Path(_) => None,
+            ItemTexture::SolidColor { size, .. } | ItemTexture::LinearGradient { size, .. } => {
+                Some(*size)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for ItemTexture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ItemTexture::Path(path) => path.display().fmt(f),
+            ItemTexture::SolidColor { .. } => f.write_str("solid color"),
+            ItemTexture::LinearGradient { .. } => f.write_str("linear gradient"),
+        }
+    }
+}
+
 /// Definition of an item to be thrown
 pub struct ItemDefinition {
-    // Path to the throwable
-    pub texture_path: PathBuf,
+    // Texture of the throwable
+    pub texture: ItemTexture,
     // Whether to pixelate the texture when scaling during render
     pub pixelate: bool,
+    /// Number of pixelation blocks across the texture, 0 disables pixelation
+    pub pixelate_size: f32,
     /// Scale for the image
     pub scale: f32,
+    /// Optional texture shown on the back of the item when it flips
+    pub back_texture_path: Option<PathBuf>,
+    /// Optional tangent space normal map lighting the front of the item
+    pub normal_texture_path: Option<PathBuf>,
+    /// Optional mask shaping the item, the red channel scales the alpha
+    pub mask_texture_path: Option<PathBuf>,
+    /// Duration the item should exist for (ms), [None] persists until
+    /// the item is removed
+    pub duration: Option<f32>,
+    /// Delay before the item appears (ms), the item is hidden until then
+    /// and its animation starts once it appears
+    pub start_delay: f32,
+    /// Whether the animation restarts each time the duration ends instead
+    /// of the item despawning, looping items persist until removed
+    pub looping: bool,
+    /// Fixed transform for static items, replaces the motion and rotation
+    pub transform: Option<ItemTransform>,
+    /// Optional text label rendered alongside the item
+    pub label: Option<ItemLabel>,
+    /// Motion of the item over its lifetime
+    pub motion: ItemMotion,
+    /// Point of the item placed on the start position
+    pub start_anchor: ItemAnchor,
+    /// Point of the item placed on the end position
+    pub end_anchor: ItemAnchor,
+    /// Rotation of the item
+    pub rotation: ItemRotation,
+    /// Cell to display when the texture is a sprite sheet
+    pub sprite_sheet: Option<SpriteSheetCell>,
+    /// Animation to play through the sprite sheet cells
+    pub sprite_animation: Option<SpriteAnimation>,
+    /// Optional drop shadow rendered behind the item
+    pub shadow: Option<ItemShadow>,
+    /// Optional outline drawn around the item
+    pub outline: Option<ItemOutline>,
+    /// Optional squash and stretch while moving
+    pub stretch: Option<ItemStretch>,
+    /// Optional jelly like wobble of the item surface
+    pub wobble: Option<ItemWobble>,
+    /// Registered effect replacing the item shaders, see [ItemEffectId]
+    pub effect: Option<ItemEffectId>,
+    /// Alpha below which pixels are discarded, cutout items are drawn
+    /// without blending
+    pub alpha_cutoff: Option<f32>,
+    /// Forces moving the item, see [ItemForces]
+    pub forces: ItemForces,
+    /// Filter used when the item is scaled up
+    pub upscale_filter: UpscaleFilter,
+    /// Shape the item is cut into, see [ItemShape]
+    pub shape: ItemShape,
+    /// Speed the hue cycles at (full cycles per second), 0 keeps the
+    /// original colors
+    pub color_cycle_speed: f32,
+    /// Whether to mirror the texture horizontally
+    pub flip_u: bool,
+    /// Whether to mirror the texture vertically
+    pub flip_v: bool,
+    /// Color treated as transparent when loading the textures
+    pub color_key: Option<ColorKey>,
+    /// Stage to render the item into
+    pub stage: usize,
+    /// Layer the item is drawn in, higher orders are drawn on top
+    pub order: i32,
+}
+
+/// Speed an item spins at, positive speeds spin counter-clockwise
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpinSpeed {
+    /// Full rotations per second
+    RotationsPerSecond(f32),
+    /// Degrees per second
+    DegreesPerSecond(f32),
+}
+
+impl SpinSpeed {
+    /// Converts a speed in the old milliseconds per rotation unit, speeds
+    /// previously set to 5000.0 become 0.2 rotations per second
+    pub fn from_ms_per_rotation(ms: f32) -> SpinSpeed {
+        if ms == 0.0 {
+            return SpinSpeed::RotationsPerSecond(0.0);
+        }
+
+        SpinSpeed::RotationsPerSecond(1000.0 / ms)
+    }
+
+    /// Speed in radians per second as used by the shaders
+    pub fn radians_per_second(&self) -> f32 {
+        match *self {
+            SpinSpeed::RotationsPerSecond(rotations) => rotations * std::f32::consts::TAU,
+            SpinSpeed::DegreesPerSecond(degrees) => degrees.to_radians(),
+        }
+    }
+}
+
+/// Spin configuration for an item, the variation and direction are
+/// randomized per item when it is spawned
+#[derive(Debug, Clone, Copy)]
+pub struct ItemSpin {
+    /// Speed to spin at
+    pub speed: SpinSpeed,
+    /// Random variation applied to the speed as a fraction of the
+    /// speed (0.2 varies the speed by up to ±20%)
+    pub variation: f32,
+    /// Whether to randomly pick between clockwise and counter-clockwise
+    pub random_direction: bool,
+}
+
+impl Default for ItemSpin {
+    fn default() -> Self {
+        Self {
+            speed: SpinSpeed::RotationsPerSecond(0.2),
+            variation: 0.0,
+            random_direction: false,
+        }
+    }
+}
+
+impl ItemSpin {
+    /// Picks the spin speed (radians per second) for a newly spawned item,
+    /// negative speeds reverse the direction
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> f32 {
+        let mut speed = self.speed.radians_per_second();
+
+        if self.variation > 0.0 {
+            speed *= 1.0 + rng.gen_range(-self.variation..=self.variation);
+        }
+
+        if self.random_direction && rng.gen_bool(0.5) {
+            speed = -speed;
+        }
+
+        speed
+    }
+}
+
+/// Point of an item placed on its start or end position, positions are
+/// item centers by default. Anchors ignore the item rotation
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum ItemAnchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    #[default]
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+    /// Point relative to the item bounds, (0, 0) is the top left corner
+    /// and (1, 1) the bottom right corner
+    Relative(Vector2<f32>),
+}
+
+impl ItemAnchor {
+    /// Point relative to the item bounds, (0, 0) is the top left corner
+    pub fn relative(&self) -> Vector2<f32> {
+        match *self {
+            ItemAnchor::TopLeft => Vector2::new(0.0, 0.0),
+            ItemAnchor::Top => Vector2::new(0.5, 0.0),
+            ItemAnchor::TopRight => Vector2::new(1.0, 0.0),
+            ItemAnchor::Left => Vector2::new(0.0, 0.5),
+            ItemAnchor::Center => Vector2::new(0.5, 0.5),
+            ItemAnchor::Right => Vector2::new(1.0, 0.5),
+            ItemAnchor::BottomLeft => Vector2::new(0.0, 1.0),
+            ItemAnchor::Bottom => Vector2::new(0.5, 1.0),
+            ItemAnchor::BottomRight => Vector2::new(1.0, 1.0),
+            ItemAnchor::Relative(point) => point,
+        }
+    }
+
+    /// Offset from the anchor point to the item center (pixels) for an
+    /// item of `size` pixels
+    pub fn center_offset(&self, size: &Vector2<f32>) -> Vector2<f32> {
+        (Vector2::repeat(0.5) - self.relative()).component_mul(size)
+    }
+}
+
+/// Rotation of an item over its lifetime
+#[derive(Debug, Clone, Copy)]
+pub enum ItemRotation {
+    /// Keep a fixed angle (radians, counter-clockwise)
+    Fixed(f32),
+    /// Spin continuously
+    Spin(ItemSpin),
+    /// Point along the direction of travel, the angle (radians) is added
+    /// on top for textures that don't point right
+    FaceVelocity(f32),
+}
+
+impl Default for ItemRotation {
+    fn default() -> Self {
+        ItemRotation::Spin(ItemSpin::default())
+    }
+}
+
+impl ItemRotation {
+    /// Writes the rotation parameters into the item data, spin speeds are
+    /// sampled using the provided random source
+    pub fn apply<R: Rng>(&self, data: &mut ItemDataBuffer, rng: &mut R) {
+        match *self {
+            ItemRotation::Fixed(angle) => {
+                data.rotation_mode = RotationMode::Fixed as u32;
+                data.rotation = angle;
+            }
+            ItemRotation::Spin(spin) => {
+                data.rotation_mode = RotationMode::Spin as u32;
+                data.spin_speed = spin.sample(rng);
+            }
+            ItemRotation::FaceVelocity(angle) => {
+                data.rotation_mode = RotationMode::FaceVelocity as u32;
+                data.rotation = angle;
+            }
+        }
+    }
+}
+
+/// Rotation mode values understood by the vertex shader
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum RotationMode {
+    /// Rotate by the spin speed over time
+    Spin = 0,
+    /// Fixed angle
+    Fixed = 1,
+    /// Point along the direction of travel
+    FaceVelocity = 2,
+}
+
+/// Drop shadow rendered behind an item using the texture alpha as a mask
+#[derive(Debug, Clone, Copy)]
+pub struct ItemShadow {
+    /// Offset from the item in pixels
+    pub offset: Vector2<f32>,
+    /// Color of the shadow (RGBA)
+    pub color: Vector4<f32>,
+    /// Radius of the shadow blur in texels, the blur is computed once
+    /// when the item spawns. 0 uses a cheap per pixel softening instead
+    pub blur_radius: u32,
+}
+
+impl Default for ItemShadow {
+    fn default() -> Self {
+        Self {
+            offset: Vector2::new(8.0, 8.0),
+            color: Vector4::new(0.0, 0.0, 0.0, 0.5),
+            blur_radius: 4,
+        }
+    }
+}
+
+impl ItemShadow {
+    /// Writes the shadow into the item data
+    pub fn apply(&self, data: &mut ItemDataBuffer, screen_size: &Vector2<f32>) {
+        // Offset is in pixels, convert to a screen space offset
+        data.shadow_offset = Vector2::new(
+            2.0 * self.offset.x / screen_size.x,
+            -2.0 * self.offset.y / screen_size.y,
+        );
+        data.shadow_color = self.color;
+        data.shadow_enabled = 1;
+        data.shadow_blurred = (self.blur_radius > 0) as u32;
+    }
+}
+
+/// Colored border drawn around the alpha edge of an item, the outline
+/// is limited to the bounds of the item quad so textures need some
+/// transparent padding for the full width to show
+#[derive(Debug, Clone, Copy)]
+pub struct ItemOutline {
+    /// Color of the outline (RGBA)
+    pub color: Vector4<f32>,
+    /// Width of the outline in texels
+    pub width: f32,
+}
+
+impl ItemOutline {
+    /// Writes the outline into the item data
+    pub fn apply(&self, data: &mut ItemDataBuffer) {
+        data.outline_color = self.color;
+        data.outline_width = self.width;
+        data.outline_enabled = 1;
+    }
+}
+
+/// Cartoon squash and stretch, the item stretches along its direction of
+/// travel as it moves and squashes briefly when it lands. The deformation
+/// preserves the area of the item
+#[derive(Debug, Clone, Copy)]
+pub struct ItemStretch {
+    /// Stretch per unit of speed (screen heights per second)
+    pub intensity: f32,
+    /// Largest factor the item is stretched or squashed by
+    pub max: f32,
+}
+
+impl Default for ItemStretch {
+    fn default() -> Self {
+        Self {
+            intensity: 0.3,
+            max: 1.6,
+        }
+    }
+}
+
+impl ItemStretch {
+    /// Writes the stretch into the item data
+    pub fn apply(&self, data: &mut ItemDataBuffer) {
+        data.stretch_intensity = self.intensity;
+        data.stretch_max = self.max;
+    }
+}
+
+/// Number of cells along each side of the grid wobbling items are drawn
+/// with
+pub const WOBBLE_GRID_SEGMENTS: u32 = 16;
+
+/// Jelly like wobble, sine waves run across the item bending its edges.
+/// Wobbling items are drawn with a grid mesh whose vertices are rewritten
+/// each frame
+#[derive(Debug, Clone, Copy)]
+pub struct ItemWobble {
+    /// Largest offset of the surface (Relative to the item size)
+    pub amplitude: f32,
+    /// Speed the waves travel at (cycles per second)
+    pub frequency: f32,
+    /// Number of waves across the item
+    pub waves: f32,
+}
+
+impl Default for ItemWobble {
+    fn default() -> Self {
+        Self {
+            amplitude: 0.05,
+            frequency: 2.0,
+            waves: 1.0,
+        }
+    }
+}
+
+impl ItemWobble {
+    /// Vertices of the item grid at `phase` (0-1 of a full cycle), in the
+    /// order [create_item_grid] emits them
+    pub fn vertices(&self, segments: u32, phase: f32) -> Vec<ItemVertex> {
+        grid_points(segments)
+            .map(|(u, v)| {
+                let wave = |t: f32| {
+                    self.amplitude * (std::f32::consts::TAU * (self.waves * t + phase)).sin()
+                };
+
+                ItemVertex {
+                    pos: Vector2::new(u - 0.5 + wave(v), v - 0.5 + wave(u)),
+                    tex: Vector2::new(u, 1.0 - v),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Forces acting on the whole scene, items opt in with
+/// [ItemDefinitionBuilder::forces] and particle emitters follow them unless
+/// they set their own
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct SceneForces {
+    /// Constant acceleration (pixels per second squared), positive y pulls
+    /// down
+    pub gravity: Vector2<f32>,
+    /// Constant drift (pixels per second)
+    pub wind: Vector2<f32>,
+}
+
+/// Forces moving an item on top of its motion
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum ItemForces {
+    /// The item follows its motion only
+    #[default]
+    None,
+    /// Moved by the scene forces at the time the item spawns
+    Scene,
+    /// Moved by its own forces instead of the scene forces
+    Custom(SceneForces),
+}
+
+impl ItemForces {
+    /// Writes the forces into the item data, `stage_size` is the size of
+    /// the stage the item renders into (pixels)
+    pub fn apply(
+        &self,
+        scene_forces: &SceneForces,
+        stage_size: &Vector2<f32>,
+        data: &mut ItemDataBuffer,
+    ) {
+        let forces = match self {
+            ItemForces::None => SceneForces::default(),
+            ItemForces::Scene => *scene_forces,
+            ItemForces::Custom(forces) => *forces,
+        };
+
+        // Pixels to normalized device coordinates, y points up
+        let scale = Vector2::new(2.0 / stage_size.x, -2.0 / stage_size.y);
+        data.force_gravity = forces.gravity.component_mul(&scale);
+        data.force_wind = forces.wind.component_mul(&scale);
+    }
+}
+
+/// Fixed placement of a static item
+#[derive(Debug, Clone, Copy)]
+pub struct ItemTransform {
+    /// Position of the item center (Normalized to the stage size, 0-1)
+    pub position: Vector2<f32>,
+    /// Rotation of the item (radians, counter-clockwise)
+    pub rotation: f32,
+    /// Scale for the image, replaces the item scale
+    pub scale: f32,
+}
+
+impl Default for ItemTransform {
+    fn default() -> Self {
+        Self {
+            position: Vector2::new(0.5, 0.5),
+            rotation: 0.0,
+            scale: 1.0,
+        }
+    }
+}
+
+impl ItemTransform {
+    /// Writes the transform into the item data
+    pub fn apply(&self, data: &mut ItemDataBuffer) {
+        let position = Vector2::new(2.0 * self.position.x - 1.0, 1.0 - 2.0 * self.position.y);
+        data.start_position = position;
+        data.end_position = position;
+        data.arc_height = 0.0;
+        data.motion_mode = MotionMode::Arc as u32;
+        data.rotation_mode = RotationMode::Fixed as u32;
+        data.rotation = self.rotation;
+        data.scale = self.scale;
+    }
+}
+
+/// Shape an item is cut into in the fragment shader, lets rectangular
+/// textures display as badges without a masked texture. The outline
+/// follows the edge of the shape instead of the texture alpha
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum ItemShape {
+    /// Full quad, the texture alpha is unchanged
+    #[default]
+    Rectangle,
+    /// Largest circle that fits within the item
+    Circle,
+    /// Rectangle with rounded corners, the radius is in texture pixels
+    RoundedRect { corner_radius: f32 },
+}
+
+impl ItemShape {
+    /// Writes the shape into the item data
+    pub fn apply(&self, data: &mut ItemDataBuffer) {
+        match *self {
+            ItemShape::Rectangle => {
+                data.shape_mode = ShapeMode::Rectangle as u32;
+            }
+            ItemShape::Circle => {
+                data.shape_mode = ShapeMode::Circle as u32;
+            }
+            ItemShape::RoundedRect { corner_radius } => {
+                data.shape_mode = ShapeMode::RoundedRect as u32;
+                data.corner_radius = corner_radius.max(0.0);
+            }
+        }
+    }
+}
+
+/// Shape mode values understood by the fragment shader
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum ShapeMode {
+    /// Full quad
+    Rectangle = 0,
+    /// Circle fit within the quad
+    Circle = 1,
+    /// Rectangle with rounded corners
+    RoundedRect = 2,
+}
+
+/// Filter used when sampling item textures that are scaled up
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum UpscaleFilter {
+    /// Hardware bilinear filtering, cheapest but soft when scaled up a lot
+    #[default]
+    Bilinear = 0,
+    /// Bicubic Catmull-Rom filtering in the pixel shader, sharper edges
+    /// on large items at the cost of extra texture taps
+    CatmullRom = 1,
+}
+
+/// Scene wide override of the sampler chosen for each item
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SamplerOverride {
+    /// Items choose using their own pixelate setting
+    #[default]
+    PerItem,
+    /// Every item uses linear filtering
+    ForceLinear,
+    /// Every item uses point filtering for a pixelated look
+    ForcePixelate,
+}
+
+/// Text label attached to an item
+#[derive(Debug, Clone)]
+pub struct ItemLabel {
+    /// Text to display
+    pub text: String,
+    /// Height of the text in pixels
+    pub size: f32,
+    /// Offset from the center of the item in pixels
+    pub offset: Vector2<f32>,
+}
+
+impl ItemDefinition {
+    pub fn builder() -> ItemDefinitionBuilder {
+        ItemDefinitionBuilder::default()
+    }
+}
+
+/// Builder for creating [ItemDefinition]s with sensible defaults
+pub struct ItemDefinitionBuilder {
+    texture: Option<ItemTexture>,
+    pixelate: bool,
+    pixelate_size: f32,
+    scale: f32,
+    back_texture_path: Option<PathBuf>,
+    normal_texture_path: Option<PathBuf>,
+    mask_texture_path: Option<PathBuf>,
+    duration: Option<f32>,
+    start_delay: f32,
+    looping: bool,
+    transform: Option<ItemTransform>,
+    label: Option<ItemLabel>,
+    motion: ItemMotion,
+    start_anchor: ItemAnchor,
+    end_anchor: ItemAnchor,
+    rotation: ItemRotation,
+    sprite_sheet: Option<SpriteSheetCell>,
+    sprite_animation: Option<SpriteAnimation>,
+    shadow: Option<ItemShadow>,
+    outline: Option<ItemOutline>,
+    stretch: Option<ItemStretch>,
+    wobble: Option<ItemWobble>,
+    effect: Option<ItemEffectId>,
+    alpha_cutoff: Option<f32>,
+    forces: ItemForces,
+    upscale_filter: UpscaleFilter,
+    shape: ItemShape,
+    color_cycle_speed: f32,
+    flip_u: bool,
+    flip_v: bool,
+    color_key: Option<ColorKey>,
+    stage: usize,
+    order: i32,
+}
+
+impl Default for ItemDefinitionBuilder {
+    fn default() -> Self {
+        Self {
+            texture: None,
+            pixelate: false,
+            pixelate_size: 0.0,
+            scale: 1.0,
+            back_texture_path: None,
+            normal_texture_path: None,
+            mask_texture_path: None,
+            duration: Some(1000.0),
+            start_delay: 0.0,
+            looping: false,
+            transform: None,
+            label: None,
+            motion: ItemMotion::default(),
+            start_anchor: ItemAnchor::Center,
+            end_anchor: ItemAnchor::Center,
+            rotation: ItemRotation::default(),
+            sprite_sheet: None,
+            sprite_animation: None,
+            shadow: None,
+            outline: None,
+            stretch: None,
+            wobble: None,
+            effect: None,
+            alpha_cutoff: None,
+            forces: ItemForces::None,
+            upscale_filter: UpscaleFilter::default(),
+            shape: ItemShape::default(),
+            color_cycle_speed: 0.0,
+            flip_u: false,
+            flip_v: false,
+            color_key: None,
+            stage: 0,
+            order: 0,
+        }
+    }
+}
+
+impl ItemDefinitionBuilder {
+    pub fn texture_path(mut self, texture_path: impl Into<PathBuf>) -> Self {
+        self.texture = Some(ItemTexture::Path(texture_path.into()));
+        self
+    }
+
+    /// Uses a generated texture of a single color (RGBA) instead of a file
+    pub fn solid_color(mut self, width: u32, height: u32, color: [u8; 4]) -> Self {
+        self.texture = Some(ItemTexture::SolidColor {
+            size: Vector2::new(width, height),
+            color,
+        });
+        self
+    }
+
+    /// Uses a generated gradient texture (RGBA) instead of a file
+    pub fn linear_gradient(
+        mut self,
+        width: u32,
+        height: u32,
+        from: [u8; 4],
+        to: [u8; 4],
+        direction: GradientDirection,
+    ) -> Self {
+        self.texture = Some(ItemTexture::LinearGradient {
+            size: Vector2::new(width, height),
+            from,
+            to,
+            direction,
+        });
+        self
+    }
+
+    pub fn pixelate(mut self, pixelate: bool) -> Self {
+        self.pixelate = pixelate;
+        self
+    }
+
+    /// Quantizes the texture into `size` x `size` blocks for a retro look
+    /// independent of the texture resolution, 0 disables pixelation
+    pub fn pixelate_size(mut self, size: f32) -> Self {
+        self.pixelate_size = size;
+        self
+    }
+
+    pub fn scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn back_texture_path(mut self, back_texture_path: impl Into<PathBuf>) -> Self {
+        self.back_texture_path = Some(back_texture_path.into());
+        self
+    }
+
+    /// Sets a normal map lighting the front of the item, must match the
+    /// layout of the front texture
+    pub fn normal_texture_path(mut self, normal_texture_path: impl Into<PathBuf>) -> Self {
+        self.normal_texture_path = Some(normal_texture_path.into());
+        self
+    }
+
+    /// Sets a mask shaping the item, the red channel of the mask scales
+    /// the alpha of both faces
+    pub fn mask_texture_path(mut self, mask_texture_path: impl Into<PathBuf>) -> Self {
+        self.mask_texture_path = Some(mask_texture_path.into());
+        self
+    }
+
+    pub fn duration(mut self, duration: f32) -> Self {
+        self.duration = Some(duration);
+        self
+    }
+
+    /// Delays the item appearing by `start_delay` (ms), staggering the
+    /// delays of items sent together cascades them
+    pub fn start_delay(mut self, start_delay: f32) -> Self {
+        self.start_delay = start_delay;
+        self
+    }
+
+    /// Restarts the animation each time the duration ends instead of
+    /// despawning, spin, orbits and color cycling continue across the
+    /// restart so only the motion along the path repeats
+    pub fn looping(mut self) -> Self {
+        self.looping = true;
+        self
+    }
+
+    /// Keeps the item until it is removed instead of despawning once its
+    /// duration ends
+    pub fn persistent(mut self) -> Self {
+        self.duration = None;
+        self
+    }
+
+    /// Places the item at a fixed transform instead of moving it, static
+    /// items persist until removed (unless given a duration afterwards)
+    /// and can be moved using [crate::Renderer::set_item_transform]
+    pub fn static_transform(mut self, transform: ItemTransform) -> Self {
+        self.transform = Some(transform);
+        self.duration = None;
+        self
+    }
+
+    pub fn label(mut self, label: ItemLabel) -> Self {
+        self.label = Some(label);
+        self
+    }
+
+    pub fn motion(mut self, motion: ItemMotion) -> Self {
+        self.motion = motion;
+        self
+    }
+
+    /// Places the same point of the item on both the start and end
+    /// positions, see [ItemAnchor]
+    pub fn anchor(mut self, anchor: ItemAnchor) -> Self {
+        self.start_anchor = anchor;
+        self.end_anchor = anchor;
+        self
+    }
+
+    /// Point of the item placed on the start position, e.g.
+    /// [ItemAnchor::Right] with a start on the left edge of the stage
+    /// starts the item just out of view
+    pub fn start_anchor(mut self, anchor: ItemAnchor) -> Self {
+        self.start_anchor = anchor;
+        self
+    }
+
+    /// Point of the item placed on the end position
+    pub fn end_anchor(mut self, anchor: ItemAnchor) -> Self {
+        self.end_anchor = anchor;
+        self
+    }
+
+    pub fn spin(mut self, spin: ItemSpin) -> Self {
+        self.rotation = ItemRotation::Spin(spin);
+        self
+    }
+
+    pub fn rotation(mut self, rotation: ItemRotation) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Displays a single cell from a sprite sheet texture
+    pub fn sprite_sheet(mut self, columns: u32, rows: u32, index: u32) -> Self {
+        self.sprite_sheet = Some(SpriteSheetCell {
+            columns,
+            rows,
+            index,
+        });
+        self
+    }
+
+    /// Animates through the sprite sheet cells, requires a sprite sheet
+    pub fn sprite_animation(mut self, frame_count: u32, fps: f32, looping: bool) -> Self {
+        self.sprite_animation = Some(SpriteAnimation {
+            frame_count,
+            fps,
+            looping,
+        });
+        self
+    }
+
+    pub fn shadow(mut self, shadow: ItemShadow) -> Self {
+        self.shadow = Some(shadow);
+        self
+    }
+
+    pub fn outline(mut self, outline: ItemOutline) -> Self {
+        self.outline = Some(outline);
+        self
+    }
+
+    /// Squashes and stretches the item along its direction of travel
+    pub fn stretch(mut self, stretch: ItemStretch) -> Self {
+        self.stretch = Some(stretch);
+        self
+    }
+
+    /// Wobbles the item surface like jelly
+    pub fn wobble(mut self, wobble: ItemWobble) -> Self {
+        self.wobble = Some(wobble);
+        self
+    }
+
+    /// Draws the item with a registered effect instead of the built in
+    /// item shaders, see [Renderer::register_item_effect](crate::Renderer::register_item_effect)
+    pub fn effect(mut self, effect: ItemEffectId) -> Self {
+        self.effect = Some(effect);
+        self
+    }
+
+    /// Draws the item as a hard edged cutout, pixels with an alpha below
+    /// `cutoff` (0-1) are discarded and the rest are drawn opaque. Avoids
+    /// the dark halo blending gives the edges of pixel art
+    pub fn alpha_cutoff(mut self, cutoff: f32) -> Self {
+        self.alpha_cutoff = Some(cutoff);
+        self
+    }
+
+    /// Moves the item by gravity and wind on top of its motion, e.g. for
+    /// items falling like snow. See [Renderer::set_scene_forces](crate::Renderer::set_scene_forces)
+    pub fn forces(mut self, forces: ItemForces) -> Self {
+        self.forces = forces;
+        self
+    }
+
+    /// Sets the filter used when the item is scaled up
+    pub fn upscale_filter(mut self, upscale_filter: UpscaleFilter) -> Self {
+        self.upscale_filter = upscale_filter;
+        self
+    }
+
+    /// Sets the shape the item is cut into
+    pub fn shape(mut self, shape: ItemShape) -> Self {
+        self.shape = shape;
+        self
+    }
+
+    /// Cycles the hue of the item over time, `speed` is in full cycles
+    /// per second and negative speeds cycle backwards
+    pub fn color_cycle(mut self, speed: f32) -> Self {
+        self.color_cycle_speed = speed;
+        self
+    }
+
+    /// Mirrors the texture horizontally
+    pub fn flip_u(mut self, flip_u: bool) -> Self {
+        self.flip_u = flip_u;
+        self
+    }
+
+    /// Mirrors the texture vertically, useful for sources or receivers
+    /// that end up upside down
+    pub fn flip_v(mut self, flip_v: bool) -> Self {
+        self.flip_v = flip_v;
+        self
+    }
+
+    /// Treats pixels within `tolerance` of `color` as transparent, for
+    /// images that use a solid background color instead of alpha
+    pub fn color_key(mut self, color: [u8; 3], tolerance: u8) -> Self {
+        self.color_key = Some(ColorKey { color, tolerance });
+        self
+    }
+
+    /// Renders the item into a stage other than the primary stage
+    pub fn stage(mut self, stage: usize) -> Self {
+        self.stage = stage;
+        self
+    }
+
+    /// Sets the layer the item is drawn in, items with a higher order are
+    /// drawn on top and equal orders are drawn in spawn order
+    pub fn order(mut self, order: i32) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Validates and creates the item definition
+    pub fn build(self) -> anyhow::Result<ItemDefinition> {
+        let texture = self
+            .texture
+            .ok_or_else(|| anyhow::anyhow!("item definition is missing a texture"))?;
+
+        if let Some(size) = texture.generated_size() {
+            if size.x == 0 || size.y == 0 {
+                anyhow::bail!(
+                    "generated item texture must not be empty (got {}x{})",
+                    size.x,
+                    size.y
+                );
+            }
+        }
+
+        if self.pixelate_size < 0.0 {
+            anyhow::bail!(
+                "item pixelate size must not be negative (got {})",
+                self.pixelate_size
+            );
+        }
+
+        if self.scale <= 0.0 {
+            anyhow::bail!("item scale must be greater than zero (got {})", self.scale);
+        }
+
+        if let Some(animation) = &self.sprite_animation {
+            let sprite_sheet = self
+                .sprite_sheet
+                .as_ref()
+                .ok_or_else(|| anyhow::anyhow!("item sprite animation requires a sprite sheet"))?;
+
+            let cell_count = sprite_sheet.columns * sprite_sheet.rows;
+            if sprite_sheet.index + animation.frame_count > cell_count {
+                anyhow::bail!(
+                    "item sprite animation exceeds the sprite sheet ({} frames from cell {}, sheet has {} cells)",
+                    animation.frame_count,
+                    sprite_sheet.index,
+                    cell_count
+                );
+            }
+
+            if animation.fps <= 0.0 {
+                anyhow::bail!(
+                    "item sprite animation fps must be greater than zero (got {})",
+                    animation.fps
+                );
+            }
+        }
+
+        if let Some(outline) = &self.outline {
+            if outline.width < 0.0 {
+                anyhow::bail!(
+                    "item outline width must not be negative (got {})",
+                    outline.width
+                );
+            }
+        }
+
+        if let Some(stretch) = &self.stretch {
+            if stretch.intensity < 0.0 {
+                anyhow::bail!(
+                    "item stretch intensity must not be negative (got {})",
+                    stretch.intensity
+                );
+            }
+
+            if stretch.max < 1.0 {
+                anyhow::bail!("item stretch max must be at least 1 (got {})", stretch.max);
+            }
+        }
+
+        if let Some(cutoff) = self.alpha_cutoff {
+            if !(cutoff > 0.0 && cutoff <= 1.0) {
+                anyhow::bail!("item alpha cutoff must be between 0 and 1 (got {cutoff})");
+            }
+        }
+
+        if let Some(wobble) = &self.wobble {
+            for (name, value) in [
+                ("amplitude", wobble.amplitude),
+                ("frequency", wobble.frequency),
+                ("waves", wobble.waves),
+            ] {
+                if !value.is_finite() || value < 0.0 {
+                    anyhow::bail!("item wobble {name} must be a non-negative number (got {value})");
+                }
+            }
+        }
+
+        if let Some(duration) = self.duration {
+            if duration <= 0.0 {
+                anyhow::bail!("item duration must be greater than zero (got {duration})");
+            }
+        }
+
+        if self.looping && self.duration.is_none() {
+            anyhow::bail!("looping items need a duration to loop over");
+        }
+
+        if !(self.start_delay >= 0.0 && self.start_delay.is_finite()) {
+            anyhow::bail!(
+                "item start delay must be a finite non-negative value (got {})",
+                self.start_delay
+            );
+        }
+
+        if let Some(transform) = &self.transform {
+            if transform.scale <= 0.0 {
+                anyhow::bail!(
+                    "item transform scale must be greater than zero (got {})",
+                    transform.scale
+                );
+            }
+        }
+
+        Ok(ItemDefinition {
+            texture,
+            pixelate: self.pixelate,
+            pixelate_size: self.pixelate_size,
+            scale: self.scale,
+            back_texture_path: self.back_texture_path,
+            normal_texture_path: self.normal_texture_path,
+            mask_texture_path: self.mask_texture_path,
+            duration: self.duration,
+            start_delay: self.start_delay,
+            looping: self.looping,
+            transform: self.transform,
+            label: self.label,
+            motion: self.motion,
+            start_anchor: self.start_anchor,
+            end_anchor: self.end_anchor,
+            rotation: self.rotation,
+            sprite_sheet: self.sprite_sheet,
+            sprite_animation: self.sprite_animation,
+            shadow: self.shadow,
+            outline: self.outline,
+            stretch: self.stretch,
+            wobble: self.wobble,
+            effect: self.effect,
+            alpha_cutoff: self.alpha_cutoff,
+            forces: self.forces,
+            upscale_filter: self.upscale_filter,
+            shape: self.shape,
+            color_cycle_speed: self.color_cycle_speed,
+            flip_u: self.flip_u,
+            flip_v: self.flip_v,
+            color_key: self.color_key,
+            stage: self.stage,
+            order: self.order,
+        })
+    }
+}
+
+/// Item definition queued to be spawned in the world
+pub struct QueuedItemDefinition {
+    /// ID the item will be spawned with
+    pub id: ItemId,
+    /// Texture of the item to spawn, [None] uses the placeholder texture
+    pub texture_data: Option<TextureData>,
+    /// Texture for the back face of the item
+    pub back_texture_data: Option<TextureData>,
+    /// Normal map for the front face of the item
+    pub normal_texture_data: Option<TextureData>,
+    /// Mask shaping the item
+    pub mask_texture_data: Option<TextureData>,
+    // Whether to pixelate the texture when scaling during render
+    pub pixelate: bool,
+    /// Number of pixelation blocks across the texture, 0 disables pixelation
+    pub pixelate_size: f32,
+    /// Scale for the image
+    pub scale: f32,
+    /// Duration the item should exist for (ms), [None] persists until
+    /// the item is removed
+    pub duration: Option<f32>,
+    /// Delay before the item appears (ms), the item is hidden until then
+    /// and its animation starts once it appears
+    pub start_delay: f32,
+    /// Whether the animation restarts each time the duration ends instead
+    /// of the item despawning, looping items persist until removed
+    pub looping: bool,
+    /// Fixed transform for static items
+    pub transform: Option<ItemTransform>,
+    /// Optional text label rendered alongside the item
+    pub label: Option<ItemLabel>,
+    /// Motion of the item over its lifetime
+    pub motion: ItemMotion,
+    /// Point of the item placed on the start position
+    pub start_anchor: ItemAnchor,
+    /// Point of the item placed on the end position
+    pub end_anchor: ItemAnchor,
+    /// Rotation of the item
+    pub rotation: ItemRotation,
+    /// Cell to display when the texture is a sprite sheet
+    pub sprite_sheet: Option<SpriteSheetCell>,
+    /// Animation to play through the sprite sheet cells
+    pub sprite_animation: Option<SpriteAnimation>,
+    /// Optional drop shadow rendered behind the item
+    pub shadow: Option<ItemShadow>,
+    /// Optional outline drawn around the item
+    pub outline: Option<ItemOutline>,
+    /// Optional squash and stretch while moving
+    pub stretch: Option<ItemStretch>,
+    /// Optional jelly like wobble of the item surface
+    pub wobble: Option<ItemWobble>,
+    /// Registered effect replacing the item shaders, see [ItemEffectId]
+    pub effect: Option<ItemEffectId>,
+    /// Alpha below which pixels are discarded, cutout items are drawn
+    /// without blending
+    pub alpha_cutoff: Option<f32>,
+    /// Forces moving the item, see [ItemForces]
+    pub forces: ItemForces,
+    /// Filter used when the item is scaled up
+    pub upscale_filter: UpscaleFilter,
+    /// Shape the item is cut into, see [ItemShape]
+    pub shape: ItemShape,
+    /// Speed the hue cycles at (full cycles per second), 0 keeps the
+    /// original colors
+    pub color_cycle_speed: f32,
+    /// Whether to mirror the texture horizontally
+    pub flip_u: bool,
+    /// Whether to mirror the texture vertically
+    pub flip_v: bool,
+    /// Stage to render the item into
+    pub stage: usize,
+    /// Layer the item is drawn in
+    pub order: i32,
+}
+
+impl QueuedItemDefinition {
+    /// Loads the textures for an item definition ready for spawning
+    pub async fn load(
+        id: ItemId,
+        definition: ItemDefinition,
+    ) -> anyhow::Result<QueuedItemDefinition> {
+        let texture_data = definition.texture.load(definition.color_key).await?;
+
+        let back_texture_data = match &definition.back_texture_path {
+            Some(path) => Some(load_item_texture(path, definition.color_key).await?),
+            None => None,
+        };
+
+        // Normal maps store directions rather than colors, never color key them
+        let normal_texture_data = match &definition.normal_texture_path {
+            Some(path) => Some(load_item_texture(path, None).await?),
+            None => None,
+        };
+
+        let mask_texture_data = match &definition.mask_texture_path {
+            Some(path) => Some(load_item_texture(path, None).await?),
+            None => None,
+        };
+
+        Ok(Self::from_definition(
+            id,
+            definition,
+            Some(texture_data),
+            back_texture_data,
+            normal_texture_data,
+            mask_texture_data,
+        ))
+    }
+
+    /// Loads the textures for an item definition, textures that fail to
+    /// load are logged and replaced with the placeholder texture so a bad
+    /// asset never prevents the item from spawning
+    pub async fn load_or_placeholder(
+        id: ItemId,
+        definition: ItemDefinition,
+    ) -> QueuedItemDefinition {
+        let texture_data = definition
+            .texture
+            .load(definition.color_key)
+            .await
+            .inspect_err(|err| {
+                eprintln!(
+                    "failed to load item texture {}, using placeholder: {err}",
+                    definition.texture
+                )
+            })
+            .ok();
+
+        // Items without a back texture show the front on both sides
+        let back_texture_data = match &definition.back_texture_path {
+            Some(path) => load_item_texture(path, definition.color_key)
+                .await
+                .inspect_err(|err| {
+                    eprintln!("failed to load item back texture {}: {err}", path.display())
+                })
+                .ok(),
+            None => None,
+        };
+
+        // Items without a normal map are drawn unlit
+        let normal_texture_data = match &definition.normal_texture_path {
+            Some(path) => load_item_texture(path, None)
+                .await
+                .inspect_err(|err| {
+                    eprintln!(
+                        "failed to load item normal texture {}: {err}",
+                        path.display()
+                    )
+                })
+                .ok(),
+            None => None,
+        };
+
+        // Items with a mask that fails to load are shown unmasked
+        let mask_texture_data = match &definition.mask_texture_path {
+            Some(path) => load_item_texture(path, None)
+                .await
+                .inspect_err(|err| {
+                    eprintln!("failed to load item mask texture {}: {err}", path.display())
+                })
+                .ok(),
+            None => None,
+        };
+
+        Self::from_definition(
+            id,
+            definition,
+            texture_data,
+            back_texture_data,
+            normal_texture_data,
+            mask_texture_data,
+        )
+    }
+
+    fn from_definition(
+        id: ItemId,
+        definition: ItemDefinition,
+        texture_data: Option<TextureData>,
+        back_texture_data: Option<TextureData>,
+        normal_texture_data: Option<TextureData>,
+        mask_texture_data: Option<TextureData>,
+    ) -> QueuedItemDefinition {
+        QueuedItemDefinition {
+            id,
+            texture_data,
+            back_texture_data,
+            normal_texture_data,
+            mask_texture_data,
+            pixelate: definition.pixelate,
+            pixelate_size: definition.pixelate_size,
+            scale: definition.scale,
+            duration: definition.duration,
+            start_delay: definition.start_delay,
+            looping: definition.looping,
+            transform: definition.transform,
+            label: definition.label,
+            motion: definition.motion,
+            start_anchor: definition.start_anchor,
+            end_anchor: definition.end_anchor,
+            rotation: definition.rotation,
+            sprite_sheet: definition.sprite_sheet,
+            sprite_animation: definition.sprite_animation,
+            shadow: definition.shadow,
+            outline: definition.outline,
+            stretch: definition.stretch,
+            wobble: definition.wobble,
+            effect: definition.effect,
+            alpha_cutoff: definition.alpha_cutoff,
+            forces: definition.forces,
+            upscale_filter: definition.upscale_filter,
+            shape: definition.shape,
+            color_cycle_speed: definition.color_cycle_speed,
+            flip_u: definition.flip_u,
+            flip_v: definition.flip_v,
+            stage: definition.stage,
+            order: definition.order,
+        }
+    }
+}
+
+/// Reads and decodes an item texture from disk
+async fn load_item_texture(
+    path: &Path,
+    color_key: Option<ColorKey>,
+) -> anyhow::Result<TextureData> {
+    let data = tokio::fs::read(path).await?;
+
+    match color_key {
+        Some(color_key) => {
+            load_texture_data_color_keyed(data, Some(DEFAULT_MAX_TEXTURE_DIMENSION), color_key)
+                .await
+        }
+        None => load_texture_data(data, Some(DEFAULT_MAX_TEXTURE_DIMENSION)).await,
+    }
+}
+
+/// Queue of items waiting to be spawned in the world
+#[derive(Default)]
+pub struct ItemQueue {
+    queue: SegQueue<QueuedItemDefinition>,
+    /// Lock and condition used to wake the render loop when idle
+    signal: Mutex<()>,
+    pushed: Condvar,
+    /// Whether the queue has been closed, wakes any idle waiters
+    closed: AtomicBool,
+}
+
+impl ItemQueue {
+    pub fn push(&self, item: QueuedItemDefinition) {
+        self.queue.push(item);
+
+        // Lock before notifying so a waiter can't miss the push between
+        // checking the queue and waiting
+        let _guard = self.signal.lock().unwrap_or_else(|err| err.into_inner());
+        self.pushed.notify_all();
+    }
+
+    /// Closes the queue waking any waiting render loop, used when
+    /// shutting down
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+
+        let _guard = self.signal.lock().unwrap_or_else(|err| err.into_inner());
+        self.pushed.notify_all();
+    }
+
+    /// Whether the queue has been closed
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    /// Blocks until an item is pushed, the queue is closed, or the timeout
+    /// elapses, [None] waits indefinitely. Returns whether there are items
+    /// waiting
+    pub fn wait_for_items(&self, timeout: Option<Duration>) -> bool {
+        let guard = self.signal.lock().unwrap_or_else(|err| err.into_inner());
+
+        match timeout {
+            Some(timeout) => {
+                let _guard = self
+                    .pushed
+                    .wait_timeout_while(guard, timeout, |_| {
+                        self.queue.is_empty() && !self.is_closed()
+                    })
+                    .unwrap_or_else(|err| err.into_inner());
+            }
+            None => {
+                let _guard = self
+                    .pushed
+                    .wait_while(guard, |_| self.queue.is_empty() && !self.is_closed())
+                    .unwrap_or_else(|err| err.into_inner());
+            }
+        }
+
+        !self.queue.is_empty()
+    }
+
+    pub fn pop(&self) -> Option<QueuedItemDefinition> {
+        self.queue.pop()
+    }
+
+    /// Number of items currently waiting to be spawned
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Takes up to `budget` items from the queue, anything beyond the
+    /// budget remains queued for later frames
+    pub fn take(&self, budget: usize) -> impl Iterator<Item = QueuedItemDefinition> + '_ {
+        std::iter::from_fn(|| self.queue.pop()).take(budget)
+    }
+}
+
+/// Handle for submitting items to the renderer from any thread or task,
+/// item textures are loaded in the background before being queued for
+/// the renderer to spawn
+#[derive(Clone)]
+pub struct ItemSender {
+    tx: mpsc::UnboundedSender<(ItemId, ItemDefinition)>,
+}
+
+impl ItemSender {
+    /// Creates a sender that loads items into the provided queue, the
+    /// loading task runs until every sender is dropped. Must be called
+    /// from within a tokio runtime
+    pub fn create(item_queue: Arc<ItemQueue>) -> ItemSender {
+        let (tx, mut rx) = mpsc::unbounded_channel::<(ItemId, ItemDefinition)>();
+
+        tokio::spawn(async move {
+            while let Some((id, definition)) = rx.recv().await {
+                let item_queue = item_queue.clone();
+
+                // Load each item separately so large textures don't hold
+                // up the items behind them
+                tokio::spawn(async move {
+                    let item = QueuedItemDefinition::load_or_placeholder(id, definition).await;
+                    item_queue.push(item);
+                });
+            }
+        });
+
+        ItemSender { tx }
+    }
+
+    /// Submits an item to be loaded and spawned, returns the ID the item
+    /// will have once spawned
+    pub fn send(&self, definition: ItemDefinition) -> anyhow::Result<ItemId> {
+        let id = ItemId::random();
+        self.tx
+            .send((id, definition))
+            .map_err(|_| anyhow::anyhow!("renderer is no longer accepting items"))?;
+        Ok(id)
+    }
+}
+
+/// Unique identifier for a spawned item
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ItemId(Uuid);
+
+impl ItemId {
+    /// Creates a new random item ID
+    pub fn random() -> ItemId {
+        ItemId(Uuid::new_v4())
+    }
 }
 
-/// Item definition queued to be spawned in the world
-pub struct QueuedItemDefinition {
-    /// Texture of the item to spawn
-    pub texture_data: TextureData,
-    // Whether to pixelate the texture when scaling during render
-    pub pixelate: bool,
-    /// Scale for the image
-    pub scale: f32,
+/// Identifier of an effect registered with [ItemShader::register_effect]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ItemEffectId(pub u32);
+
+/// Read-only snapshot of a spawned item
+#[derive(Debug, Clone, Copy)]
+pub struct ItemInfo {
+    /// Unique ID of the item
+    pub id: ItemId,
+    /// Stage the item is rendered into
+    pub stage: usize,
+    /// How far through its lifetime the item is (0-1), always 0 for
+    /// items that persist until removed
+    pub progress: f32,
+    /// Current center of the item in render target pixels
+    pub position: Vector2<f32>,
+    /// Size of the item texture in pixels
+    pub texture_size: Vector2<u32>,
+}
+
+/// Attachment of an item to a parent item, attached items follow the
+/// animated position of their parent
+#[derive(Debug, Clone, Copy)]
+pub struct ItemAttachment {
+    /// ID of the parent item
+    pub parent: ItemId,
+    /// Offset from the parent position (Normalized to screen size)
+    pub offset: Vector2<f32>,
 }
 
 /// Item definition that is ready to render
 pub struct RenderItemDefinition {
+    /// Unique ID of the item
+    pub id: ItemId,
+
+    /// Parent this item is attached to
+    pub attachment: Option<ItemAttachment>,
+
     pub texture: Texture,
 
     /// Shader resource view for the texture
     pub shader_resource_view: ShaderResourceView,
 
+    /// Geometry the item is drawn with
+    pub mesh: Mesh,
+
+    /// Texture for the back face of the item
+    pub back_texture: Option<Texture>,
+
+    /// Shader resource view for the back face texture
+    pub back_srv: Option<ShaderResourceView>,
+
+    /// Normal map for the front face of the item
+    pub normal_texture: Option<Texture>,
+
+    /// Shader resource view for the normal map
+    pub normal_srv: Option<ShaderResourceView>,
+
+    /// Mask shaping the item
+    pub mask_texture: Option<Texture>,
+
+    /// Shader resource view for the mask
+    pub mask_srv: Option<ShaderResourceView>,
+
+    /// Blurred alpha of the front texture used by the drop shadow
+    pub shadow_texture: Option<Texture>,
+
+    /// Shader resource view for the blurred shadow
+    pub shadow_srv: Option<ShaderResourceView>,
+
     /// Whether to pixelate when rendering
     pub pixelate: bool,
 
-    /// Instance the item was created at
-    pub start_time: Instant,
+    /// Scene time the item appears at, after any start delay
+    pub start_time: Duration,
+
+    /// Whether the item is still waiting for its start delay, delayed
+    /// items are not drawn. Set by [RenderItemDefinition::update]
+    pub delayed: bool,
+
+    /// Whether the animation restarts each time the duration ends, looping
+    /// items never expire
+    pub looping: bool,
+
+    /// Stage the item is rendered into
+    pub stage: usize,
+
+    /// Layer the item is drawn in, higher orders are drawn on top
+    pub order: i32,
+
+    /// Wobble deforming the item mesh, the mesh must be dynamic
+    pub wobble: Option<ItemWobble>,
+
+    /// Current phase of the wobble (0-1). Set by
+    /// [RenderItemDefinition::update]
+    pub wobble_phase: f32,
+
+    /// Registered effect the item is drawn with, [None] uses the built in
+    /// item shaders
+    pub effect: Option<ItemEffectId>,
 
     pub item_data: ItemDataBuffer,
 }
 
 impl RenderItemDefinition {
     /// Updates the timing data for this item
-    pub fn update(&mut self) -> anyhow::Result<()> {
-        let elapsed_time = self.start_time.elapsed().as_millis() as f32;
+    pub fn update(&mut self, clock: &SceneClock) -> anyhow::Result<()> {
+        self.delayed = clock.now() < self.start_time;
+
+        let elapsed = self
+            .item_data
+            .update_timing(self.start_time, self.looping, clock);
 
-        self.item_data.elapsed_time = elapsed_time;
+        if let Some(wobble) = &self.wobble {
+            self.wobble_phase = (wobble.frequency as f64 * elapsed).rem_euclid(1.0) as f32;
+        }
 
         Ok(())
     }
 
-    pub fn render(&mut self, ctx: &ID3D11DeviceContext) {
-        // Bind item texture
-        self.shader_resource_view.bind(ctx);
+    /// Rewrites the mesh of a wobbling item for the current phase, must be
+    /// called before [RenderItemDefinition::render]
+    pub fn deform_mesh(&mut self, ctx: &ID3D11DeviceContext) -> anyhow::Result<()> {
+        let Some(wobble) = &self.wobble else {
+            return Ok(());
+        };
 
-        unsafe {
-            ctx.DrawIndexed(6, 0, 0);
+        let vertices = wobble.vertices(WOBBLE_GRID_SEGMENTS, self.wobble_phase);
+        self.mesh.update_vertices(ctx, &vertices)
+    }
+
+    /// Whether the item has finished its lifetime and should be removed,
+    /// squashing items stay until they finish landing
+    pub fn is_expired(&self) -> bool {
+        if self.looping {
+            return false;
+        }
+
+        let data = &self.item_data;
+        let linger = if data.stretch_intensity > 0.0 {
+            IMPACT_SQUASH_TIME
+        } else {
+            0.0
+        };
+
+        data.elapsed_time > data.duration + linger
+    }
+
+    /// How far through its lifetime the item is (0-1), persistent items
+    /// are always at 0
+    pub fn progress(&self) -> f32 {
+        let data = &self.item_data;
+        if data.duration.is_infinite() {
+            return 0.0;
+        }
+
+        (data.elapsed_time / data.duration).clamp(0.0, 1.0)
+    }
+
+    /// Snapshot of the item, `viewport` is the viewport of the item stage
+    /// used to convert the position into render target pixels
+    pub fn info(&self, viewport: &Viewport) -> ItemInfo {
+        let position = self.item_data.current_position();
+        let relative = Vector2::new((position.x + 1.0) * 0.5, (1.0 - position.y) * 0.5);
+
+        ItemInfo {
+            id: self.id,
+            stage: self.stage,
+            progress: self.progress(),
+            position: viewport.position() + relative.component_mul(&viewport.size()),
+            texture_size: self.texture.size,
         }
     }
+
+    /// Places the item at a fixed position, used by attached items
+    /// to follow their parent
+    pub fn set_fixed_position(&mut self, position: Vector2<f32>) {
+        self.item_data.start_position = position;
+        self.item_data.end_position = position;
+        self.item_data.arc_height = 0.0;
+        self.item_data.motion_mode = MotionMode::Arc as u32;
+    }
+
+    pub fn render(&mut self, ctx: &ID3D11DeviceContext) {
+        let front = Some(&self.shader_resource_view);
+
+        // Items without a back face texture show the front texture on
+        // both sides
+        let back = self.back_srv.as_ref().or(front);
+
+        // Every slot is bound so textures from the previous item never
+        // leak into this one
+        ShaderResourceView::bind_many(
+            ctx,
+            FRONT_TEXTURE_SLOT,
+            &[
+                front,
+                back,
+                self.normal_srv.as_ref(),
+                self.mask_srv.as_ref(),
+                self.shadow_srv.as_ref(),
+            ],
+        );
+
+        self.mesh.bind(ctx);
+        self.mesh.draw(ctx);
+    }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone)]
 #[repr(C, align(16))]
 pub struct ItemDataBuffer {
     /// Normalized world size for the texture (texture_size / screen_size) scaled
@@ -93,60 +1721,662 @@ pub struct ItemDataBuffer {
     /// Final end position (Normalized to screen size)
     pub end_position: Vector2<f32>,
 
-    /// Speed to spin at (ms)
+    /// Speed to spin at (radians per second)
     pub spin_speed: f32,
 
     /// Relative scaling of the item image
     pub scale: f32,
 
-    /// Duration the item should exist for
+    /// Duration the item should exist for (ms), infinite for items that
+    /// persist until removed
     pub duration: f32,
 
     /// Elapsed time since the item creation
     pub elapsed_time: f32,
+
+    /// Height of the arc the item travels along
+    pub arc_height: f32,
+
+    /// How the item moves between its positions ([MotionMode])
+    pub motion_mode: u32,
+
+    /// Radius for orbit motion (Normalized to screen size)
+    pub motion_radius: f32,
+
+    /// Angular speed for spiral and orbit motion (radians per second)
+    pub angular_speed: f32,
+
+    /// Offset of the sampled texture region (UV space)
+    pub uv_offset: Vector2<f32>,
+
+    /// Size of the sampled texture region (UV space)
+    pub uv_scale: Vector2<f32>,
+
+    /// Number of sprite sheet frames to animate through, values
+    /// below 2 display a single cell
+    pub frame_count: u32,
+
+    /// Playback rate of the sprite sheet animation (frames per second)
+    pub frame_rate: f32,
+
+    /// Number of columns in the sprite sheet
+    pub sheet_columns: u32,
+
+    /// Whether the animation loops (1) or holds the last frame (0)
+    pub frame_looping: u32,
+
+    /// Offset of the drop shadow from the item (Normalized to screen size)
+    pub shadow_offset: Vector2<f32>,
+
+    /// Color of the drop shadow, alpha is multiplied by the texture alpha
+    pub shadow_color: Vector4<f32>,
+
+    /// Whether the item renders a drop shadow (1) or not (0)
+    pub shadow_enabled: u32,
+
+    /// Whether the current draw is the shadow pass (1) or the item (0),
+    /// set by the renderer
+    pub shadow_pass: u32,
+
+    /// Width of the outline (texels)
+    pub outline_width: f32,
+
+    /// Whether the item renders an outline (1) or not (0)
+    pub outline_enabled: u32,
+
+    /// Color of the outline (RGBA)
+    pub outline_color: Vector4<f32>,
+
+    /// Whether to mirror the texture horizontally (1) or not (0)
+    pub flip_u: u32,
+
+    /// Whether to mirror the texture vertically (1) or not (0)
+    pub flip_v: u32,
+
+    /// Number of pixelation blocks across the texture, 0 disables pixelation
+    pub pixelate_size: f32,
+
+    /// Whether the normal map is used to light the item (1) or not (0)
+    pub normal_enabled: u32,
+
+    /// Whether the mask scales the alpha of the item (1) or not (0)
+    pub mask_enabled: u32,
+
+    /// How the item rotates, see [RotationMode]
+    pub rotation_mode: u32,
+
+    /// Fixed angle or angle offset when facing the velocity (radians)
+    pub rotation: f32,
+
+    /// Stretch per unit of speed (screen heights per second), 0 disables
+    /// squash and stretch
+    pub stretch_intensity: f32,
+
+    /// Largest factor the item is stretched or squashed by
+    pub stretch_max: f32,
+
+    /// Filter used when sampling the texture, see [UpscaleFilter]
+    pub upscale_filter: u32,
+
+    /// Whether the shadow samples the blurred shadow texture (t4)
+    pub shadow_blurred: u32,
+
+    /// Whether the item textures are premultiplied by alpha
+    pub premultiplied: u32,
+
+    /// Current spin angle (radians) wrapped to a single rotation, set by
+    /// [RenderItemDefinition::update]
+    pub spin_phase: f32,
+
+    /// Current orbit or spiral angle (radians) wrapped to a single
+    /// rotation, set by [RenderItemDefinition::update]
+    pub motion_phase: f32,
+
+    /// Current sprite animation frame, set by [RenderItemDefinition::update]
+    pub sprite_frame: u32,
+
+    /// Shape the item is cut into, see [ShapeMode]
+    pub shape_mode: u32,
+
+    /// Corner radius of rounded rectangle shapes (texture pixels)
+    pub corner_radius: f32,
+
+    /// Speed the hue cycles at (cycles per second), 0 disables cycling
+    pub color_cycle_speed: f32,
+
+    /// Current hue offset (0-1), set by [RenderItemDefinition::update]
+    pub hue_phase: f32,
+
+    /// Alpha below which pixels are discarded, the remaining pixels are
+    /// drawn opaque. 0 disables the alpha test
+    pub alpha_cutoff: f32,
+
+    /// Acceleration from forces (normalized device units per second
+    /// squared), see [ItemForces]
+    pub force_gravity: Vector2<f32>,
+
+    /// Drift from forces (normalized device units per second)
+    pub force_wind: Vector2<f32>,
+}
+
+impl Default for ItemDataBuffer {
+    fn default() -> Self {
+        Self {
+            norm_texture_size: Vector2::zeros(),
+            start_position: Vector2::zeros(),
+            end_position: Vector2::zeros(),
+            spin_speed: 0.0,
+            scale: 1.0,
+            duration: 0.0,
+            elapsed_time: 0.0,
+            arc_height: 0.0,
+            motion_mode: MotionMode::Arc as u32,
+            motion_radius: 0.0,
+            angular_speed: 0.0,
+            // Sample the entire texture by default
+            uv_offset: Vector2::zeros(),
+            uv_scale: Vector2::new(1.0, 1.0),
+            frame_count: 0,
+            frame_rate: 0.0,
+            sheet_columns: 1,
+            frame_looping: 0,
+            shadow_offset: Vector2::zeros(),
+            shadow_color: Vector4::zeros(),
+            shadow_enabled: 0,
+            shadow_pass: 0,
+            outline_width: 0.0,
+            outline_enabled: 0,
+            outline_color: Vector4::zeros(),
+            flip_u: 0,
+            flip_v: 0,
+            pixelate_size: 0.0,
+            normal_enabled: 0,
+            mask_enabled: 0,
+            rotation_mode: RotationMode::Spin as u32,
+            rotation: 0.0,
+            stretch_intensity: 0.0,
+            stretch_max: 1.0,
+            upscale_filter: UpscaleFilter::Bilinear as u32,
+            shadow_blurred: 0,
+            premultiplied: 0,
+            spin_phase: 0.0,
+            motion_phase: 0.0,
+            sprite_frame: 0,
+            shape_mode: ShapeMode::Rectangle as u32,
+            corner_radius: 0.0,
+            color_cycle_speed: 0.0,
+            hue_phase: 0.0,
+            alpha_cutoff: 0.0,
+            force_gravity: Vector2::zeros(),
+            force_wind: Vector2::zeros(),
+        }
+    }
+}
+
+impl ItemDataBuffer {
+    /// Sets the elapsed time and the phases derived from it, `elapsed` is
+    /// the time in seconds since the item started
+    /// Advances the animation to the current scene time, `start_time` is
+    /// the scene time the item appeared at. Returns the time since the item
+    /// appeared (seconds)
+    pub fn update_timing(
+        &mut self,
+        start_time: Duration,
+        looping: bool,
+        clock: &SceneClock,
+    ) -> f64 {
+        // Phases are reduced in f64 so they stay precise on long lived
+        // items, f32 milliseconds get coarse after a few minutes
+        let elapsed = clock.now().saturating_sub(start_time).as_secs_f64();
+        self.advance(elapsed, looping);
+        elapsed
+    }
+
+    pub fn advance(&mut self, elapsed: f64, looping: bool) {
+        // Looping items wrap the time used for the motion path, phases
+        // below use the unwrapped time so they don't jump on the restart
+        let elapsed_ms = elapsed * 1000.0;
+        self.elapsed_time = if looping && self.duration.is_finite() {
+            elapsed_ms.rem_euclid(self.duration as f64) as f32
+        } else {
+            elapsed_ms as f32
+        };
+        self.spin_phase = wrap_phase(self.spin_speed, elapsed);
+        self.motion_phase = wrap_phase(self.angular_speed, elapsed);
+        self.sprite_frame = sprite_frame(self, elapsed);
+        self.hue_phase = (self.color_cycle_speed as f64 * elapsed).rem_euclid(1.0) as f32;
+    }
+
+    /// Computes the current position of the item (Normalized to screen size)
+    pub fn current_position(&self) -> Vector2<f32> {
+        self.position_at(self.elapsed_time)
+    }
+
+    /// Computes the position of the item at the provided elapsed time (ms),
+    /// matches the interpolation performed in the vertex shader
+    pub fn position_at(&self, elapsed_time: f32) -> Vector2<f32> {
+        let t = (elapsed_time / self.duration).clamp(0.0, 1.0);
+        let angle = motion_angle(self, elapsed_time);
+
+        let position = match self.motion_mode {
+            mode if mode == MotionMode::Spiral as u32 => {
+                let offset = self.start_position - self.end_position;
+                let (sin, cos) = angle.sin_cos();
+                let rotated = Vector2::new(
+                    offset.x * cos - offset.y * sin,
+                    offset.x * sin + offset.y * cos,
+                );
+                self.end_position + rotated * (1.0 - t)
+            }
+            mode if mode == MotionMode::Orbit as u32 => {
+                let (sin, cos) = angle.sin_cos();
+                self.end_position + Vector2::new(cos, sin) * self.motion_radius
+            }
+            _ => {
+                let position = self.start_position.lerp(&self.end_position, t);
+                let arc = self.arc_height * t * (1.0 - t);
+                Vector2::new(position.x, position.y + arc)
+            }
+        };
+
+        position + force_offset(self, elapsed_time)
+    }
+
+    /// Computes the current rotation of the item in radians, matches
+    /// the rotation performed in the vertex shader
+    pub fn current_rotation(&self, screen_aspect: f32) -> f32 {
+        match self.rotation_mode {
+            mode if mode == RotationMode::Fixed as u32 => self.rotation,
+            mode if mode == RotationMode::FaceVelocity as u32 => {
+                let velocity = self.velocity_at(self.heading_sample_time(), screen_aspect);
+                if velocity == Vector2::zeros() {
+                    return self.rotation;
+                }
+
+                velocity.y.atan2(velocity.x) + self.rotation
+            }
+            _ => self.spin_phase,
+        }
+    }
+
+    /// Velocity of the item at the provided elapsed time (ms) in aspect
+    /// corrected space (per ms), matches `ItemVelocity` in the vertex shader
+    fn velocity_at(&self, elapsed_time: f32, screen_aspect: f32) -> Vector2<f32> {
+        let velocity = (self.position_at(elapsed_time + VELOCITY_SAMPLE_TIME)
+            - self.position_at(elapsed_time))
+            / VELOCITY_SAMPLE_TIME;
+        Vector2::new(velocity.x * screen_aspect, velocity.y)
+    }
+
+    /// Time to sample the direction of travel at, kept within the lifetime
+    /// so items keep their heading once they stop
+    fn heading_sample_time(&self) -> f32 {
+        (self.elapsed_time.min(self.duration) - VELOCITY_SAMPLE_TIME).max(0.0)
+    }
+
+    /// Computes the current squash and stretch of the item, returns the
+    /// direction of travel (aspect corrected) and the factor to stretch
+    /// along it. Matches `StretchFactor` in the vertex shader
+    pub fn current_stretch(&self, screen_aspect: f32) -> Option<(Vector2<f32>, f32)> {
+        if self.stretch_intensity <= 0.0 {
+            return None;
+        }
+
+        let heading = self.velocity_at(self.heading_sample_time(), screen_aspect);
+        if heading == Vector2::zeros() {
+            return None;
+        }
+
+        // Speed in screen heights per second, screen space spans 2 units
+        let speed = self.velocity_at(self.elapsed_time, screen_aspect).norm() * 500.0;
+        let mut stretch = 1.0 + self.stretch_intensity * speed;
+
+        // Squash briefly after landing based on the landing speed
+        let since_impact = self.elapsed_time - self.duration;
+        if self.motion_mode != MotionMode::Orbit as u32
+            && (0.0..IMPACT_SQUASH_TIME).contains(&since_impact)
+        {
+            let impact_speed = heading.norm() * 500.0;
+            let squash =
+                self.stretch_intensity * impact_speed * (1.0 - since_impact / IMPACT_SQUASH_TIME);
+            stretch = 1.0 / (1.0 + squash);
+        }
+
+        let stretch = stretch.clamp(1.0 / self.stretch_max, self.stretch_max);
+        Some((heading.normalize(), stretch))
+    }
+
+    /// Computes the current corners of the item quad (Normalized to screen size),
+    /// rotation happens in aspect corrected space to match the vertex shader
+    pub fn current_corners(&self, screen_aspect: f32) -> [Vector2<f32>; 4] {
+        let position = self.current_position();
+        let size = self.norm_texture_size * self.scale;
+        let (sin, cos) = self.current_rotation(screen_aspect).sin_cos();
+        let stretch = self.current_stretch(screen_aspect);
+
+        [
+            Vector2::new(-0.5, -0.5),
+            Vector2::new(-0.5, 0.5),
+            Vector2::new(0.5, 0.5),
+            Vector2::new(0.5, -0.5),
+        ]
+        .map(|corner| {
+            let offset = corner.component_mul(&size);
+            let offset = Vector2::new(offset.x * screen_aspect, offset.y);
+            let mut rotated = Vector2::new(
+                offset.x * cos - offset.y * sin,
+                offset.x * sin + offset.y * cos,
+            );
+
+            // Stretch along the direction of travel preserving area
+            if let Some((direction, factor)) = stretch {
+                let along = rotated.dot(&direction);
+                let perpendicular = rotated - direction * along;
+                rotated = direction * along * factor + perpendicular / factor;
+            }
+
+            Vector2::new(rotated.x / screen_aspect, rotated.y) + position
+        })
+    }
+}
+
+/// Cell within a sprite sheet made up of a grid of equally sized cells
+#[derive(Debug, Clone, Copy)]
+pub struct SpriteSheetCell {
+    /// Number of columns in the sheet
+    pub columns: u32,
+    /// Number of rows in the sheet
+    pub rows: u32,
+    /// Index of the cell, counted left to right then top to bottom
+    pub index: u32,
+}
+
+impl SpriteSheetCell {
+    /// Size of a single cell (UV space)
+    pub fn uv_scale(&self) -> Vector2<f32> {
+        Vector2::new(
+            1.0 / self.columns.max(1) as f32,
+            1.0 / self.rows.max(1) as f32,
+        )
+    }
+
+    /// Offset of the cell (UV space)
+    pub fn uv_offset(&self) -> Vector2<f32> {
+        let columns = self.columns.max(1);
+        let column = self.index % columns;
+        let row = self.index / columns;
+
+        Vector2::new(column as f32, row as f32).component_mul(&self.uv_scale())
+    }
+
+    /// Writes the cell region into the item data
+    pub fn apply(&self, data: &mut ItemDataBuffer) {
+        data.uv_offset = self.uv_offset();
+        data.uv_scale = self.uv_scale();
+        data.sheet_columns = self.columns.max(1);
+    }
+}
+
+/// Animation playing through the cells of a sprite sheet, frames
+/// start at the sheet cell index and advance left to right then
+/// top to bottom
+#[derive(Debug, Clone, Copy)]
+pub struct SpriteAnimation {
+    /// Number of frames in the animation
+    pub frame_count: u32,
+    /// Playback rate (frames per second)
+    pub fps: f32,
+    /// Whether to loop the animation, otherwise the last frame is held
+    pub looping: bool,
+}
+
+impl SpriteAnimation {
+    /// Writes the animation into the item data
+    pub fn apply(&self, data: &mut ItemDataBuffer) {
+        data.frame_count = self.frame_count;
+        data.frame_rate = self.fps;
+        data.frame_looping = self.looping as u32;
+    }
+}
+
+/// Per-frame data shared by all items, updated once per frame
+#[derive(Debug, Default)]
+#[repr(C, align(16))]
+pub struct GlobalsBuffer {
+    /// Size of the render target in pixels
+    pub screen_size: Vector2<f32>,
+
+    /// Current scene time (seconds)
+    pub scene_time: f32,
+
+    /// Random value that changes every frame
+    pub random_seed: u32,
+
+    /// Aspect ratio of the render target (width / height)
+    pub screen_aspect: f32,
+
+    /// Whether items output colors premultiplied by alpha
+    pub premultiplied_output: u32,
+
+    /// Scene gravity (pixels per second squared), see [SceneForces]
+    pub scene_gravity: Vector2<f32>,
+
+    /// Scene wind (pixels per second)
+    pub scene_wind: Vector2<f32>,
+
+    /// Whether item edges fade across the pixels they partially cover
+    pub subpixel_edges: u32,
+}
+
+/// Angle after `elapsed` seconds at `speed` radians per second, wrapped
+/// to a single rotation
+fn wrap_phase(speed: f32, elapsed: f64) -> f32 {
+    (speed as f64 * elapsed).rem_euclid(std::f64::consts::TAU) as f32
+}
+
+/// Sprite animation frame after `elapsed` seconds
+fn sprite_frame(data: &ItemDataBuffer, elapsed: f64) -> u32 {
+    if data.frame_count < 2 {
+        return 0;
+    }
+
+    let frame = (elapsed * data.frame_rate as f64) as u64;
+    if data.frame_looping != 0 {
+        (frame % data.frame_count as u64) as u32
+    } else {
+        frame.min(data.frame_count as u64 - 1) as u32
+    }
+}
+
+/// Orbit or spiral angle at the provided elapsed time (ms), offset from
+/// the wrapped phase so only the small difference is computed in f32.
+/// Matches `MotionAngle` in the vertex shader
+fn motion_angle(data: &ItemDataBuffer, elapsed_time: f32) -> f32 {
+    data.motion_phase + data.angular_speed * (elapsed_time - data.elapsed_time) * 0.001
+}
+
+/// Distance the item forces have moved the item after `elapsed_time` (ms).
+/// Matches `ForceOffset` in the vertex shader
+fn force_offset(data: &ItemDataBuffer, elapsed_time: f32) -> Vector2<f32> {
+    let t = elapsed_time.max(0.0) * 0.001;
+    data.force_gravity * (0.5 * t * t) + data.force_wind * t
+}
+
+/// Time window (ms) used to estimate the direction of travel, must match
+/// `VELOCITY_SAMPLE_TIME` in the vertex shader
+const VELOCITY_SAMPLE_TIME: f32 = 1.0;
+
+/// Time (ms) items stay squashed after landing, must match
+/// `IMPACT_SQUASH_TIME` in the vertex shader
+const IMPACT_SQUASH_TIME: f32 = 150.0;
+
+/// Motion mode values understood by the vertex shader
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum MotionMode {
+    /// Travel from start to end along an arc
+    Arc = 0,
+    /// Spiral inwards from start to end
+    Spiral = 1,
+    /// Orbit around the end position
+    Orbit = 2,
+}
+
+/// Motion of an item over its lifetime
+#[derive(Debug, Clone, Copy)]
+pub enum ItemMotion {
+    /// Travel along an arc of the provided height, a height
+    /// of zero moves in a straight line
+    Arc { height: f32 },
+    /// Spiral inwards to the end position
+    Spiral { angular_speed: f32 },
+    /// Orbit around the end position at the provided radius
+    Orbit { radius: f32, angular_speed: f32 },
+}
+
+impl Default for ItemMotion {
+    fn default() -> Self {
+        ItemMotion::Arc { height: 0.5 }
+    }
 }
 
-/// Creates a vertex buffer used to render items
-pub fn create_item_vertex_buffer(device: &ID3D11Device) -> anyhow::Result<VertexBuffer> {
-    #[repr(C)]
-    struct Vertex {
-        pos: Vector2<f32>,
-        tex: Vector2<f32>,
+impl ItemMotion {
+    /// Writes the motion parameters into the item data
+    pub fn apply(&self, data: &mut ItemDataBuffer) {
+        match *self {
+            ItemMotion::Arc { height } => {
+                data.motion_mode = MotionMode::Arc as u32;
+                data.arc_height = height;
+            }
+            ItemMotion::Spiral { angular_speed } => {
+                data.motion_mode = MotionMode::Spiral as u32;
+                data.angular_speed = angular_speed;
+            }
+            ItemMotion::Orbit {
+                radius,
+                angular_speed,
+            } => {
+                data.motion_mode = MotionMode::Orbit as u32;
+                data.motion_radius = radius;
+                data.angular_speed = angular_speed;
+            }
+        }
     }
+}
 
+/// Vertex of an item mesh, must match the item shader input layout
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct ItemVertex {
+    /// Position relative to the item center, the item spans -0.5 to 0.5
+    pub pos: Vector2<f32>,
+    /// Texture coordinate
+    pub tex: Vector2<f32>,
+}
+
+/// Creates the quad mesh items are drawn with by default
+pub fn create_item_quad(device: &ID3D11Device) -> anyhow::Result<Mesh> {
     let vertices = [
         // Top-left
-        Vertex {
+        ItemVertex {
             pos: Vector2::new(-0.5, -0.5),
             tex: Vector2::new(0.0, 1.0),
         },
         // Bottom-left
-        Vertex {
+        ItemVertex {
             pos: Vector2::new(-0.5, 0.5),
             tex: Vector2::new(0.0, 0.0),
         },
         // Bottom-right
-        Vertex {
+        ItemVertex {
             pos: Vector2::new(0.5, 0.5),
             tex: Vector2::new(1.0, 0.0),
         },
         // Top-right
-        Vertex {
+        ItemVertex {
             pos: Vector2::new(0.5, -0.5),
             tex: Vector2::new(1.0, 1.0),
         },
     ];
+    let indices: [u32; 6] = [0, 1, 2, 0, 2, 3];
 
-    let vertex_buffer = VertexBuffer::create_from_array(device, &vertices)?;
-    Ok(vertex_buffer)
+    Mesh::create(
+        device,
+        &vertices,
+        &indices,
+        D3D11_PRIMITIVE_TOPOLOGY_TRIANGLELIST,
+    )
 }
 
-/// Creates the index buffer for rendering items
-pub fn create_item_index_buffer(device: &ID3D11Device) -> anyhow::Result<IndexBuffer> {
-    let indices: [u32; 6] = [0, 1, 2, 0, 2, 3];
-    let index_buffer = IndexBuffer::create_from_array(device, &indices, DXGI_FORMAT_R32_UINT)?;
+/// Creates a dynamic grid mesh of `segments` by `segments` cells spanning
+/// the same area as the item quad, used by items that deform
+pub fn create_item_grid(device: &ID3D11Device, segments: u32) -> anyhow::Result<Mesh> {
+    let vertices = ItemWobble {
+        amplitude: 0.0,
+        ..Default::default()
+    }
+    .vertices(segments, 0.0);
+
+    let row = segments + 1;
+    let indices: Vec<u32> = (0..segments)
+        .flat_map(|y| (0..segments).map(move |x| y * row + x))
+        .flat_map(|top_left| {
+            let bottom_left = top_left + row;
+            [
+                top_left,
+                bottom_left,
+                bottom_left + 1,
+                top_left,
+                bottom_left + 1,
+                top_left + 1,
+            ]
+        })
+        .collect();
+
+    Mesh::create_dynamic(
+        device,
+        &vertices,
+        &indices,
+        D3D11_PRIMITIVE_TOPOLOGY_TRIANGLELIST,
+    )
+}
+
+/// Points of a `segments` by `segments` grid (0-1), row by row
+fn grid_points(segments: u32) -> impl Iterator<Item = (f32, f32)> {
+    let step = 1.0 / segments as f32;
+    (0..=segments)
+        .flat_map(move |y| (0..=segments).map(move |x| (x as f32 * step, y as f32 * step)))
+}
+
+/// Input layout of [ItemVertex], every item vertex shader must accept it
+fn item_input_elements() -> [D3D11_INPUT_ELEMENT_DESC; 2] {
+    [
+        D3D11_INPUT_ELEMENT_DESC {
+            SemanticName: "POSITION\0".as_ptr() as _,
+            SemanticIndex: 0,
+            Format: DXGI_FORMAT_R32G32_FLOAT,
+            InputSlot: 0,
+            AlignedByteOffset: 0,
+            InputSlotClass: D3D11_INPUT_PER_VERTEX_DATA,
+            InstanceDataStepRate: 0,
+        },
+        D3D11_INPUT_ELEMENT_DESC {
+            SemanticName: "TEXCOORD\0".as_ptr() as _,
+            SemanticIndex: 0,
+            Format: DXGI_FORMAT_R32G32_FLOAT,
+            InputSlot: 0,
+            AlignedByteOffset: 8,
+            InputSlotClass: D3D11_INPUT_PER_VERTEX_DATA,
+            InstanceDataStepRate: 0,
+        },
+    ]
+}
 
-    Ok(index_buffer)
+/// Custom shaders replacing one or both of the item shaders, stages left
+/// as [None] use the built in item shader
+struct ItemEffect {
+    vertex: Option<(VertexShader, ShaderInputLayout)>,
+    pixel: Option<PixelShader>,
 }
 
 /// Shader for rendering items
@@ -154,99 +2384,203 @@ pub struct ItemShader {
     input_layout: ShaderInputLayout,
     vertex: VertexShader,
     pixel: PixelShader,
+    /// Registered effects items can be drawn with
+    effects: HashMap<ItemEffectId, ItemEffect>,
 }
 
 impl ItemShader {
     pub fn create(device: &ID3D11Device) -> anyhow::Result<ItemShader> {
         // Compile shaders
-        let vertex_shader_blob = ShaderBlob::compile(
-            include_bytes!("shaders/vertex_shader.hlsl"),
-            "vs_5_0",
-            "VSMain",
-        )?;
-        let pixel_shader_blob = ShaderBlob::compile(
-            include_bytes!("shaders/fragment_shader.hlsl"),
-            "ps_5_0",
-            "PSMain",
-        )?;
+        let vertex_shader_blob = load_shader!("vertex_shader.hlsl", "vs_5_0", "VSMain")?;
+        let pixel_shader_blob = load_shader!("fragment_shader.hlsl", "ps_5_0", "PSMain")?;
 
         // Create shaders
         let vertex = VertexShader::create(device, vertex_shader_blob.clone())?;
         let pixel = PixelShader::create(device, pixel_shader_blob)?;
 
         // Create shader input layout
-        let input_layout = ShaderInputLayout::create(
-            device,
-            &[
-                D3D11_INPUT_ELEMENT_DESC {
-                    SemanticName: "POSITION\0".as_ptr() as _,
-                    SemanticIndex: 0,
-                    Format: DXGI_FORMAT_R32G32_FLOAT,
-                    InputSlot: 0,
-                    AlignedByteOffset: 0,
-                    InputSlotClass: D3D11_INPUT_PER_VERTEX_DATA,
-                    InstanceDataStepRate: 0,
-                },
-                D3D11_INPUT_ELEMENT_DESC {
-                    SemanticName: "TEXCOORD\0".as_ptr() as _,
-                    SemanticIndex: 0,
-                    Format: DXGI_FORMAT_R32G32_FLOAT,
-                    InputSlot: 0,
-                    AlignedByteOffset: 8,
-                    InputSlotClass: D3D11_INPUT_PER_VERTEX_DATA,
-                    InstanceDataStepRate: 0,
-                },
-            ],
-            vertex_shader_blob,
-        )?;
+        let input_layout =
+            ShaderInputLayout::create(device, &item_input_elements(), vertex_shader_blob)?;
 
         Ok(ItemShader {
             input_layout,
             vertex,
             pixel,
+            effects: HashMap::new(),
         })
     }
 
-    pub fn bind(&mut self, ctx: &ID3D11DeviceContext) {
-        // Set shader resources
-        self.input_layout.bind(ctx);
+    /// Registers custom shaders items can select using `id`, replacing any
+    /// effect already registered with the id. The vertex shader must take
+    /// [ItemVertex] as input so items keep sharing the common meshes, and
+    /// both shaders see the same constant buffers and texture slots as the
+    /// built in item shaders
+    pub fn register_effect(
+        &mut self,
+        device: &ID3D11Device,
+        id: ItemEffectId,
+        vertex_blob: Option<ShaderBlob>,
+        pixel_blob: Option<ShaderBlob>,
+    ) -> anyhow::Result<()> {
+        if vertex_blob.is_none() && pixel_blob.is_none() {
+            anyhow::bail!("item effect {id:?} must replace at least one shader");
+        }
+
+        let vertex = match vertex_blob {
+            Some(blob) => {
+                let shader = VertexShader::create(device, blob.clone())?;
+
+                // Creating the layout fails when the shader input signature
+                // doesn't match the item vertex format
+                let input_layout = ShaderInputLayout::create(device, &item_input_elements(), blob)
+                    .with_context(|| {
+                        format!("item effect {id:?} vertex shader must take the item vertex format")
+                    })?;
+
+                Some((shader, input_layout))
+            }
+            None => None,
+        };
+
+        let pixel = pixel_blob
+            .map(|blob| PixelShader::create(device, blob))
+            .transpose()?;
+
+        self.effects.insert(id, ItemEffect { vertex, pixel });
+        Ok(())
+    }
+
+    /// Whether an effect is registered with `id`
+    pub fn has_effect(&self, id: ItemEffectId) -> bool {
+        self.effects.contains_key(&id)
+    }
+
+    /// Binds the shaders of the effect, [None] or an unregistered effect
+    /// binds the built in item shaders
+    pub fn bind(&mut self, ctx: &ID3D11DeviceContext, effect: Option<ItemEffectId>) {
+        let effect = effect.and_then(|id| self.effects.get_mut(&id));
+        let (custom_vertex, custom_pixel) = match effect {
+            Some(effect) => (effect.vertex.as_mut(), effect.pixel.as_mut()),
+            None => (None, None),
+        };
+
+        match custom_vertex {
+            Some((vertex, input_layout)) => {
+                input_layout.bind(ctx);
+                vertex.set_shader(ctx);
+            }
+            None => {
+                self.input_layout.bind(ctx);
+                self.vertex.set_shader(ctx);
+            }
+        }
 
-        // Set the vertex shader to current
-        self.vertex.set_shader(ctx);
-        self.pixel.set_shader(ctx);
+        match custom_pixel {
+            Some(pixel) => pixel.set_shader(ctx),
+            None => self.pixel.set_shader(ctx),
+        }
     }
 }
 
+/// Pixel shader texture slot for the front face texture (t0)
+pub const FRONT_TEXTURE_SLOT: u32 = 0;
+/// Pixel shader texture slot for the back face texture (t1)
+pub const BACK_TEXTURE_SLOT: u32 = 1;
+/// Pixel shader texture slot for the normal map (t2)
+pub const NORMAL_TEXTURE_SLOT: u32 = 2;
+/// Pixel shader texture slot for the alpha mask (t3)
+pub const MASK_TEXTURE_SLOT: u32 = 3;
+/// Pixel shader texture slot for the blurred shadow (t4)
+pub const SHADOW_TEXTURE_SLOT: u32 = 4;
+/// Number of texture slots bound when drawing items
+pub const ITEM_TEXTURE_SLOTS: u32 = 5;
+
 pub struct ItemRenderContext {
     pub item_shader: ItemShader,
-    pub index_buffer: IndexBuffer,
-    pub vertex_buffer: VertexBuffer,
+    /// Quad mesh shared by items and the background
+    pub quad: Mesh,
     pub linear_sampler: SamplerState,
     pub pixelate_sampler: SamplerState,
+    /// Level of detail settings the samplers were created with
+    pub sampler_lod: SamplerLod,
+    /// Anisotropy of the linear sampler, 1 uses plain linear filtering
+    pub max_anisotropy: u32,
+    /// Override of the per item sampler choice
+    pub sampler_override: SamplerOverride,
+    /// Effect whose shaders are currently bound, [None] for the built in
+    /// item shaders
+    bound_effect: Option<ItemEffectId>,
     pub item_data: ConstantBuffer<ItemDataBuffer>,
+    pub globals: ConstantBuffer<GlobalsBuffer>,
 }
 
 impl ItemRenderContext {
     pub fn create(device: &ID3D11Device) -> anyhow::Result<Self> {
         let item_shader = ItemShader::create(device)?;
-        let index_buffer = create_item_index_buffer(device)?;
-        let vertex_buffer = create_item_vertex_buffer(device)?;
+        let quad = create_item_quad(device)?;
 
         let linear_sampler = SamplerState::linear(device)?;
         let pixelate_sampler = SamplerState::pixelate(device)?;
 
         let item_data = ConstantBuffer::create_default(device)?;
+        let globals = ConstantBuffer::create_default(device)?;
 
         Ok(Self {
             item_shader,
-            index_buffer,
-            vertex_buffer,
+            quad,
             linear_sampler,
             pixelate_sampler,
+            sampler_lod: SamplerLod::default(),
+            max_anisotropy: 1,
+            sampler_override: SamplerOverride::default(),
+            bound_effect: None,
             item_data,
+            globals,
         })
     }
 
+    /// Recreates the item samplers using the provided level of detail
+    /// settings
+    pub fn set_sampler_lod(
+        &mut self,
+        device: &ID3D11Device,
+        lod: SamplerLod,
+    ) -> anyhow::Result<()> {
+        self.linear_sampler = match self.max_anisotropy {
+            1 => SamplerState::with_lod_bias(device, D3D11_FILTER_MIN_MAG_MIP_LINEAR, lod)?,
+            max_anisotropy => SamplerState::anisotropic(device, max_anisotropy, lod)?.0,
+        };
+        self.pixelate_sampler =
+            SamplerState::with_lod_bias(device, D3D11_FILTER_MIN_MAG_MIP_POINT, lod)?;
+        self.sampler_lod = lod;
+        Ok(())
+    }
+
+    /// Recreates the linear sampler with anisotropic filtering, 1 disables
+    /// it. The anisotropy is clamped to what the device supports, returns
+    /// the effective anisotropy
+    pub fn set_max_anisotropy(
+        &mut self,
+        device: &ID3D11Device,
+        max_anisotropy: u32,
+    ) -> anyhow::Result<u32> {
+        let (linear_sampler, effective) = match max_anisotropy {
+            0 | 1 => (
+                SamplerState::with_lod_bias(
+                    device,
+                    D3D11_FILTER_MIN_MAG_MIP_LINEAR,
+                    self.sampler_lod,
+                )?,
+                1,
+            ),
+            max_anisotropy => SamplerState::anisotropic(device, max_anisotropy, self.sampler_lod)?,
+        };
+
+        self.linear_sampler = linear_sampler;
+        self.max_anisotropy = effective;
+        Ok(effective)
+    }
+
     pub fn set_current_data(
         &mut self,
         ctx: &ID3D11DeviceContext,
@@ -256,31 +2590,70 @@ impl ItemRenderContext {
         Ok(())
     }
 
-    /// Binds the constant buffers for this item
+    /// Updates the per-frame globals, should be called once per frame
+    /// before rendering items
+    pub fn set_globals(
+        &mut self,
+        ctx: &ID3D11DeviceContext,
+        globals: &GlobalsBuffer,
+    ) -> anyhow::Result<()> {
+        self.globals.replace(ctx, globals)?;
+        Ok(())
+    }
+
+    /// Binds the constant buffers for this item to both the vertex and
+    /// pixel stages. Custom item shaders can rely on the following layout:
+    ///
+    /// - `b0` [ItemDataBuffer] (Per-item data)
+    /// - `b1` [GlobalsBuffer] (Per-frame data)
+    /// - `t0` Item texture
+    /// - `t1` Item back face texture
+    /// - `s0` Item sampler
     pub fn bind_constants(&mut self, ctx: &ID3D11DeviceContext) {
         unsafe {
-            // Bind item data and timing data
-            ctx.VSSetConstantBuffers(0, 1, &self.item_data.buffer.as_ptr());
+            let buffers = [self.item_data.buffer.as_ptr(), self.globals.buffer.as_ptr()];
+            ctx.VSSetConstantBuffers(0, buffers.len() as u32, buffers.as_ptr());
+            ctx.PSSetConstantBuffers(0, buffers.len() as u32, buffers.as_ptr());
         }
     }
 
+    /// Binds the item pipeline, safe to call every frame or after another
+    /// pass has changed the pipeline state
     pub fn prepare_render(&mut self, ctx: &ID3D11DeviceContext) {
-        // Bind item shader
-        self.item_shader.bind(ctx);
-
-        // Bind vertex and index buffers
-        self.vertex_buffer.bind(ctx);
-        self.index_buffer.bind(ctx);
+        // Bind item shader, geometry is bound by each item as items can
+        // use different meshes
+        self.item_shader.bind(ctx, None);
+        self.bound_effect = None;
 
         self.bind_constants(ctx);
+    }
 
-        unsafe {
-            // Set drawing mode and draw from index buffer
-            ctx.IASetPrimitiveTopology(D3D11_PRIMITIVE_TOPOLOGY_TRIANGLELIST);
+    /// Binds the shaders of the effect an item is drawn with, only rebinds
+    /// when the effect differs from the previous item
+    pub fn set_effect(&mut self, ctx: &ID3D11DeviceContext, effect: Option<ItemEffectId>) {
+        if self.bound_effect != effect {
+            self.item_shader.bind(ctx, effect);
+            self.bound_effect = effect;
         }
     }
 
+    /// Unbinds the item textures, sampler and geometry bound while
+    /// drawing items, prevents stale bindings leaking into later passes
+    pub fn finish_render(&mut self, ctx: &ID3D11DeviceContext) {
+        ShaderResourceView::unbind_slots(ctx, FRONT_TEXTURE_SLOT, ITEM_TEXTURE_SLOTS);
+        self.linear_sampler.unbind(ctx);
+        Mesh::unbind(ctx);
+    }
+
+    /// Binds the sampler for an item, `pixelate` is the item's own choice
+    /// which the [SamplerOverride] can replace
     pub fn set_sampler(&mut self, ctx: &ID3D11DeviceContext, pixelate: bool) {
+        let pixelate = match self.sampler_override {
+            SamplerOverride::PerItem => pixelate,
+            SamplerOverride::ForceLinear => false,
+            SamplerOverride::ForcePixelate => true,
+        };
+
         // Set current sampler
         if pixelate {
             self.pixelate_sampler.bind(ctx);
@@ -289,3 +2662,60 @@ impl ItemRenderContext {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::clock::MockClock;
+
+    use super::*;
+
+    /// Item moving in a straight line from (-1, 0.5) to (0.5, -0.5)
+    /// over a second
+    fn linear_item() -> ItemDataBuffer {
+        let mut data = ItemDataBuffer {
+            start_position: Vector2::new(-1.0, 0.5),
+            end_position: Vector2::new(0.5, -0.5),
+            duration: 1000.0,
+            ..Default::default()
+        };
+        // An arc without height is a straight line
+        ItemMotion::Arc { height: 0.0 }.apply(&mut data);
+        data
+    }
+
+    #[test]
+    fn linear_motion_is_halfway_at_half_duration() {
+        let source = MockClock::default();
+        let mut clock = SceneClock::with_source(source.clone());
+
+        let mut data = linear_item();
+        let start_time = clock.now();
+
+        source.advance(Duration::from_millis(500));
+        clock.tick();
+
+        let elapsed = data.update_timing(start_time, false, &clock);
+
+        assert_eq!(elapsed, 0.5);
+        assert_eq!(data.elapsed_time, 500.0);
+        assert_eq!(data.current_position(), Vector2::new(-0.25, 0.0));
+    }
+
+    #[test]
+    fn looping_motion_restarts_after_duration() {
+        let source = MockClock::default();
+        let mut clock = SceneClock::with_source(source.clone());
+
+        let mut data = linear_item();
+        let start_time = clock.now();
+
+        source.advance(Duration::from_millis(1500));
+        clock.tick();
+
+        let elapsed = data.update_timing(start_time, true, &clock);
+
+        assert_eq!(elapsed, 1.5);
+        assert_eq!(data.elapsed_time, 500.0);
+        assert_eq!(data.current_position(), Vector2::new(-0.25, 0.0));
+    }
+}
@@ -0,0 +1,23 @@
+pub mod app;
+pub mod background;
+pub mod clock;
+pub mod com;
+pub mod debug;
+pub mod dirty;
+pub mod dx;
+pub mod emitter;
+pub mod item;
+pub mod pacer;
+pub mod premultiply;
+#[cfg(any(test, feature = "reference"))]
+pub mod reference;
+pub mod renderer;
+pub mod shadow;
+pub mod spout;
+pub mod supersample;
+pub mod text;
+pub mod texture_loader;
+
+pub use app::RendererConfig;
+pub use item::ItemDefinition;
+pub use renderer::Renderer;
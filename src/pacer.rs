@@ -0,0 +1,76 @@
+use std::time::{Duration, Instant};
+
+/// Time before the frame deadline where the pacer stops sleeping and
+/// spins instead, sleeping is not precise enough to hit the deadline
+const SPIN_THRESHOLD: Duration = Duration::from_millis(2);
+
+/// Paces the render loop to a target frame rate using a mix of sleeping
+/// and spinning, sleeping for most of the frame keeps CPU usage low
+/// while the final spin keeps frame times accurate
+pub struct FramePacer {
+    /// Duration of a single frame, [None] when running uncapped
+    frame_duration: Option<Duration>,
+    /// Time the next frame should start
+    next_frame: Instant,
+}
+
+impl FramePacer {
+    /// Creates a pacer targeting the provided frame rate, [None] runs
+    /// uncapped
+    pub fn new(target_fps: Option<u32>) -> Self {
+        Self {
+            frame_duration: target_fps.map(frame_duration),
+            next_frame: Instant::now(),
+        }
+    }
+
+    /// Creates a pacer that never waits, useful for benchmarking
+    pub fn uncapped() -> Self {
+        Self::new(None)
+    }
+
+    /// Changes the target frame rate, [None] runs uncapped
+    pub fn set_target_fps(&mut self, target_fps: Option<u32>) {
+        self.frame_duration = target_fps.map(frame_duration);
+        self.next_frame = Instant::now();
+    }
+
+    /// Current target frame rate, [None] when running uncapped
+    pub fn target_fps(&self) -> Option<f32> {
+        self.frame_duration
+            .map(|duration| 1.0 / duration.as_secs_f32())
+    }
+
+    /// Waits until the next frame should start
+    pub fn wait(&mut self) {
+        let Some(frame_duration) = self.frame_duration else {
+            return;
+        };
+
+        let now = Instant::now();
+        if self.next_frame > now {
+            let remaining = self.next_frame - now;
+            if remaining > SPIN_THRESHOLD {
+                std::thread::sleep(remaining - SPIN_THRESHOLD);
+            }
+
+            while Instant::now() < self.next_frame {
+                std::hint::spin_loop();
+            }
+        }
+
+        self.next_frame += frame_duration;
+
+        // Fell behind by more than a frame, start pacing from now rather
+        // than rendering a burst of frames to catch up
+        let now = Instant::now();
+        if self.next_frame < now {
+            self.next_frame = now + frame_duration;
+        }
+    }
+}
+
+/// Duration of a single frame at the provided frame rate
+fn frame_duration(fps: u32) -> Duration {
+    Duration::from_secs_f64(1.0 / fps.max(1) as f64)
+}
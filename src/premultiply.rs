@@ -0,0 +1,67 @@
+use nalgebra::Vector2;
+use winapi::um::d3d11::{ID3D11Device, ID3D11DeviceContext};
+
+use crate::{
+    dx::{
+        shader::{ComputeShader, ShaderResourceView, UnorderedAccessView},
+        texture::Texture,
+    },
+    load_shader,
+};
+
+/// Thread group size of the premultiply shader, must match `GROUP_SIZE`
+/// in premultiply.hlsl
+const PREMULTIPLY_GROUP_SIZE: u32 = 8;
+
+/// Where item textures are premultiplied by their alpha
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PremultiplyMode {
+    /// On the CPU before the texture is uploaded, see
+    /// [premultiply_alpha](crate::texture_loader::premultiply_alpha)
+    Cpu,
+    /// With a compute shader after the texture is uploaded, only supported
+    /// for non sRGB render targets since sRGB textures can't be written
+    /// through an unordered access view
+    Compute,
+}
+
+/// Compute pass producing a premultiplied copy of a texture
+pub struct Premultiply {
+    shader: ComputeShader,
+}
+
+impl Premultiply {
+    pub fn create(device: &ID3D11Device) -> anyhow::Result<Premultiply> {
+        let blob = load_shader!("premultiply.hlsl", "cs_5_0", "CSMain")?;
+        let shader = ComputeShader::create(device, blob)?;
+
+        Ok(Premultiply { shader })
+    }
+
+    /// Premultiplies `source`, returning the premultiplied texture and a
+    /// view for sampling it
+    pub fn apply(
+        &mut self,
+        device: &ID3D11Device,
+        ctx: &ID3D11DeviceContext,
+        source: &mut ShaderResourceView,
+        size: Vector2<u32>,
+    ) -> anyhow::Result<(Texture, ShaderResourceView)> {
+        let mut texture = Texture::create_unordered(device, size.x, size.y)?;
+        let mut uav =
+            UnorderedAccessView::create_from_texture(device, texture.texture.cast_as_mut())?;
+        let srv = ShaderResourceView::create_from_texture(device, texture.texture.cast_as_mut())?;
+
+        self.shader.set_shader(ctx);
+        source.bind_compute(ctx, 0);
+        uav.bind_compute(ctx, 0);
+
+        ComputeShader::dispatch(ctx, size, PREMULTIPLY_GROUP_SIZE);
+
+        // The output must be unbound before the pixel shader can sample it
+        UnorderedAccessView::unbind_compute(ctx, 0);
+        ShaderResourceView::unbind_compute(ctx, 0);
+
+        Ok((texture, srv))
+    }
+}
@@ -0,0 +1,227 @@
+//! Software reference for the item shaders, computes on the CPU what the
+//! GPU is expected to draw for an item so output can be checked without
+//! reading back from a device.
+//!
+//! Covers the quad placement and the base texture sample of the front
+//! face. Lighting, outlines, masks, shapes and shadows are not modelled
+
+use nalgebra::{Vector2, Vector4};
+
+use crate::{item::ItemDataBuffer, texture_loader::TextureData};
+
+/// Corners of the item quad (Normalized to screen size) after
+/// `elapsed_time` ms, in the order the quad mesh emits them
+pub fn quad_corners(
+    data: &ItemDataBuffer,
+    elapsed_time: f32,
+    screen_aspect: f32,
+) -> [Vector2<f32>; 4] {
+    at(data, elapsed_time).current_corners(screen_aspect)
+}
+
+/// Texture coordinate sampled at `local` (0-1 across the quad) after
+/// `elapsed_time` ms, matches the vertex shader flip and sprite frame
+pub fn texture_coordinate(
+    data: &ItemDataBuffer,
+    elapsed_time: f32,
+    local: Vector2<f32>,
+) -> Vector2<f32> {
+    let data = at(data, elapsed_time);
+
+    let mut tex = local;
+    if data.flip_u != 0 {
+        tex.x = 1.0 - tex.x;
+    }
+    if data.flip_v != 0 {
+        tex.y = 1.0 - tex.y;
+    }
+
+    sprite_frame_offset(&data) + tex.component_mul(&data.uv_scale)
+}
+
+/// Color (straight alpha, 0-1) of the front face at `local` (0-1 across
+/// the quad) after `elapsed_time` ms, `texture` is the front texture as
+/// uploaded (premultiplied when the item data says so)
+pub fn sample_color(
+    data: &ItemDataBuffer,
+    texture: &TextureData,
+    elapsed_time: f32,
+    local: Vector2<f32>,
+) -> Vector4<f32> {
+    let uv = texture_coordinate(data, elapsed_time, local);
+    let data = at(data, elapsed_time);
+    let mut color = sample_bilinear(texture, uv);
+
+    if data.premultiplied != 0 && color.w > 0.0 {
+        let alpha = color.w;
+        color.x /= alpha;
+        color.y /= alpha;
+        color.z /= alpha;
+    }
+
+    if data.color_cycle_speed != 0.0 {
+        color = rotate_hue(color, data.hue_phase);
+    }
+
+    // Discarded pixels leave the target untouched, the rest are opaque
+    if data.alpha_cutoff > 0.0 {
+        color.w = if color.w < data.alpha_cutoff {
+            0.0
+        } else {
+            1.0
+        };
+    }
+
+    color
+}
+
+/// Copy of the item data advanced to `elapsed_time` ms
+fn at(data: &ItemDataBuffer, elapsed_time: f32) -> ItemDataBuffer {
+    let mut data = data.clone();
+    data.advance(elapsed_time as f64 / 1000.0, false);
+    data
+}
+
+/// Matches `SpriteFrameOffset` in the vertex shader
+fn sprite_frame_offset(data: &ItemDataBuffer) -> Vector2<f32> {
+    if data.frame_count < 2 {
+        return data.uv_offset;
+    }
+
+    let start_cell = data.uv_offset.component_div(&data.uv_scale);
+    let cell = start_cell.y.round() as u32 * data.sheet_columns
+        + start_cell.x.round() as u32
+        + data.sprite_frame;
+
+    Vector2::new(
+        (cell % data.sheet_columns) as f32,
+        (cell / data.sheet_columns) as f32,
+    )
+    .component_mul(&data.uv_scale)
+}
+
+/// Bilinear sample with wrapping addressing, matches the item sampler
+fn sample_bilinear(texture: &TextureData, uv: Vector2<f32>) -> Vector4<f32> {
+    let position = Vector2::new(
+        uv.x * texture.width as f32 - 0.5,
+        uv.y * texture.height as f32 - 0.5,
+    );
+    let base = position.map(f32::floor);
+    let f = position - base;
+
+    let texel = |dx: i64, dy: i64| {
+        let x = (base.x as i64 + dx).rem_euclid(texture.width as i64) as u32;
+        let y = (base.y as i64 + dy).rem_euclid(texture.height as i64) as u32;
+        let [r, g, b, a] = texture.buffer.get_pixel(x, y).0;
+        Vector4::new(r, g, b, a).map(|channel| channel as f32 / 255.0)
+    };
+
+    let top = texel(0, 0).lerp(&texel(1, 0), f.x);
+    let bottom = texel(0, 1).lerp(&texel(1, 1), f.x);
+    top.lerp(&bottom, f.y)
+}
+
+/// Rotates the hue of the color by `phase` (0-1 of a full turn),
+/// matches `ApplyColorCycle` in the fragment shader
+fn rotate_hue(color: Vector4<f32>, phase: f32) -> Vector4<f32> {
+    let (r, g, b) = (color.x, color.y, color.z);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+
+    let hue = if delta <= 0.0 {
+        0.0
+    } else if max == r {
+        ((g - b) / delta).rem_euclid(6.0) / 6.0
+    } else if max == g {
+        ((b - r) / delta + 2.0) / 6.0
+    } else {
+        ((r - g) / delta + 4.0) / 6.0
+    };
+    let saturation = if max > 0.0 { delta / max } else { 0.0 };
+
+    let hue = (hue + phase).fract();
+    let channel = |offset: f32| {
+        let pure = (((hue + offset).fract() * 6.0 - 3.0).abs() - 1.0).clamp(0.0, 1.0);
+        max * (1.0 + (pure - 1.0) * saturation)
+    };
+
+    Vector4::new(
+        channel(1.0),
+        channel(2.0 / 3.0),
+        channel(1.0 / 3.0),
+        color.w,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use image::{ImageBuffer, Rgba};
+
+    use super::*;
+
+    /// 2x2 texture with a different color in each corner
+    fn corner_texture() -> TextureData {
+        let colors = [
+            [255, 0, 0, 255],
+            [0, 255, 0, 255],
+            [0, 0, 255, 255],
+            [255, 255, 255, 255],
+        ];
+        let buffer = ImageBuffer::from_fn(2, 2, |x, y| Rgba(colors[(y * 2 + x) as usize]));
+
+        TextureData {
+            buffer,
+            width: 2,
+            height: 2,
+        }
+    }
+
+    #[test]
+    fn flips_texture_coordinates() {
+        let local = Vector2::new(0.25, 0.125);
+        let mut data = ItemDataBuffer::default();
+        assert_eq!(
+            texture_coordinate(&data, 0.0, local),
+            Vector2::new(0.25, 0.125)
+        );
+
+        data.flip_u = 1;
+        assert_eq!(
+            texture_coordinate(&data, 0.0, local),
+            Vector2::new(0.75, 0.125)
+        );
+
+        data.flip_v = 1;
+        assert_eq!(
+            texture_coordinate(&data, 0.0, local),
+            Vector2::new(0.75, 0.875)
+        );
+
+        data.flip_u = 0;
+        assert_eq!(
+            texture_coordinate(&data, 0.0, local),
+            Vector2::new(0.25, 0.875)
+        );
+    }
+
+    #[test]
+    fn flips_sampled_texels() {
+        let texture = corner_texture();
+        // Center of the top left texel
+        let local = Vector2::new(0.25, 0.25);
+        let color = |flip_u: bool, flip_v: bool| {
+            let data = ItemDataBuffer {
+                flip_u: flip_u as u32,
+                flip_v: flip_v as u32,
+                ..Default::default()
+            };
+            sample_color(&data, &texture, 0.0, local)
+        };
+
+        assert_eq!(color(false, false), Vector4::new(1.0, 0.0, 0.0, 1.0));
+        assert_eq!(color(true, false), Vector4::new(0.0, 1.0, 0.0, 1.0));
+        assert_eq!(color(false, true), Vector4::new(0.0, 0.0, 1.0, 1.0));
+        assert_eq!(color(true, true), Vector4::new(1.0, 1.0, 1.0, 1.0));
+    }
+}
@@ -0,0 +1,1079 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use nalgebra::Vector2;
+use rand::rngs::StdRng;
+use winapi::um::{
+    d3d11::{ID3D11Device, ID3D11DeviceContext},
+    winnt::HANDLE,
+};
+
+use crate::{
+    app::{
+        admit_item, expire_items, remove_items, render, update, DroppedItems, RenderContext,
+        RendererConfig, ShutdownSignal, SpawnOutcome,
+    },
+    background::BackgroundFit,
+    clock::SceneClock,
+    com::ComPtr,
+    dx::{
+        device::DeviceCapabilities,
+        sampler::SamplerLod,
+        shader::{ShaderBlob, ShaderResourceView},
+        texture::Texture,
+    },
+    emitter::{Emitter, EmitterDefinition},
+    item::{
+        create_item_grid, ItemAttachment, ItemDataBuffer, ItemDefinition, ItemEffectId, ItemId,
+        ItemInfo, ItemQueue, ItemSender, ItemTransform, QueuedItemDefinition, RenderItemDefinition,
+        SamplerOverride, SceneForces, UpscaleFilter, WOBBLE_GRID_SEGMENTS,
+    },
+    pacer::FramePacer,
+    premultiply::PremultiplyMode,
+    spout::{SendStatus, SpoutSender},
+    text::TextRenderer,
+    texture_loader::{premultiply_alpha, TextureData},
+};
+
+/// Time to wait for a consumer to release the keyed mutex of the shared
+/// render target before skipping the frame
+const KEYED_MUTEX_TIMEOUT: Duration = Duration::from_millis(16);
+
+/// CPU time spent in each stage of the most recently rendered frame, the
+/// GPU finishes the frame asynchronously so its time is not included
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameStats {
+    /// Advancing the items and emitters to the scene time
+    pub update: Duration,
+    /// Recording the draw calls of the frame
+    pub draw: Duration,
+    /// Sharing the frame over Spout, nothing is sent by headless renderers
+    pub send: Duration,
+}
+
+/// Renders thrown items and shares the result over Spout, call
+/// [Renderer::tick] to render each frame
+pub struct Renderer {
+    config: RendererConfig,
+    render_ctx: RenderContext,
+    /// Sender sharing frames over Spout, [None] for headless renderers
+    sender: Option<SpoutSender>,
+    /// Status of the most recent frame sent over Spout
+    send_status: SendStatus,
+    /// Items dropped by the item cap
+    dropped_items: DroppedItems,
+    /// Timings of the most recently rendered frame
+    frame_stats: FrameStats,
+    /// Renderer for item labels, only available when a font is present
+    text_renderer: Option<TextRenderer>,
+    /// Queue for items to be spawned
+    item_queue: Arc<ItemQueue>,
+    /// Sender handed out for submitting items
+    item_sender: ItemSender,
+    /// Currently alive items
+    items: Vec<RenderItemDefinition>,
+    /// Currently alive particle emitters
+    emitters: Vec<Emitter>,
+    spawn_rng: StdRng,
+    clock: SceneClock,
+    pacer: FramePacer,
+    /// Last time there were items to render, used to detect idling
+    last_active: Instant,
+    shutdown: ShutdownSignal,
+}
+
+impl Renderer {
+    /// Creates the renderer and starts the Spout sender, must be called
+    /// from within a tokio runtime
+    pub fn new(config: RendererConfig) -> anyhow::Result<Renderer> {
+        Self::start(config, true, RenderContext::create)
+    }
+
+    /// Creates a renderer that renders without sharing frames over Spout,
+    /// frames are only available through [Renderer::read_frame]. Used for
+    /// tests and benchmarks, must be called from within a tokio runtime
+    pub fn headless(config: RendererConfig) -> anyhow::Result<Renderer> {
+        Self::start(config, false, RenderContext::create)
+    }
+
+    /// Creates the renderer on a device and immediate context owned by the
+    /// host application, the Spout sender shares frames from the same
+    /// device. See [RenderContext::from_existing] for the requirements
+    pub fn from_existing(
+        config: RendererConfig,
+        device: ComPtr<ID3D11Device>,
+        ctx: ComPtr<ID3D11DeviceContext>,
+    ) -> anyhow::Result<Renderer> {
+        Self::start(config, true, |config| {
+            RenderContext::from_existing(device, ctx, config)
+        })
+    }
+
+    /// Starts the render context created by `create_render_ctx` along with
+    /// the Spout sender when `spout` is set
+    fn start(
+        config: RendererConfig,
+        spout: bool,
+        create_render_ctx: impl FnOnce(&RendererConfig) -> anyhow::Result<RenderContext>,
+    ) -> anyhow::Result<Renderer> {
+        let mut sender = match spout {
+            true => {
+                let mut sender = SpoutSender::create()?;
+                sender.set_sender_name(&config.sender_name)?;
+                // Validates that spout can share the format before creating
+                // the target
+                sender.set_sender_format(config.render_target_format)?;
+                Some(sender)
+            }
+            false => None,
+        };
+
+        let mut render_ctx = create_render_ctx(&config)?;
+        render_ctx.warmup()?;
+
+        if let Some(sender) = sender.as_mut() {
+            sender.open_directx11(render_ctx.device.as_mut())?;
+
+            if config.frame_sync {
+                sender.enable_frame_sync(true);
+            }
+        }
+
+        let text_renderer = config.font_path.as_ref().and_then(|path| {
+            TextRenderer::load(path)
+                .inspect_err(|err| eprintln!("item labels disabled, failed to load font: {err}"))
+                .ok()
+        });
+
+        let item_queue = Arc::new(ItemQueue::default());
+        let item_sender = ItemSender::create(item_queue.clone());
+
+        Ok(Renderer {
+            spawn_rng: config.spawn_rng(),
+            pacer: config.frame_pacer(),
+            config,
+            render_ctx,
+            sender,
+            send_status: SendStatus::Sent,
+            dropped_items: DroppedItems::default(),
+            frame_stats: FrameStats::default(),
+            text_renderer,
+            item_queue,
+            item_sender,
+            items: Vec::new(),
+            emitters: Vec::new(),
+            clock: SceneClock::new(),
+            last_active: Instant::now(),
+            shutdown: ShutdownSignal::default(),
+        })
+    }
+
+    /// Loads the item textures in the background then queues the item
+    /// to be spawned, returns the ID the item will have once spawned
+    pub fn spawn_item(&self, definition: ItemDefinition) -> anyhow::Result<ItemId> {
+        self.item_sender.send(definition)
+    }
+
+    /// Sender for submitting items from other threads or tasks
+    pub fn item_sender(&self) -> ItemSender {
+        self.item_sender.clone()
+    }
+
+    /// Queue items are spawned from, items with already loaded textures
+    /// can be pushed directly from any thread
+    pub fn item_queue(&self) -> Arc<ItemQueue> {
+        self.item_queue.clone()
+    }
+
+    /// Signal that stops the renderer, can be requested from any thread
+    pub fn shutdown_signal(&self) -> ShutdownSignal {
+        self.shutdown.clone()
+    }
+
+    /// Sets the image drawn behind all items
+    pub fn set_background(&mut self, data: &TextureData, fit: BackgroundFit) -> anyhow::Result<()> {
+        let texture = create_item_texture(&self.render_ctx.device, data, self.config.srgb)?;
+        self.render_ctx.set_background(texture, fit)?;
+
+        // Render again in case the renderer is idle
+        self.last_active = Instant::now();
+        Ok(())
+    }
+
+    /// Removes the background image
+    pub fn clear_background(&mut self) {
+        self.render_ctx.clear_background();
+        self.last_active = Instant::now();
+    }
+
+    /// Moves a spawned item to a fixed transform, the item stops following
+    /// its motion and rotation. Returns false when no item with the ID has
+    /// been spawned (items spawn once their textures finish loading)
+    pub fn set_item_transform(&mut self, id: ItemId, transform: ItemTransform) -> bool {
+        let Some(item) = self.items.iter_mut().find(|item| item.id == id) else {
+            return false;
+        };
+
+        transform.apply(&mut item.item_data);
+        true
+    }
+
+    /// Removes a spawned item along with anything attached to it, the item
+    /// GPU resources are released immediately. Returns false when no item
+    /// with the ID has been spawned
+    pub fn remove_item(&mut self, id: ItemId) -> bool {
+        remove_items(&mut self.items, |item| item.id == id) > 0
+    }
+
+    /// Removes every spawned item and drops items waiting to be spawned,
+    /// items whose textures are still loading spawn once they finish
+    pub fn clear_all(&mut self) {
+        while self.item_queue.pop().is_some() {}
+        self.items.clear();
+    }
+
+    /// Snapshots of the spawned items in spawn order, items attached to
+    /// another item (such as labels) are not included
+    pub fn items(&self) -> impl Iterator<Item = ItemInfo> + '_ {
+        let viewports = &self.render_ctx.world.viewports;
+
+        self.items
+            .iter()
+            .filter(|item| item.attachment.is_none())
+            .filter_map(|item| Some(item.info(viewports.get(item.stage)?)))
+    }
+
+    /// Sets the mip level of detail used when sampling item textures, a
+    /// negative bias sharpens scaled down items and a positive bias
+    /// softens them
+    pub fn set_sampler_lod(&mut self, lod: SamplerLod) -> anyhow::Result<()> {
+        self.render_ctx
+            .item
+            .set_sampler_lod(&self.render_ctx.device, lod)?;
+        self.render_ctx.invalidate();
+        Ok(())
+    }
+
+    /// Sets the anisotropic filtering used when sampling item textures,
+    /// sharpens items viewed at steep angles while spinning. Values above
+    /// what the device supports are clamped, returns the effective value
+    pub fn set_max_anisotropy(&mut self, max_anisotropy: u32) -> anyhow::Result<u32> {
+        let effective = self
+            .render_ctx
+            .item
+            .set_max_anisotropy(&self.render_ctx.device, max_anisotropy)?;
+        self.render_ctx.invalidate();
+        Ok(effective)
+    }
+
+    /// Overrides the sampler chosen by each item, forces every item to
+    /// linear filtering or to a pixelated look without changing the items
+    pub fn set_sampler_override(&mut self, sampler_override: SamplerOverride) {
+        self.render_ctx.item.sampler_override = sampler_override;
+        self.render_ctx.invalidate();
+    }
+
+    /// Spawns a particle emitter, unlike items emitters have no textures to
+    /// load so they start rendering on the next frame
+    pub fn spawn_emitter(&mut self, definition: EmitterDefinition) -> anyhow::Result<()> {
+        let Some(stage_size) = self.render_ctx.world.stage_size(definition.stage) else {
+            anyhow::bail!("emitter stage {} does not exist", definition.stage);
+        };
+
+        let emitter = Emitter::create(
+            &self.render_ctx.device,
+            &definition,
+            stage_size,
+            self.clock.now(),
+            &mut self.spawn_rng,
+        )?;
+        self.emitters.push(emitter);
+
+        // Wake the render loop in case the renderer is idle
+        self.last_active = Instant::now();
+        Ok(())
+    }
+
+    /// Renames the Spout sender, receivers connected to the old name lose
+    /// the sender and have to find it again under the new name (e.g. when
+    /// switching scenes). See [SpoutSender::set_sender_name]
+    pub fn set_sender_name(&mut self, name: impl Into<String>) -> anyhow::Result<()> {
+        let name = name.into();
+        if let Some(sender) = self.sender.as_mut() {
+            sender.set_sender_name(&name)?;
+        }
+        self.config.sender_name = name;
+        Ok(())
+    }
+
+    /// Sets the gravity and wind acting on the scene. Items following the
+    /// scene forces keep the forces they spawned with, particles follow
+    /// the change immediately
+    pub fn set_scene_forces(&mut self, forces: SceneForces) {
+        self.render_ctx.world.forces = forces;
+    }
+
+    /// Fades the edges of items across the pixels they partially cover.
+    /// Item quads are rasterized by pixel centers so slowly moving items
+    /// step a whole pixel at a time, with sub-pixel edges they glide
+    /// smoothly instead. Cutout items keep their hard edges
+    pub fn set_subpixel_edges(&mut self, enabled: bool) {
+        self.render_ctx.world.subpixel_edges = enabled;
+        self.render_ctx.invalidate();
+    }
+
+    /// Only redraws the area around items that moved or changed each frame
+    /// instead of the whole render target, frames where nothing changed
+    /// are not redrawn at all. Saves GPU time for overlays that are idle
+    /// most of the time
+    pub fn set_dirty_rects(&mut self, enabled: bool) -> anyhow::Result<()> {
+        self.render_ctx.set_dirty_tracking(enabled)
+    }
+
+    /// Registers custom item shaders that items select using
+    /// [ItemDefinitionBuilder::effect](crate::item::ItemDefinitionBuilder::effect),
+    /// see [ItemShader::register_effect](crate::item::ItemShader::register_effect)
+    pub fn register_item_effect(
+        &mut self,
+        id: ItemEffectId,
+        vertex_blob: Option<ShaderBlob>,
+        pixel_blob: Option<ShaderBlob>,
+    ) -> anyhow::Result<()> {
+        self.render_ctx.item.item_shader.register_effect(
+            &self.render_ctx.device,
+            id,
+            vertex_blob,
+            pixel_blob,
+        )?;
+
+        // Items already using the effect change look
+        self.render_ctx.invalidate();
+        Ok(())
+    }
+
+    /// Adds a stage rendering into the provided sub-rectangle of the render
+    /// target (pixels), returns the stage index to spawn items into
+    pub fn add_stage(&mut self, position: Vector2<f32>, size: Vector2<f32>) -> usize {
+        let stage = self.render_ctx.world.add_stage(position, size);
+        self.render_ctx.invalidate();
+        stage
+    }
+
+    /// Shared handle of the frames sent to receivers for consumers that
+    /// open the texture directly instead of through Spout, see
+    /// [crate::dx::texture::RenderTargetTexture::shared_handle]
+    pub fn shared_handle(&self) -> anyhow::Result<HANDLE> {
+        self.render_ctx.rtv.shared_handle()
+    }
+
+    /// Capabilities of the device the renderer is using
+    pub fn capabilities(&self) -> &DeviceCapabilities {
+        &self.render_ctx.capabilities
+    }
+
+    /// Whether the renderer has been asked to shutdown
+    pub fn is_shutdown_requested(&self) -> bool {
+        self.shutdown.is_requested()
+    }
+
+    /// Renders and sends a single frame, waiting for the frame pacer. While
+    /// idle this blocks until an item is queued or an idle frame is due
+    pub fn tick(&mut self) -> anyhow::Result<()> {
+        if !self.items.is_empty() || !self.emitters.is_empty() || !self.item_queue.is_empty() {
+            self.last_active = Instant::now();
+        } else if self
+            .config
+            .idle_timeout
+            .is_some_and(|timeout| self.last_active.elapsed() >= timeout)
+        {
+            // Nothing to render, sleep until an item is pushed
+            if !self
+                .item_queue
+                .wait_for_items(self.config.idle_frame_interval)
+            {
+                if !self.shutdown.is_requested() {
+                    // Keep publishing the last (empty) frame at a low rate
+                    self.with_output_locked(Self::send_frame)?;
+                }
+
+                return Ok(());
+            }
+
+            self.last_active = Instant::now();
+        }
+
+        self.clock.tick();
+        expire_items(&mut self.items);
+        self.emitters.retain(|emitter| !emitter.is_expired());
+
+        if !self.item_queue.is_empty() {
+            let item_queue = self.item_queue.clone();
+            for item in item_queue.take(self.config.max_spawns_per_frame) {
+                self.spawn_queued(item)?;
+            }
+        }
+
+        let update_start = Instant::now();
+        update(
+            &self.render_ctx.ctx,
+            &mut self.items,
+            &mut self.emitters,
+            &self.clock,
+        )?;
+        let update_time = update_start.elapsed();
+
+        self.with_output_locked(|renderer| {
+            let draw_start = Instant::now();
+            render(
+                &mut renderer.render_ctx,
+                &mut renderer.items,
+                &mut renderer.emitters,
+                &renderer.clock,
+            )?;
+
+            let send_start = Instant::now();
+            renderer.send_frame()?;
+
+            renderer.frame_stats = FrameStats {
+                update: update_time,
+                draw: send_start - draw_start,
+                send: send_start.elapsed(),
+            };
+            Ok(())
+        })?;
+        self.pacer.wait();
+
+        Ok(())
+    }
+
+    /// Status of the most recent frame sent over Spout, hosts can use this
+    /// to show when receivers aren't getting frames
+    pub fn send_status(&self) -> SendStatus {
+        self.send_status
+    }
+
+    /// Timings of the most recently rendered frame, frames skipped while
+    /// idle or while a consumer holds the keyed mutex keep the previous
+    /// timings
+    pub fn frame_stats(&self) -> FrameStats {
+        self.frame_stats
+    }
+
+    /// Number of items dropped because [RendererConfig::max_items] was
+    /// reached, either evicted or refused depending on the
+    /// [crate::app::EvictionPolicy]
+    pub fn dropped_items(&self) -> DroppedItems {
+        self.dropped_items
+    }
+
+    /// Reads back the most recently rendered frame, the pixels are tightly
+    /// packed in the render target format (BGRA for
+    /// [crate::dx::texture::RENDER_TARGET_FORMAT]).
+    /// Stalls until the GPU has finished the frame
+    pub fn read_frame(&mut self) -> anyhow::Result<Vec<u8>> {
+        self.render_ctx
+            .rtv
+            .read_pixels(&self.render_ctx.device, &self.render_ctx.ctx)
+    }
+
+    /// Runs `frame` while holding the keyed mutex of the shared render
+    /// target, the frame is skipped if a consumer keeps holding the mutex.
+    /// Runs `frame` directly when the render target has no keyed mutex
+    fn with_output_locked(
+        &mut self,
+        frame: impl FnOnce(&mut Self) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        if !self.render_ctx.rtv.acquire_sync(KEYED_MUTEX_TIMEOUT)? {
+            return Ok(());
+        }
+
+        let result = frame(self);
+        self.render_ctx.rtv.release_sync()?;
+        result
+    }
+
+    /// Sends the current render target over Spout, logging when the
+    /// sender disconnects or recovers. Does nothing for headless renderers
+    fn send_frame(&mut self) -> anyhow::Result<()> {
+        let Some(sender) = self.sender.as_mut() else {
+            return Ok(());
+        };
+
+        let output = self.render_ctx.rtv.output_texture(&self.render_ctx.ctx);
+        let status = sender.send_texture(output)?;
+
+        // Wake receivers waiting for the new frame
+        if self.config.frame_sync && status == SendStatus::Sent {
+            sender.set_frame_sync(&self.config.sender_name)?;
+        }
+
+        match (self.send_status, status) {
+            (SendStatus::Disconnected, SendStatus::Disconnected) => {}
+            (_, SendStatus::Disconnected) => {
+                eprintln!("spout sender disconnected, frames are failing to send")
+            }
+            (SendStatus::Disconnected, SendStatus::Sent) => {
+                eprintln!("spout sender reconnected")
+            }
+            _ => {}
+        }
+
+        self.send_status = status;
+        Ok(())
+    }
+
+    /// Creates the GPU resources for a queued item and adds it to the world
+    fn spawn_queued(&mut self, mut item: QueuedItemDefinition) -> anyhow::Result<()> {
+        // Items are sized and positioned relative to their stage
+        let Some(screen_size_f32) = self.render_ctx.world.stage_size(item.stage) else {
+            eprintln!("item stage {} does not exist, dropped new item", item.stage);
+            return Ok(());
+        };
+
+        if let Some(effect) = item.effect {
+            if !self.render_ctx.item.item_shader.has_effect(effect) {
+                eprintln!("item effect {effect:?} is not registered, dropped new item");
+                return Ok(());
+            }
+        }
+
+        let outcome = admit_item(&self.config, &mut self.items);
+        self.dropped_items.record(outcome);
+
+        match outcome {
+            SpawnOutcome::Spawned => {}
+            SpawnOutcome::EvictedOldest => {
+                eprintln!("item limit reached, evicted oldest item");
+            }
+            SpawnOutcome::Refused => {
+                eprintln!("item limit reached, dropped new item");
+                return Ok(());
+            }
+        }
+
+        if self.config.premultiply == Some(PremultiplyMode::Cpu) {
+            item.texture_data = item.texture_data.map(premultiply_alpha);
+            item.back_texture_data = item.back_texture_data.map(premultiply_alpha);
+        }
+
+        let device = &self.render_ctx.device;
+        let start_position = Vector2::new(0.0, 0.0);
+        let end_position = Vector2::new(0.5, 0.5);
+
+        // Items whose texture failed to load use the placeholder
+        let (item_texture, mut srv) = match &item.texture_data {
+            Some(texture_data) => {
+                let mut texture = create_item_texture(device, texture_data, self.config.srgb)?;
+                let mut srv =
+                    ShaderResourceView::create_from_texture(device, texture.texture.cast_as_mut())?;
+                if self.config.premultiply == Some(PremultiplyMode::Compute) {
+                    (texture, srv) = self.render_ctx.premultiply.apply(
+                        device,
+                        &self.render_ctx.ctx,
+                        &mut srv,
+                        texture.size,
+                    )?;
+                }
+                (texture, srv)
+            }
+            None => (
+                self.render_ctx.placeholder.clone(),
+                self.render_ctx.placeholder_srv.clone(),
+            ),
+        };
+
+        let mut texture_size = item_texture.size.cast::<f32>();
+
+        // Sprite sheet items are sized by a single cell
+        if let Some(sprite_sheet) = &item.sprite_sheet {
+            texture_size = texture_size.component_mul(&sprite_sheet.uv_scale());
+        }
+
+        // Texture size relative to the window
+        let norm_texture_size = texture_size.component_div(&screen_size_f32);
+
+        // Positions are item centers, anchors move the center so another
+        // point of the item lands on the position
+        let item_size = texture_size * item.scale;
+        let start_pos = start_position.component_mul(&screen_size_f32)
+            + item.start_anchor.center_offset(&item_size);
+        let end_pos = end_position.component_mul(&screen_size_f32)
+            + item.end_anchor.center_offset(&item_size);
+
+        let mut item_data = ItemDataBuffer {
+            norm_texture_size,
+            start_position: to_screen_space(start_pos, &screen_size_f32),
+            end_position: to_screen_space(end_pos, &screen_size_f32),
+            scale: item.scale,
+            duration: item.duration.unwrap_or(f32::INFINITY),
+            flip_u: item.flip_u as u32,
+            flip_v: item.flip_v as u32,
+            pixelate_size: item.pixelate_size,
+            normal_enabled: item.normal_texture_data.is_some() as u32,
+            mask_enabled: item.mask_texture_data.is_some() as u32,
+            premultiplied: self.config.premultiply.is_some() as u32,
+            color_cycle_speed: item.color_cycle_speed,
+            // Pixelated items sample with point filtering
+            upscale_filter: match item.pixelate {
+                true => UpscaleFilter::Bilinear as u32,
+                false => item.upscale_filter as u32,
+            },
+            ..Default::default()
+        };
+        item.motion.apply(&mut item_data);
+        item.rotation.apply(&mut item_data, &mut self.spawn_rng);
+        if let Some(transform) = &item.transform {
+            transform.apply(&mut item_data);
+        }
+        if let Some(sprite_sheet) = &item.sprite_sheet {
+            sprite_sheet.apply(&mut item_data);
+        }
+        if let Some(animation) = &item.sprite_animation {
+            animation.apply(&mut item_data);
+        }
+        if let Some(shadow) = &item.shadow {
+            shadow.apply(&mut item_data, &screen_size_f32);
+        }
+        if let Some(outline) = &item.outline {
+            outline.apply(&mut item_data);
+        }
+        if let Some(stretch) = &item.stretch {
+            stretch.apply(&mut item_data);
+        }
+        item.shape.apply(&mut item_data);
+        item_data.alpha_cutoff = item.alpha_cutoff.unwrap_or(0.0);
+        item.forces.apply(
+            &self.render_ctx.world.forces,
+            &screen_size_f32,
+            &mut item_data,
+        );
+
+        let (mut back_texture, mut back_srv) =
+            create_optional_texture(device, item.back_texture_data, self.config.srgb)?;
+        if let (Some(texture), Some(srv), Some(PremultiplyMode::Compute)) =
+            (&mut back_texture, &mut back_srv, self.config.premultiply)
+        {
+            (*texture, *srv) = self.render_ctx.premultiply.apply(
+                device,
+                &self.render_ctx.ctx,
+                srv,
+                texture.size,
+            )?;
+        }
+
+        // Normal maps hold directions, they are never sRGB encoded
+        let (normal_texture, normal_srv) =
+            create_optional_texture(device, item.normal_texture_data, false)?;
+        let (mask_texture, mask_srv) =
+            create_optional_texture(device, item.mask_texture_data, false)?;
+
+        // Blurred shadows are computed once here rather than per frame
+        let (shadow_texture, shadow_srv) = match &item.shadow {
+            Some(shadow) if shadow.blur_radius > 0 => {
+                let (texture, srv) = self.render_ctx.shadow_blur.blur(
+                    device,
+                    &self.render_ctx.ctx,
+                    &mut srv,
+                    item_texture.size,
+                    shadow.blur_radius,
+                )?;
+                (Some(texture), Some(srv))
+            }
+            _ => (None, None),
+        };
+
+        // Wobbling items rewrite their vertices so they can't share the quad
+        let mesh = match item.wobble {
+            Some(_) => create_item_grid(device, WOBBLE_GRID_SEGMENTS)?,
+            None => self.render_ctx.item.quad.clone(),
+        };
+
+        let start_time = self.clock.now() + Duration::from_secs_f32(item.start_delay / 1000.0);
+        let item_id = item.id;
+
+        self.items.push(RenderItemDefinition {
+            id: item_id,
+            attachment: None,
+            texture: item_texture,
+            shader_resource_view: srv,
+            mesh,
+            back_texture,
+            back_srv,
+            normal_texture,
+            normal_srv,
+            mask_texture,
+            mask_srv,
+            shadow_texture,
+            shadow_srv,
+            pixelate: item.pixelate,
+            start_time,
+            delayed: start_time > self.clock.now(),
+            looping: item.looping,
+            stage: item.stage,
+            order: item.order,
+            wobble: item.wobble,
+            wobble_phase: 0.0,
+            effect: item.effect,
+            item_data,
+        });
+
+        // Spawn the label as its own item attached to the item
+        if let (Some(label), Some(text_renderer)) = (item.label, self.text_renderer.as_mut()) {
+            let label_texture = text_renderer.get_or_create(device, &label.text, label.size)?;
+            let label_size = label_texture.texture.size.cast::<f32>();
+
+            // Offset is in pixels, convert to a screen space offset
+            let offset = Vector2::new(
+                2.0 * label.offset.x / screen_size_f32.x,
+                -2.0 * label.offset.y / screen_size_f32.y,
+            );
+
+            let item_data = ItemDataBuffer {
+                norm_texture_size: label_size.component_div(&screen_size_f32),
+                spin_speed: 0.0,
+                scale: 1.0,
+                // Labels are removed along with their parent
+                duration: f32::INFINITY,
+                ..Default::default()
+            };
+
+            self.items.push(RenderItemDefinition {
+                id: ItemId::random(),
+                attachment: Some(ItemAttachment {
+                    parent: item_id,
+                    offset,
+                }),
+                texture: label_texture.texture,
+                shader_resource_view: label_texture.shader_resource_view,
+                mesh: self.render_ctx.item.quad.clone(),
+                back_texture: None,
+                back_srv: None,
+                normal_texture: None,
+                normal_srv: None,
+                mask_texture: None,
+                mask_srv: None,
+                shadow_texture: None,
+                shadow_srv: None,
+                pixelate: false,
+                start_time,
+                delayed: start_time > self.clock.now(),
+                looping: false,
+                stage: item.stage,
+                order: item.order,
+                wobble: None,
+                wobble_phase: 0.0,
+                effect: None,
+                item_data,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Stops the renderer releasing the Spout sender and GPU resources
+    pub fn shutdown(self) {
+        let Renderer {
+            render_ctx,
+            sender,
+            text_renderer,
+            item_queue,
+            items,
+            emitters,
+            ..
+        } = self;
+
+        // Wake anything waiting on the queue
+        item_queue.close();
+
+        // Release resources in order, items reference textures created by
+        // the device and the sender must stop sharing before the device goes
+        drop(items);
+        drop(emitters);
+        drop(text_renderer);
+        if let Some(mut sender) = sender {
+            sender.release();
+        }
+        drop(render_ctx);
+    }
+}
+
+/// Uploads item texture data, sRGB textures are linearized when sampled
+fn create_item_texture(
+    device: &ID3D11Device,
+    data: &TextureData,
+    srgb: bool,
+) -> anyhow::Result<Texture> {
+    if srgb {
+        Texture::create_from_data_srgb(device, data.width, data.height, &data.buffer)
+    } else {
+        Texture::create_from_data(device, data.width, data.height, &data.buffer)
+    }
+}
+
+/// Creates the texture and view for an optional item texture
+fn create_optional_texture(
+    device: &ID3D11Device,
+    data: Option<TextureData>,
+    srgb: bool,
+) -> anyhow::Result<(Option<Texture>, Option<ShaderResourceView>)> {
+    let Some(data) = data else {
+        return Ok((None, None));
+    };
+
+    let mut texture = create_item_texture(device, &data, srgb)?;
+    let srv = ShaderResourceView::create_from_texture(device, texture.texture.cast_as_mut())?;
+    Ok((Some(texture), Some(srv)))
+}
+
+fn to_screen_space(vector: Vector2<f32>, screen_size: &Vector2<f32>) -> Vector2<f32> {
+    let relative_pos = vector.component_div(screen_size);
+
+    Vector2::new(2.0 * relative_pos.x - 1.0, 1.0 - 2.0 * relative_pos.y)
+}
+
+#[cfg(test)]
+mod test {
+    use nalgebra::Vector4;
+
+    use super::*;
+    use crate::{
+        clock::MockClock,
+        item::{ItemDefinitionBuilder, ItemMotion, ItemRotation},
+        reference,
+        texture_loader::GradientDirection,
+    };
+
+    /// Size of the render target in pixels
+    const SCREEN_SIZE: u32 = 128;
+    /// Size of the gradient texture, drawn at 2x scale so each texel
+    /// covers a single pixel
+    const TEXTURE_SIZE: u32 = 64;
+    /// Allowed difference per channel (0-255)
+    const TOLERANCE: f32 = 2.0;
+    /// Colors (RGBA) the gradients run between
+    const FROM: [u8; 4] = [255, 0, 0, 255];
+    const TO: [u8; 4] = [0, 0, 255, 255];
+
+    /// Headless renderer (WARP when there is no GPU) rendering into a
+    /// square target
+    fn headless_renderer() -> Renderer {
+        let config = RendererConfig {
+            screen_size: Vector2::new(SCREEN_SIZE, SCREEN_SIZE),
+            font_path: None,
+            target_fps: None,
+            idle_timeout: None,
+            ..Default::default()
+        };
+        Renderer::headless(config).unwrap()
+    }
+
+    /// Spawns the item and renders a frame
+    async fn render_item(renderer: &mut Renderer, definition: ItemDefinition) {
+        let item = QueuedItemDefinition::load_or_placeholder(ItemId::random(), definition).await;
+        renderer.item_queue().push(item);
+        renderer.tick().unwrap();
+    }
+
+    /// Gradient texture drawn unrotated at the center of the target
+    fn gradient_item(direction: GradientDirection) -> ItemDefinitionBuilder {
+        ItemDefinition::builder()
+            .linear_gradient(TEXTURE_SIZE, TEXTURE_SIZE, FROM, TO, direction)
+            .static_transform(ItemTransform {
+                scale: 2.0,
+                ..Default::default()
+            })
+    }
+
+    /// Color (RGBA, 0-255) of a pixel in a frame read back from the BGRA
+    /// render target
+    fn pixel(pixels: &[u8], x: u32, y: u32) -> Vector4<f32> {
+        let index = ((y * SCREEN_SIZE + x) * 4) as usize;
+        let [b, g, r, a] = pixels[index..index + 4] else {
+            unreachable!()
+        };
+        Vector4::new(r, g, b, a).map(|channel| channel as f32)
+    }
+
+    /// Bounds (min, max) of the item quad in clip space, the quad must not
+    /// be rotated
+    fn quad_bounds(data: &ItemDataBuffer) -> (Vector2<f32>, Vector2<f32>) {
+        let corners = reference::quad_corners(data, data.elapsed_time, 1.0);
+        let min = corners
+            .iter()
+            .fold(corners[0], |min, corner| min.inf(corner));
+        let max = corners
+            .iter()
+            .fold(corners[0], |max, corner| max.sup(corner));
+        (min, max)
+    }
+
+    /// Checks the pixels covered by a centered item drawn with `texture`
+    /// against the CPU reference of the item shaders
+    fn assert_matches_reference(pixels: &[u8], data: &ItemDataBuffer, texture: &TextureData) {
+        let (min, max) = quad_bounds(data);
+
+        let start = (SCREEN_SIZE - TEXTURE_SIZE) / 2;
+        let end = start + TEXTURE_SIZE;
+
+        for y in (start..end).step_by(7) {
+            for x in (start..end).step_by(7) {
+                let clip = Vector2::new(
+                    2.0 * (x as f32 + 0.5) / SCREEN_SIZE as f32 - 1.0,
+                    1.0 - 2.0 * (y as f32 + 0.5) / SCREEN_SIZE as f32,
+                );
+                let local = Vector2::new(
+                    (clip.x - min.x) / (max.x - min.x),
+                    (max.y - clip.y) / (max.y - min.y),
+                );
+
+                let expected =
+                    reference::sample_color(data, texture, data.elapsed_time, local) * 255.0;
+                let actual = pixel(pixels, x, y);
+                assert!(
+                    (actual - expected).amax() <= TOLERANCE,
+                    "pixel ({x}, {y}) is {actual:?}, expected {expected:?}"
+                );
+            }
+        }
+    }
+
+    /// Renders a vertical gradient at a fixed transform and checks the
+    /// read back pixels against the CPU reference of the item shaders
+    #[tokio::test]
+    async fn renders_gradient() {
+        let mut renderer = headless_renderer();
+        let definition = gradient_item(GradientDirection::Vertical).build().unwrap();
+        render_item(&mut renderer, definition).await;
+
+        let pixels = renderer.read_frame().unwrap();
+        let texture = TextureData::linear_gradient(
+            TEXTURE_SIZE,
+            TEXTURE_SIZE,
+            FROM,
+            TO,
+            GradientDirection::Vertical,
+        );
+        assert_matches_reference(&pixels, &renderer.items[0].item_data, &texture);
+
+        // Nothing is drawn just outside the quad
+        let start = (SCREEN_SIZE - TEXTURE_SIZE) / 2;
+        let end = start + TEXTURE_SIZE;
+        let center = SCREEN_SIZE / 2;
+        for (x, y) in [
+            (start - 1, center),
+            (end, center),
+            (center, start - 1),
+            (center, end),
+        ] {
+            assert_eq!(pixel(&pixels, x, y), Vector4::zeros(), "pixel ({x}, {y})");
+        }
+
+        renderer.shutdown();
+    }
+
+    /// Renders gradients with and without each flip and checks which end
+    /// of the gradient the top left of the item shows, the gradients only
+    /// read the same when the texture is upright
+    #[tokio::test]
+    async fn renders_flipped_textures() {
+        for direction in [GradientDirection::Horizontal, GradientDirection::Vertical] {
+            for flip in [false, true] {
+                let mut renderer = headless_renderer();
+                let builder = gradient_item(direction);
+                let builder = match direction {
+                    GradientDirection::Horizontal => builder.flip_u(flip),
+                    GradientDirection::Vertical => builder.flip_v(flip),
+                };
+                render_item(&mut renderer, builder.build().unwrap()).await;
+
+                let pixels = renderer.read_frame().unwrap();
+                let texture =
+                    TextureData::linear_gradient(TEXTURE_SIZE, TEXTURE_SIZE, FROM, TO, direction);
+                assert_matches_reference(&pixels, &renderer.items[0].item_data, &texture);
+
+                // Pixels line up with texels so the corner is the first
+                // (or when flipped last) texel of the gradient
+                let start = (SCREEN_SIZE - TEXTURE_SIZE) / 2;
+                let expected = Vector4::from(if flip { TO } else { FROM }).map(f32::from);
+                let actual = pixel(&pixels, start, start);
+                assert!(
+                    (actual - expected).amax() <= TOLERANCE,
+                    "{direction:?} gradient flipped {flip} starts with {actual:?}, expected {expected:?}"
+                );
+
+                renderer.shutdown();
+            }
+        }
+    }
+
+    /// Moves an item right and down by a quarter of a pixel each frame
+    /// with sub-pixel edges and checks the pixel its right edge crosses
+    /// fades in over several frames instead of turning on at once
+    #[tokio::test]
+    async fn subpixel_edges_move_smoothly() {
+        let mut renderer = headless_renderer();
+        let source = MockClock::default();
+        renderer.clock = SceneClock::with_source(source.clone());
+        renderer.set_subpixel_edges(true);
+
+        // Travels from the top left corner to the center of the target
+        // (64 pixels) over a second
+        let definition = ItemDefinition::builder()
+            .solid_color(32, 32, [255, 255, 255, 255])
+            .motion(ItemMotion::Arc { height: 0.0 })
+            .rotation(ItemRotation::Fixed(0.0))
+            .duration(1000.0)
+            .build()
+            .unwrap();
+        render_item(&mut renderer, definition).await;
+
+        // Halfway the item is fully within the target
+        source.advance(Duration::from_millis(500));
+        renderer.tick().unwrap();
+
+        // Right edge and vertical center of the item (pixels)
+        let edge = |renderer: &Renderer| {
+            let (min, max) = quad_bounds(&renderer.items[0].item_data);
+            let x = (max.x + 1.0) * 0.5 * SCREEN_SIZE as f32;
+            let y = (1.0 - (min.y + max.y) * 0.5) * 0.5 * SCREEN_SIZE as f32;
+            (x, y)
+        };
+
+        // First pixel whose center the edge hasn't reached yet
+        let (edge_x, center_y) = edge(&renderer);
+        let x = (edge_x + 0.5).floor() as u32;
+        let y = center_y as u32;
+
+        let mut coverage = Vec::new();
+        for _ in 0..6 {
+            source.advance(Duration::from_millis(4));
+            renderer.tick().unwrap();
+
+            // Pixels are covered by how far the edge is past their center
+            let (edge_x, _) = edge(&renderer);
+            let expected = (edge_x - (x as f32 + 0.5)).clamp(0.0, 1.0);
+
+            // White over the black clear color, red is the coverage
+            let pixels = renderer.read_frame().unwrap();
+            let actual = pixel(&pixels, x, y).x / 255.0;
+            assert!(
+                (actual - expected).abs() <= 0.05,
+                "pixel ({x}, {y}) coverage is {actual}, expected {expected}"
+            );
+            coverage.push(actual);
+        }
+
+        assert!(
+            coverage.iter().any(|&value| value > 0.1 && value < 0.9),
+            "edge pixel never partially covered: {coverage:?}"
+        );
+        assert!(
+            coverage.windows(2).all(|pair| pair[1] - pair[0] < 0.5),
+            "edge pixel jumped: {coverage:?}"
+        );
+
+        renderer.shutdown();
+    }
+}
@@ -0,0 +1,25 @@
+// Clears the scissor region of the render target, ClearRenderTargetView
+// ignores the scissor so partial clears draw a fullscreen triangle instead
+cbuffer ClearParams : register(b0)
+{
+    float4 clear_color;
+};
+
+struct PS_IN {
+    float4 pos : SV_POSITION;
+};
+
+// Fullscreen triangle generated from the vertex id, no vertex buffer is
+// bound for this pass
+PS_IN VSMain(uint id : SV_VertexID)
+{
+    PS_IN output;
+    float2 uv = float2((id << 1) & 2, id & 2);
+    output.pos = float4(uv * float2(2.0f, -2.0f) + float2(-1.0f, 1.0f), 0.0f, 1.0f);
+    return output;
+}
+
+float4 PSMain(PS_IN input) : SV_TARGET
+{
+    return clear_color;
+}
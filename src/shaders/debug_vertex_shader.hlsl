@@ -0,0 +1,17 @@
+struct VS_IN {
+    float2 pos : POSITION;
+    float4 color : COLOR;
+};
+
+struct PS_IN {
+    float4 pos : SV_POSITION;
+    float4 color : COLOR;
+};
+
+// Debug lines are provided already in screen space
+PS_IN VSMain(VS_IN input) {
+    PS_IN output;
+    output.pos = float4(input.pos, 0.0, 1.0);
+    output.color = input.color;
+    return output;
+}
@@ -0,0 +1,129 @@
+// Downsamples the supersampled scene into the output render target
+Texture2D scene : register(t0);
+
+cbuffer DownsampleParams : register(b0)
+{
+    // Scene pixels per output pixel along each axis
+    float2 scale;
+    // Whether the scene colors are premultiplied by alpha
+    uint premultiplied;
+    // Filter used to combine the scene texels
+    uint filter;
+};
+
+#define FILTER_BOX 0
+#define FILTER_LANCZOS 1
+
+// Lobes of the Lanczos kernel either side of the center
+#define LANCZOS_LOBES 2
+
+static const float PI = 3.14159265f;
+
+struct PS_IN {
+    float4 pos : SV_POSITION;
+};
+
+// Fullscreen triangle generated from the vertex id, no vertex buffer is
+// bound for this pass
+PS_IN VSMain(uint id : SV_VertexID)
+{
+    PS_IN output;
+    float2 uv = float2((id << 1) & 2, id & 2);
+    output.pos = float4(uv * float2(2.0f, -2.0f) + float2(-1.0f, 1.0f), 0.0f, 1.0f);
+    return output;
+}
+
+// Loads a scene texel premultiplied by alpha so transparent texels don't
+// darken the edges when combined
+float4 LoadPremultiplied(int2 texel)
+{
+    uint width, height;
+    scene.GetDimensions(width, height);
+
+    float4 color = scene.Load(int3(clamp(texel, 0, int2(width, height) - 1), 0));
+    if (premultiplied == 0) {
+        color.rgb *= color.a;
+    }
+
+    return color;
+}
+
+// Averages the scene texels covered by the output pixel weighted by the
+// area of each texel that is covered
+float4 BoxFilter(float2 pixel)
+{
+    float2 start = pixel * scale;
+    float2 end = start + scale;
+
+    float4 sum = 0.0f;
+    float total = 0.0f;
+    for (int y = (int)floor(start.y); y < (int)ceil(end.y); y++) {
+        float wy = min(end.y, y + 1.0f) - max(start.y, (float)y);
+
+        for (int x = (int)floor(start.x); x < (int)ceil(end.x); x++) {
+            float wx = min(end.x, x + 1.0f) - max(start.x, (float)x);
+            sum += LoadPremultiplied(int2(x, y)) * wx * wy;
+            total += wx * wy;
+        }
+    }
+
+    return sum / total;
+}
+
+float Sinc(float x)
+{
+    if (abs(x) < 1e-5f) {
+        return 1.0f;
+    }
+
+    return sin(PI * x) / (PI * x);
+}
+
+// Lanczos windowed sinc, `x` is the distance in output pixels
+float LanczosWeight(float x)
+{
+    return abs(x) < LANCZOS_LOBES ? Sinc(x) * Sinc(x / LANCZOS_LOBES) : 0.0f;
+}
+
+// Windowed sinc over the scene texels near the output pixel, sharper than
+// the box filter but reads (2 * lobes * scale)^2 texels per pixel
+float4 LanczosFilter(float2 pixel)
+{
+    float2 center = (pixel + 0.5f) * scale;
+    float2 radius = LANCZOS_LOBES * scale;
+    int2 first = (int2)floor(center - radius);
+    int2 last = (int2)ceil(center + radius);
+
+    float4 sum = 0.0f;
+    float total = 0.0f;
+    for (int y = first.y; y <= last.y; y++) {
+        float wy = LanczosWeight((y + 0.5f - center.y) / scale.y);
+
+        for (int x = first.x; x <= last.x; x++) {
+            float wx = LanczosWeight((x + 0.5f - center.x) / scale.x);
+            sum += LoadPremultiplied(int2(x, y)) * wx * wy;
+            total += wx * wy;
+        }
+    }
+
+    // Negative lobes can overshoot past the valid range
+    return saturate(sum / total);
+}
+
+float4 PSMain(PS_IN input) : SV_TARGET
+{
+    float2 pixel = floor(input.pos.xy);
+
+    float4 color;
+    if (filter == FILTER_LANCZOS) {
+        color = LanczosFilter(pixel);
+    } else {
+        color = BoxFilter(pixel);
+    }
+
+    if (premultiplied == 0 && color.a > 0.0f) {
+        color.rgb /= color.a;
+    }
+
+    return color;
+}
@@ -1,11 +1,276 @@
 Texture2D texture0 : register(t0);
+// Texture shown when the back face of the item is visible
+Texture2D texture1 : register(t1);
+// Tangent space normal map lighting the front face
+Texture2D texture2 : register(t2);
+// Mask shaping the item, the red channel scales the alpha
+Texture2D texture3 : register(t3);
+// Blurred alpha of the front texture for the drop shadow
+Texture2D texture4 : register(t4);
 SamplerState sampler0 : register(s0);
 
+#include "item_data.hlsli"
+
 struct PS_IN {
     float4 pos : SV_POSITION;
     float2 tex : TEXCOORD;
+    float2 local : TEXCOORD1;
 };
 
-float4 PSMain(PS_IN input) : SV_TARGET {
-    return texture0.Sample(sampler0, input.tex);
-}
\ No newline at end of file
+// Alpha of the texture softened by averaging neighboring texels
+float ShadowAlpha(Texture2D tex, float2 uv)
+{
+    float width, height;
+    tex.GetDimensions(width, height);
+    float2 texel = 1.5f / float2(width, height);
+
+    float alpha = tex.Sample(sampler0, uv).a;
+    alpha += tex.Sample(sampler0, uv + float2(texel.x, 0.0f)).a;
+    alpha += tex.Sample(sampler0, uv - float2(texel.x, 0.0f)).a;
+    alpha += tex.Sample(sampler0, uv + float2(0.0f, texel.y)).a;
+    alpha += tex.Sample(sampler0, uv - float2(0.0f, texel.y)).a;
+
+    return alpha / 5.0f;
+}
+
+// Size of a single texel in UV space, derived from the on screen size
+// of the sampled texture region
+float2 TexelSize()
+{
+    return uv_scale / (tx_size * screen_size);
+}
+
+// Draws the outline color behind the texture where neighboring texels
+// are opaque
+float4 ApplyOutline(Texture2D tex, float2 uv, float4 color)
+{
+    float2 offset = TexelSize() * outline_width;
+
+    float edge = 0.0f;
+    edge = max(edge, tex.Sample(sampler0, uv + float2(offset.x, 0.0f)).a);
+    edge = max(edge, tex.Sample(sampler0, uv - float2(offset.x, 0.0f)).a);
+    edge = max(edge, tex.Sample(sampler0, uv + float2(0.0f, offset.y)).a);
+    edge = max(edge, tex.Sample(sampler0, uv - float2(0.0f, offset.y)).a);
+    edge = max(edge, tex.Sample(sampler0, uv + offset * 0.7071f).a);
+    edge = max(edge, tex.Sample(sampler0, uv - offset * 0.7071f).a);
+    edge = max(edge, tex.Sample(sampler0, uv + float2(offset.x, -offset.y) * 0.7071f).a);
+    edge = max(edge, tex.Sample(sampler0, uv + float2(-offset.x, offset.y) * 0.7071f).a);
+
+    float outline_alpha = outline_color.a * edge;
+    float alpha = color.a + outline_alpha * (1.0f - color.a);
+    if (alpha <= 0.0f) {
+        return float4(0.0f, 0.0f, 0.0f, 0.0f);
+    }
+
+    float3 rgb = (color.rgb * color.a + outline_color.rgb * outline_alpha * (1.0f - color.a)) / alpha;
+    return float4(rgb, alpha);
+}
+
+// Snaps the texture coordinate to the center of its pixelation block,
+// blocks are relative to the sampled region so sprite sheet cells
+// pixelate evenly
+float2 PixelateUV(float2 uv)
+{
+    float2 block = uv_scale / pixelate_size;
+    return (floor(uv / block) + 0.5f) * block;
+}
+
+// Bicubic Catmull-Rom sample using 9 bilinear taps, the bilinear filter
+// combines the middle 2x2 texels of the 4x4 footprint into single taps
+float4 SampleCatmullRom(Texture2D tex, float2 uv)
+{
+    float width, height;
+    tex.GetDimensions(width, height);
+    float2 tex_size = float2(width, height);
+
+    float2 sample_pos = uv * tex_size;
+    float2 tex_pos1 = floor(sample_pos - 0.5f) + 0.5f;
+    float2 f = sample_pos - tex_pos1;
+
+    // Catmull-Rom weights for the 4 texels along each axis
+    float2 w0 = f * (-0.5f + f * (1.0f - 0.5f * f));
+    float2 w1 = 1.0f + f * f * (-2.5f + 1.5f * f);
+    float2 w2 = f * (0.5f + f * (2.0f - 1.5f * f));
+    float2 w3 = f * f * (-0.5f + 0.5f * f);
+
+    float2 w12 = w1 + w2;
+    float2 offset12 = w2 / w12;
+
+    float2 tex_pos0 = (tex_pos1 - 1.0f) / tex_size;
+    float2 tex_pos3 = (tex_pos1 + 2.0f) / tex_size;
+    float2 tex_pos12 = (tex_pos1 + offset12) / tex_size;
+
+    float4 result = 0.0f;
+    result += tex.SampleLevel(sampler0, float2(tex_pos0.x, tex_pos0.y), 0.0f) * w0.x * w0.y;
+    result += tex.SampleLevel(sampler0, float2(tex_pos12.x, tex_pos0.y), 0.0f) * w12.x * w0.y;
+    result += tex.SampleLevel(sampler0, float2(tex_pos3.x, tex_pos0.y), 0.0f) * w3.x * w0.y;
+
+    result += tex.SampleLevel(sampler0, float2(tex_pos0.x, tex_pos12.y), 0.0f) * w0.x * w12.y;
+    result += tex.SampleLevel(sampler0, float2(tex_pos12.x, tex_pos12.y), 0.0f) * w12.x * w12.y;
+    result += tex.SampleLevel(sampler0, float2(tex_pos3.x, tex_pos12.y), 0.0f) * w3.x * w12.y;
+
+    result += tex.SampleLevel(sampler0, float2(tex_pos0.x, tex_pos3.y), 0.0f) * w0.x * w3.y;
+    result += tex.SampleLevel(sampler0, float2(tex_pos12.x, tex_pos3.y), 0.0f) * w12.x * w3.y;
+    result += tex.SampleLevel(sampler0, float2(tex_pos3.x, tex_pos3.y), 0.0f) * w3.x * w3.y;
+
+    // Negative lobes can overshoot past the valid range
+    return saturate(result);
+}
+
+// Samples the item texture using the item's upscale filter, pixelated
+// items always use the sampler's point filtering
+float4 SampleItem(Texture2D tex, float2 uv)
+{
+    float4 color;
+    if (upscale_filter == UPSCALE_CATMULL_ROM && pixelate_size <= 0.0f) {
+        color = SampleCatmullRom(tex, uv);
+    } else {
+        color = tex.Sample(sampler0, uv);
+    }
+
+    // Filtering happened on premultiplied texels, return to straight alpha
+    // so the effects below work the same for every texture
+    if (premultiplied != 0 && color.a > 0.0f) {
+        color.rgb /= color.a;
+    }
+
+    return color;
+}
+
+// Direction light arrives from when lighting normal mapped items (top
+// left, towards the viewer)
+static const float3 LIGHT_DIRECTION = normalize(float3(-0.5f, 0.5f, 1.0f));
+// Minimum light applied to normal mapped items
+static const float LIGHT_AMBIENT = 0.35f;
+
+// Shades the color using the normal map
+float4 ApplyNormalMap(float2 uv, float4 color)
+{
+    float3 normal = normalize(texture2.Sample(sampler0, uv).xyz * 2.0f - 1.0f);
+    float diffuse = saturate(dot(normal, LIGHT_DIRECTION));
+    float light = LIGHT_AMBIENT + (1.0f - LIGHT_AMBIENT) * diffuse;
+    return float4(color.rgb * light, color.a);
+}
+
+// Rotates the hue of the color by the current hue phase
+float4 ApplyColorCycle(float4 color)
+{
+    // RGB to HSV
+    float4 k = float4(0.0f, -1.0f / 3.0f, 2.0f / 3.0f, -1.0f);
+    float4 p = lerp(float4(color.bg, k.wz), float4(color.gb, k.xy), step(color.b, color.g));
+    float4 q = lerp(float4(p.xyw, color.r), float4(color.r, p.yzx), step(p.x, color.r));
+    float d = q.x - min(q.w, q.y);
+    float3 hsv = float3(abs(q.z + (q.w - q.y) / (6.0f * d + 1e-10f)), d / (q.x + 1e-10f), q.x);
+
+    // HSV to RGB with the hue rotated
+    float hue = frac(hsv.x + hue_phase);
+    float3 rgb = saturate(abs(frac(hue + float3(1.0f, 2.0f / 3.0f, 1.0f / 3.0f)) * 6.0f - 3.0f) - 1.0f);
+
+    return float4(hsv.z * lerp(1.0f, rgb, hsv.y), color.a);
+}
+
+// Scales the alpha of the color by the mask
+float4 ApplyMask(float2 uv, float4 color)
+{
+    return float4(color.rgb, color.a * texture3.Sample(sampler0, uv).r);
+}
+
+// Signed distance (texture pixels) from the edge of the item shape,
+// negative inside the shape
+float ShapeDistance(float2 local)
+{
+    float2 half_size = tx_size * screen_size * 0.5f;
+    float2 p = (local - 0.5f) * half_size * 2.0f;
+
+    if (shape_mode == SHAPE_CIRCLE) {
+        return length(p) - min(half_size.x, half_size.y);
+    }
+
+    float radius = min(corner_radius, min(half_size.x, half_size.y));
+    float2 q = abs(p) - half_size + radius;
+    return length(max(q, 0.0f)) + min(max(q.x, q.y), 0.0f) - radius;
+}
+
+// Cuts the color to the item shape, anti-aliased over a screen pixel.
+// The outline is drawn as a border inside the shape edge
+float4 ApplyShape(float2 local, float4 color, bool border)
+{
+    float d = ShapeDistance(local);
+    float aa = max(fwidth(d), 0.0001f);
+
+    if (border) {
+        float edge = saturate(0.5f + (d + outline_width) / aa) * outline_color.a;
+        color = float4(lerp(color.rgb, outline_color.rgb, edge), max(color.a, edge));
+    }
+
+    return float4(color.rgb, color.a * saturate(0.5f - d / aa));
+}
+
+// Fraction of the outermost pixels covered by the item (0-1), `local` is
+// the position within the quad (0-1). Pixels fade as the edge crosses them
+// so edges glide between pixels instead of snapping a whole pixel at once
+float EdgeCoverage(float2 local)
+{
+    float2 pixels_to_edge = min(local, 1.0f - local) / max(fwidth(local), 1e-6f);
+    return saturate(min(pixels_to_edge.x, pixels_to_edge.y));
+}
+
+float4 PSMain(PS_IN input, bool is_front : SV_IsFrontFace) : SV_TARGET {
+    if (pixelate_size > 0.0f) {
+        input.tex = PixelateUV(input.tex);
+    }
+
+    float4 color;
+    if (shadow_pass != 0) {
+        float alpha;
+        if (shadow_blurred != 0 && is_front) {
+            alpha = texture4.Sample(sampler0, input.tex).a;
+        } else {
+            alpha = is_front
+                ? ShadowAlpha(texture0, input.tex)
+                : ShadowAlpha(texture1, input.tex);
+        }
+        color = float4(shadow_color.rgb, shadow_color.a * alpha);
+    } else if (!is_front) {
+        color = SampleItem(texture1, input.tex);
+        if (color_cycle_speed != 0.0f) {
+            color = ApplyColorCycle(color);
+        }
+        if (outline_enabled != 0 && shape_mode == SHAPE_RECTANGLE) {
+            color = ApplyOutline(texture1, input.tex, color);
+        }
+    } else {
+        color = SampleItem(texture0, input.tex);
+        if (color_cycle_speed != 0.0f) {
+            color = ApplyColorCycle(color);
+        }
+        if (normal_enabled != 0) {
+            color = ApplyNormalMap(input.tex, color);
+        }
+        if (outline_enabled != 0 && shape_mode == SHAPE_RECTANGLE) {
+            color = ApplyOutline(texture0, input.tex, color);
+        }
+    }
+
+    // Mask applies last so the shadow and outline follow its shape
+    if (mask_enabled != 0) {
+        color = ApplyMask(input.tex, color);
+    }
+    if (shape_mode != SHAPE_RECTANGLE) {
+        color = ApplyShape(input.local, color, outline_enabled != 0 && shadow_pass == 0);
+    }
+
+    // Cutout items are drawn without blending, the shadow keeps its soft
+    // edges as it is still blended
+    bool cutout = alpha_cutoff > 0.0f && shadow_pass == 0;
+    if (subpixel_edges != 0 && !cutout) {
+        color.a *= EdgeCoverage(input.local);
+    }
+
+    if (cutout) {
+        clip(color.a - alpha_cutoff);
+        color.a = 1.0f;
+    }
+
+    return premultiplied_output != 0 ? float4(color.rgb * color.a, color.a) : color;
+}
@@ -0,0 +1,14 @@
+// Per-frame globals shared by item and particle shaders, the layout must
+// match `GlobalsBuffer` in item.rs
+cbuffer GlobalsBuffer : register (b1) {
+    float2 screen_size;
+    float scene_time;
+    uint random_seed;
+    float screen_aspect;
+    uint premultiplied_output;
+    // Scene forces (pixels), see `SceneForces` in item.rs
+    float2 scene_gravity;
+    float2 scene_wind;
+    // Whether item edges are antialiased for sub-pixel movement
+    uint subpixel_edges;
+}
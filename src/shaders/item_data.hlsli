@@ -0,0 +1,71 @@
+// Constant buffers available to item shaders, the layout must match
+// `ItemDataBuffer` and `GlobalsBuffer` in item.rs
+cbuffer ItemDataBuffer : register (b0) {
+    float2 tx_size; 
+    float2 start_pos;
+    float2 end_pos;
+    float spin_speed;
+    float scale;
+    float duration;
+    float elapsed_time;
+    float arc_height;
+    uint motion_mode;
+    float motion_radius;
+    float angular_speed;
+    float2 uv_offset;
+    float2 uv_scale;
+    uint frame_count;
+    float frame_rate;
+    uint sheet_columns;
+    uint frame_looping;
+    float2 shadow_offset;
+    float4 shadow_color;
+    uint shadow_enabled;
+    uint shadow_pass;
+    float outline_width;
+    uint outline_enabled;
+    float4 outline_color;
+    uint flip_u;
+    uint flip_v;
+    float pixelate_size;
+    uint normal_enabled;
+    uint mask_enabled;
+    uint rotation_mode;
+    float rotation;
+    float stretch_intensity;
+    float stretch_max;
+    uint upscale_filter;
+    uint shadow_blurred;
+    uint premultiplied;
+    float spin_phase;
+    float motion_phase;
+    uint sprite_frame;
+    uint shape_mode;
+    float corner_radius;
+    float color_cycle_speed;
+    float hue_phase;
+    float alpha_cutoff;
+    float2 force_gravity;
+    float2 force_wind;
+}
+
+#include "globals.hlsli"
+
+// Motion modes, must match `MotionMode` in item.rs
+#define MOTION_ARC 0
+#define MOTION_SPIRAL 1
+#define MOTION_ORBIT 2
+
+// Upscale filters, must match `UpscaleFilter` in item.rs
+#define UPSCALE_BILINEAR 0
+#define UPSCALE_CATMULL_ROM 1
+
+// Shape modes, must match `ShapeMode` in item.rs
+#define SHAPE_RECTANGLE 0
+#define SHAPE_CIRCLE 1
+#define SHAPE_ROUNDED_RECT 2
+
+// Rotation modes, must match `RotationMode` in item.rs
+#define ROTATION_SPIN 0
+#define ROTATION_FIXED 1
+#define ROTATION_FACE_VELOCITY 2
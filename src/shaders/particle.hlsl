@@ -0,0 +1,137 @@
+// Particles simulated entirely in the vertex shader, each particle's path
+// is derived from its seed and the time since it was emitted
+#include "globals.hlsli"
+
+// The layout must match `EmitterParams` in emitter.rs
+cbuffer EmitterParams : register(b0)
+{
+    float4 color_a;
+    float4 color_b;
+    // Emitter position within the stage (pixels)
+    float2 origin;
+    float2 stage_size;
+    // Acceleration (pixels per second squared)
+    float2 gravity;
+    // Drift (pixels per second)
+    float2 wind;
+    // Seconds since the emitter started
+    float elapsed_time;
+    float lifetime;
+    float direction;
+    float spread;
+    float min_speed;
+    float max_speed;
+    float particle_size;
+    uint shape;
+    // Whether the scene forces replace `gravity` and `wind`
+    uint scene_forces;
+};
+
+struct Particle
+{
+    uint seed;
+    // Seconds after the emitter started the particle is emitted
+    float spawn_time;
+};
+
+StructuredBuffer<Particle> particles : register(t0);
+
+#define SHAPE_SQUARE 0
+#define SHAPE_CIRCLE 1
+
+static const float PI = 3.14159265f;
+
+// Fraction of the lifetime spent fading out
+static const float FADE_FRACTION = 0.25f;
+
+// Two triangles forming a quad centered on the particle
+static const float2 CORNERS[6] = {
+    float2(-0.5f, -0.5f),
+    float2(-0.5f, 0.5f),
+    float2(0.5f, 0.5f),
+    float2(-0.5f, -0.5f),
+    float2(0.5f, 0.5f),
+    float2(0.5f, -0.5f),
+};
+
+struct PS_IN {
+    float4 pos : SV_POSITION;
+    float4 color : COLOR;
+    // Position within the particle quad, -1 to 1
+    float2 local : TEXCOORD0;
+};
+
+// Random value between 0 and 1, a different `channel` gives an unrelated
+// value for the same seed
+float Random(uint seed, uint channel)
+{
+    uint hash = seed ^ (channel * 0x9E3779B9u);
+    hash ^= hash >> 16;
+    hash *= 0x7FEB352Du;
+    hash ^= hash >> 15;
+    hash *= 0x846CA68Bu;
+    hash ^= hash >> 16;
+    return hash / 4294967295.0f;
+}
+
+PS_IN VSMain(uint vertex_id : SV_VertexID, uint instance_id : SV_InstanceID)
+{
+    Particle particle = particles[instance_id];
+    float age = elapsed_time - particle.spawn_time;
+
+    PS_IN output;
+    output.local = CORNERS[vertex_id] * 2.0f;
+    output.color = float4(0.0f, 0.0f, 0.0f, 0.0f);
+
+    // Particles not yet emitted or already dead collapse into a point
+    // outside the viewport so they are culled
+    if (age < 0.0f || age > lifetime) {
+        output.pos = float4(-2.0f, -2.0f, 0.0f, 1.0f);
+        return output;
+    }
+
+    float angle = direction + (Random(particle.seed, 0) - 0.5f) * spread;
+    float speed = lerp(min_speed, max_speed, Random(particle.seed, 1));
+    float2 velocity = float2(cos(angle), sin(angle)) * speed;
+    float2 acceleration = scene_forces ? scene_gravity : gravity;
+    float2 drift = scene_forces ? scene_wind : wind;
+    float2 center = origin + (velocity + drift) * age + 0.5f * acceleration * age * age;
+
+    // Squares tumble like confetti, circles look the same at any angle
+    float2 corner = CORNERS[vertex_id] * particle_size;
+    if (shape == SHAPE_SQUARE) {
+        float spin = (Random(particle.seed, 2) - 0.5f) * 4.0f * PI * age;
+        float s = sin(spin);
+        float c = cos(spin);
+        corner = float2(corner.x * c - corner.y * s, corner.x * s + corner.y * c);
+    }
+
+    // Pixels to normalized device coordinates, y points down in pixels
+    float2 pixel = center + corner;
+    output.pos = float4(
+        pixel.x / stage_size.x * 2.0f - 1.0f,
+        1.0f - pixel.y / stage_size.y * 2.0f,
+        0.0f,
+        1.0f
+    );
+
+    float4 color = lerp(color_a, color_b, Random(particle.seed, 3));
+    color.a *= saturate((lifetime - age) / (lifetime * FADE_FRACTION));
+    output.color = color;
+
+    return output;
+}
+
+float4 PSMain(PS_IN input) : SV_TARGET
+{
+    float4 color = input.color;
+
+    if (shape == SHAPE_CIRCLE) {
+        // Soft edged dot
+        float distance = length(input.local);
+        color.a *= saturate(1.0f - distance * distance);
+    }
+
+    // Additive blending expects premultiplied colors
+    return float4(color.rgb * color.a, color.a);
+}
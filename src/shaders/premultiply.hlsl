@@ -0,0 +1,20 @@
+// Multiplies the color of a texture by its alpha so filtering doesn't
+// bleed the color of transparent texels into the edges
+Texture2D<float4> source : register(t0);
+RWTexture2D<unorm float4> output : register(u0);
+
+// Must match `PREMULTIPLY_GROUP_SIZE` in premultiply.rs
+#define GROUP_SIZE 8
+
+[numthreads(GROUP_SIZE, GROUP_SIZE, 1)]
+void CSMain(uint3 id : SV_DispatchThreadID)
+{
+    uint width, height;
+    source.GetDimensions(width, height);
+    if (id.x >= width || id.y >= height) {
+        return;
+    }
+
+    float4 color = source.Load(int3(id.xy, 0));
+    output[id.xy] = float4(color.rgb * color.a, color.a);
+}
@@ -0,0 +1,36 @@
+// Blurs the alpha of an item texture into a soft mask for the drop shadow,
+// the layout of `BlurParams` must match `ShadowBlurParams` in shadow.rs
+Texture2D<float4> source : register(t0);
+RWTexture2D<unorm float4> output : register(u0);
+
+cbuffer BlurParams : register (b0) {
+    uint2 size;
+    uint radius;
+}
+
+// Must match `SHADOW_BLUR_GROUP_SIZE` in shadow.rs
+#define GROUP_SIZE 8
+
+[numthreads(GROUP_SIZE, GROUP_SIZE, 1)]
+void CSMain(uint3 id : SV_DispatchThreadID)
+{
+    if (id.x >= size.x || id.y >= size.y) {
+        return;
+    }
+
+    // Box blur, texels outside the texture count as transparent so the
+    // shadow fades out towards the edges
+    int r = (int)radius;
+    float alpha = 0.0f;
+    for (int y = -r; y <= r; y++) {
+        for (int x = -r; x <= r; x++) {
+            int2 pos = int2(id.xy) + int2(x, y);
+            if (all(pos >= 0) && all(pos < int2(size))) {
+                alpha += source.Load(int3(pos, 0)).a;
+            }
+        }
+    }
+
+    float taps = (2 * r + 1) * (2 * r + 1);
+    output[id.xy] = float4(0.0f, 0.0f, 0.0f, alpha / taps);
+}
@@ -1,12 +1,4 @@
-cbuffer ItemDataBuffer : register (b0) {
-    float2 tx_size; 
-    float2 start_pos;
-    float2 end_pos;
-    float spin_speed;
-    float scale;
-    float duration;
-    float elapsed_time;
-}
+#include "item_data.hlsli"
 
 struct VS_IN {
     float2 pos : POSITION;
@@ -16,6 +8,8 @@ struct VS_IN {
 struct PS_IN {
     float4 pos : SV_POSITION;
     float2 tex : TEXCOORD;
+    // Position within the item quad (0-1), unaffected by sprite sheets
+    float2 local : TEXCOORD1;
 };
 
 // Interpolate between two positions using an Arc of the provided height
@@ -33,10 +27,149 @@ float2 ArcInterpolation(float2 start, float2 end, float t, float height)
 
 #define PI 3.14159265358979323846f
 
-float YawInterpolation(float spin_speed, float elapsed_time) {
-    // radians per millisecond
-    float rotationSpeed = 2.0f * PI / spin_speed; 
-    return rotationSpeed * elapsed_time;
+// Spiral inwards from the start position towards the end position
+float2 SpiralInterpolation(float2 start, float2 end, float t, float angle)
+{
+    float2 offset = start - end;
+    float s = sin(angle);
+    float c = cos(angle);
+    float2 rotated = float2(offset.x * c - offset.y * s, offset.x * s + offset.y * c);
+
+    // Radius shrinks to zero as the item reaches the end
+    return end + rotated * (1.0f - t);
+}
+
+// Orbit around the center at a fixed radius
+float2 OrbitPosition(float2 center, float radius, float angle)
+{
+    return center + float2(cos(angle), sin(angle)) * radius;
+}
+
+// Orbit or spiral angle at the provided elapsed time (ms), offset from
+// the wrapped phase so precision doesn't degrade on long lived items.
+// Must match `motion_angle` in item.rs
+float MotionAngle(float time)
+{
+    // angular speed is in radians per second
+    return motion_phase + angular_speed * (time - elapsed_time) * 0.001f;
+}
+
+// Distance the item forces have moved the item at the provided elapsed
+// time (ms). Must match `force_offset` in item.rs
+float2 ForceOffset(float time)
+{
+    float t = max(time, 0.0f) * 0.001f;
+    return force_gravity * (0.5f * t * t) + force_wind * t;
+}
+
+// Computes the item position at the provided elapsed time (ms) based
+// on the motion mode
+float2 MotionPosition(float time)
+{
+    float t = clamp(time / duration, 0.0f, 1.0f);
+    float angle = MotionAngle(time);
+
+    float2 position;
+    if (motion_mode == MOTION_SPIRAL) {
+        position = SpiralInterpolation(start_pos, end_pos, t, angle);
+    } else if (motion_mode == MOTION_ORBIT) {
+        position = OrbitPosition(end_pos, motion_radius, angle);
+    } else {
+        position = ArcInterpolation(start_pos, end_pos, t, arc_height);
+    }
+
+    return position + ForceOffset(time);
+}
+
+// Time window (ms) used to estimate the direction of travel, must match
+// `VELOCITY_SAMPLE_TIME` in item.rs
+#define VELOCITY_SAMPLE_TIME 1.0f
+
+// Time (ms) items stay squashed after landing, must match
+// `IMPACT_SQUASH_TIME` in item.rs
+#define IMPACT_SQUASH_TIME 150.0f
+
+// Velocity of the item at the provided elapsed time (ms) in aspect
+// corrected space (per ms)
+float2 ItemVelocity(float time)
+{
+    float2 velocity = (MotionPosition(time + VELOCITY_SAMPLE_TIME) - MotionPosition(time))
+        / VELOCITY_SAMPLE_TIME;
+    velocity.x *= screen_aspect;
+    return velocity;
+}
+
+// Time to sample the direction of travel at, kept within the lifetime so
+// items keep their heading once they stop
+float HeadingSampleTime()
+{
+    return max(min(elapsed_time, duration) - VELOCITY_SAMPLE_TIME, 0.0f);
+}
+
+// Angle pointing along the direction of travel
+float VelocityYaw()
+{
+    float2 velocity = ItemVelocity(HeadingSampleTime());
+    if (all(velocity == 0.0f)) {
+        return rotation;
+    }
+
+    return atan2(velocity.y, velocity.x) + rotation;
+}
+
+// Factor to stretch along the direction of travel, above 1 stretches and
+// below 1 squashes
+float StretchFactor(float2 heading)
+{
+    // Speed in screen heights per second, screen space spans 2 units
+    float speed = length(ItemVelocity(elapsed_time)) * 500.0f;
+    float stretch = 1.0f + stretch_intensity * speed;
+
+    // Squash briefly after landing based on the landing speed
+    float since_impact = elapsed_time - duration;
+    if (motion_mode != MOTION_ORBIT && since_impact >= 0.0f && since_impact < IMPACT_SQUASH_TIME) {
+        float impact_speed = length(heading) * 500.0f;
+        float squash = stretch_intensity * impact_speed * (1.0f - since_impact / IMPACT_SQUASH_TIME);
+        stretch = 1.0f / (1.0f + squash);
+    }
+
+    return clamp(stretch, 1.0f / stretch_max, stretch_max);
+}
+
+// Stretches the aspect corrected offset along the direction of travel
+// preserving the area of the item
+float2 ApplyStretch(float2 offset)
+{
+    if (stretch_intensity <= 0.0f) {
+        return offset;
+    }
+
+    float2 heading = ItemVelocity(HeadingSampleTime());
+    if (all(heading == 0.0f)) {
+        return offset;
+    }
+
+    float2 direction = normalize(heading);
+    float factor = StretchFactor(heading);
+    float along = dot(offset, direction);
+    float2 perpendicular = offset - direction * along;
+
+    return direction * along * factor + perpendicular / factor;
+}
+
+// Computes the current item rotation based on the rotation mode
+float ItemYaw()
+{
+    if (rotation_mode == ROTATION_FIXED) {
+        return rotation;
+    }
+
+    if (rotation_mode == ROTATION_FACE_VELOCITY) {
+        return VelocityYaw();
+    }
+
+    // Spin phase is wrapped on the CPU
+    return spin_phase;
 }
 
 // Apply yaw onto the provided input 
@@ -50,35 +183,64 @@ float2 ApplyYaw(float2 input, float yaw) {
     );
 }
 
+// Offset of the current sprite sheet frame, frames advance from the
+// starting cell over the lifetime of the item
+float2 SpriteFrameOffset()
+{
+    if (frame_count < 2) {
+        return uv_offset;
+    }
+
+    // Frame is computed on the CPU, looping is already applied
+    uint2 start_cell = (uint2)round(uv_offset / uv_scale);
+    uint cell = start_cell.y * sheet_columns + start_cell.x + sprite_frame;
+
+    return float2(cell % sheet_columns, cell / sheet_columns) * uv_scale;
+}
+
 PS_IN VSMain(VS_IN input) {
     PS_IN output;
     
-    float item_time = clamp(elapsed_time / duration, 0.0f, 1.0f);
-    float yaw = YawInterpolation(spin_speed, elapsed_time);
-
-    float2 inputPosition = input.pos;
-
-    // Apply yaw rotation to the input position
-    float2 rotatedInputPosition = ApplyYaw(inputPosition, yaw);
+    float yaw = ItemYaw();
 
-    // Interpolate the current position in the throw arc
-    float2 position = ArcInterpolation(
-        start_pos,
-        end_pos,
-        item_time,
-        0.5
-    );
+    // Interpolate the current position along the item motion
+    float2 position = MotionPosition(elapsed_time);
     
     // Adjust normalized texture scale by the item scale
     float2 size = tx_size * scale;
 
+    // Rotate in aspect corrected space so the quad doesn't squash as
+    // it spins on non-square render targets
+    float2 offset = input.pos * size;
+    offset.x *= screen_aspect;
+    offset = ApplyYaw(offset, yaw);
+    offset = ApplyStretch(offset);
+    offset.x /= screen_aspect;
+
     // Multiply positioning
-    float2 outputPosition = (rotatedInputPosition * size) + position;
+    float2 outputPosition = offset + position;
+
+    // Shadow is drawn behind the item at an offset
+    if (shadow_pass != 0) {
+        outputPosition += shadow_offset;
+    }
     
     output.pos = float4(outputPosition.xy, 0.0, 1.0);
    
     // output.pos =  float4(input.pos, 1.0);
-    output.tex = input.tex; 
+    // Mirror the texture before mapping into the sampled region so
+    // sprite sheet cells flip in place
+    float2 tex = input.tex;
+    if (flip_u != 0) {
+        tex.x = 1.0f - tex.x;
+    }
+    if (flip_v != 0) {
+        tex.y = 1.0f - tex.y;
+    }
+
+    // Sample the item region of the texture
+    output.tex = SpriteFrameOffset() + tex * uv_scale;
+    output.local = input.tex;
 
     return output;
 }
@@ -0,0 +1,82 @@
+use nalgebra::Vector2;
+use winapi::um::d3d11::{ID3D11Device, ID3D11DeviceContext};
+
+use crate::{
+    dx::{
+        buffer::ConstantBuffer,
+        shader::{ComputeShader, ShaderResourceView, UnorderedAccessView},
+        texture::Texture,
+    },
+    load_shader,
+};
+
+/// Thread group size of the blur shader, must match `GROUP_SIZE` in
+/// shadow_blur.hlsl
+const SHADOW_BLUR_GROUP_SIZE: u32 = 8;
+
+/// Largest blur radius in texels, the blur samples (2r + 1)^2 texels per
+/// output texel so large radii get expensive quickly
+pub const MAX_SHADOW_BLUR_RADIUS: u32 = 16;
+
+#[derive(Default)]
+#[repr(C, align(16))]
+struct ShadowBlurParams {
+    size: Vector2<u32>,
+    radius: u32,
+}
+
+/// Compute pass producing a blurred copy of an item texture's alpha, the
+/// copy is made once when the item spawns and sampled by the shadow pass
+pub struct ShadowBlur {
+    shader: ComputeShader,
+    params: ConstantBuffer<ShadowBlurParams>,
+}
+
+impl ShadowBlur {
+    pub fn create(device: &ID3D11Device) -> anyhow::Result<ShadowBlur> {
+        let blob = load_shader!("shadow_blur.hlsl", "cs_5_0", "CSMain")?;
+        let shader = ComputeShader::create(device, blob)?;
+        let params = ConstantBuffer::create_default(device)?;
+
+        Ok(ShadowBlur { shader, params })
+    }
+
+    /// Blurs the alpha of `source` (`size` texels) by `radius` texels,
+    /// returning the blurred texture and a view for sampling it
+    pub fn blur(
+        &mut self,
+        device: &ID3D11Device,
+        ctx: &ID3D11DeviceContext,
+        source: &mut ShaderResourceView,
+        size: Vector2<u32>,
+        radius: u32,
+    ) -> anyhow::Result<(Texture, ShaderResourceView)> {
+        let mut texture = Texture::create_unordered(device, size.x, size.y)?;
+        let mut uav =
+            UnorderedAccessView::create_from_texture(device, texture.texture.cast_as_mut())?;
+        let srv = ShaderResourceView::create_from_texture(device, texture.texture.cast_as_mut())?;
+
+        self.params.replace(
+            ctx,
+            &ShadowBlurParams {
+                size,
+                radius: radius.min(MAX_SHADOW_BLUR_RADIUS),
+            },
+        )?;
+
+        self.shader.set_shader(ctx);
+        source.bind_compute(ctx, 0);
+        uav.bind_compute(ctx, 0);
+        unsafe {
+            ctx.CSSetConstantBuffers(0, 1, &self.params.buffer.as_ptr());
+        }
+
+        ComputeShader::dispatch(ctx, size, SHADOW_BLUR_GROUP_SIZE);
+
+        // The output must be unbound before the pixel shader can sample it
+        UnorderedAccessView::unbind_compute(ctx, 0);
+        ShaderResourceView::unbind_compute(ctx, 0);
+
+        Ok((texture, srv))
+    }
+}
@@ -1,22 +1,120 @@
 use autocxx::prelude::*;
-use ffi::{spoutDX, spoutDirectX, spoutSenderNames};
-use std::{ffi::CString, pin::Pin};
+use ffi::{spoutDX, spoutDirectX, spoutFrameCount, spoutSenderNames};
+use std::{ffi::CString, pin::Pin, time::Duration};
 use winapi::{
-    shared::dxgiformat::DXGI_FORMAT,
-    um::d3d11::{ID3D11Device, ID3D11Texture2D},
+    shared::dxgiformat::{
+        DXGI_FORMAT, DXGI_FORMAT_B8G8R8A8_UNORM, DXGI_FORMAT_R10G10B10A2_UNORM,
+        DXGI_FORMAT_R16G16B16A16_FLOAT, DXGI_FORMAT_R16G16B16A16_UNORM,
+        DXGI_FORMAT_R32G32B32A32_FLOAT, DXGI_FORMAT_R8G8B8A8_UNORM,
+    },
+    um::d3d11::{ID3D11Device, ID3D11Texture2D, D3D11_TEXTURE2D_DESC},
 }; // use all the main autocxx functions
 
+use crate::com::ComPtr;
+
 include_cpp! {
     #include "Spout.h"
+    #include "SpoutFrame.h"
     safety!(unsafe)
     generate!("spoutDX")
     generate!("spoutSenderNames")
     generate!("spoutDirectX")
     generate!("spoutFrameCount")
+    generate!("SpoutDXFrame")
+}
+
+/// Maps a DXGI format to the equivalent Spout format, returns [None]
+/// for formats that Spout is unable to share
+fn spout_format(format: DXGI_FORMAT) -> Option<ffi::DXGI_FORMAT> {
+    Some(match format {
+        DXGI_FORMAT_B8G8R8A8_UNORM => ffi::DXGI_FORMAT::DXGI_FORMAT_B8G8R8A8_UNORM,
+        DXGI_FORMAT_R8G8B8A8_UNORM => ffi::DXGI_FORMAT::DXGI_FORMAT_R8G8B8A8_UNORM,
+        DXGI_FORMAT_R10G10B10A2_UNORM => ffi::DXGI_FORMAT::DXGI_FORMAT_R10G10B10A2_UNORM,
+        DXGI_FORMAT_R16G16B16A16_UNORM => ffi::DXGI_FORMAT::DXGI_FORMAT_R16G16B16A16_UNORM,
+        DXGI_FORMAT_R16G16B16A16_FLOAT => ffi::DXGI_FORMAT::DXGI_FORMAT_R16G16B16A16_FLOAT,
+        DXGI_FORMAT_R32G32B32A32_FLOAT => ffi::DXGI_FORMAT::DXGI_FORMAT_R32G32B32A32_FLOAT,
+        _ => return None,
+    })
+}
+
+/// Number of consecutive failed sends before the sender is considered
+/// disconnected (about a second at 30fps)
+const DISCONNECT_THRESHOLD: u32 = 30;
+
+/// Outcome of sending a frame through Spout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SendStatus {
+    /// The frame was shared
+    Sent,
+    /// Spout failed to share the frame, usually transient
+    Failed,
+    /// Sending has failed repeatedly, receivers are not getting frames
+    /// (e.g. the shared texture could not be created or the device was lost)
+    Disconnected,
 }
 
+/// Texture provided to [SpoutSender::send_texture] doesn't match what the
+/// sender negotiated with receivers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SendError {
+    /// The texture format differs from the format set using
+    /// [SpoutSender::set_sender_format]
+    FormatMismatch {
+        sender: DXGI_FORMAT,
+        texture: DXGI_FORMAT,
+    },
+    /// The texture size (width, height) differs from the size of the
+    /// sender receivers are reading, the render target is stale and must
+    /// be recreated at the sender size or the sender reset using
+    /// [SpoutSender::reset_sender]
+    SizeMismatch {
+        sender: (u32, u32),
+        texture: (u32, u32),
+    },
+}
+
+impl std::fmt::Display for SendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SendError::FormatMismatch { sender, texture } => write!(
+                f,
+                "texture format {texture} does not match sender format {sender}"
+            ),
+            SendError::SizeMismatch { sender, texture } => write!(
+                f,
+                "texture size {}x{} does not match sender size {}x{}",
+                texture.0, texture.1, sender.0, sender.1
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SendError {}
+
+/// Spout sender sharing the rendered frames with receivers (e.g. OBS)
+///
+/// Frame sync handshake: with frame sync enabled the sender calls
+/// [SpoutSender::set_frame_sync] after each [SpoutSender::send_texture] to
+/// signal a new frame is ready, receivers wait on the signal before
+/// receiving so they never read a frame twice or while it is being
+/// written. The handshake also works in reverse, a receiver signals after
+/// reading and the sender calls [SpoutSender::wait_frame_sync] before
+/// rendering to run at the receiver's rate.
+///
+/// With frame counting enabled each sent frame is numbered, receivers
+/// compare the number against the last frame they read to detect dropped
+/// or duplicate frames when the sender and receiver frame rates differ.
+/// Frame counting is a Spout setting shared by every Spout application
+/// (stored in the registry by SpoutSettings), a sender can only opt out
+/// for itself using [SpoutSender::disable_frame_count]
 pub struct SpoutSender {
     handle: UniquePtr<ffi::spoutDX>,
+    /// Format the sender was configured to share
+    format: DXGI_FORMAT,
+    /// Number of sends that have failed in a row
+    consecutive_failures: u32,
+    /// Name set by [SpoutSender::set_sender_name]
+    name: Option<String>,
 }
 
 impl SpoutSender {
@@ -26,47 +124,239 @@ impl SpoutSender {
             return Err(anyhow::anyhow!("Failed to get spout sender names handle"));
         }
 
-        Ok(Self { handle })
+        Ok(Self {
+            handle,
+            // Default format used by Spout when none is specified
+            format: DXGI_FORMAT_B8G8R8A8_UNORM,
+            consecutive_failures: 0,
+            name: None,
+        })
     }
 
+    /// Sets the name receivers find the sender by.
+    ///
+    /// Spout registers the sender under its name when the first frame is
+    /// sent, renaming afterwards releases the sender so the next
+    /// [SpoutSender::send_texture] registers it again under the new name.
+    /// The frame sync event is named after the sender so it is closed as
+    /// well, the next [SpoutSender::set_frame_sync] opens it under the new
+    /// name.
+    ///
+    /// Not yet verified against a live receiver: that the old name is
+    /// removed from the sender list (rather than left registered until the
+    /// process exits) relies on ReleaseSender unregistering it
     pub fn set_sender_name<N: AsRef<str>>(&mut self, name: N) -> anyhow::Result<()> {
-        let library = self.handle.as_mut().unwrap();
         let sender_name = CString::new(name.as_ref())?;
 
+        if self
+            .name
+            .as_ref()
+            .is_some_and(|current| current != name.as_ref())
+        {
+            if self.is_frame_sync_enabled() {
+                self.close_frame_sync();
+                self.enable_frame_sync(true);
+            }
+
+            self.reset_sender();
+        }
+
+        let library = self.handle.as_mut().unwrap();
         unsafe {
             spoutDX::SetSenderName(library, sender_name.as_ptr());
         }
 
+        self.name = Some(name.as_ref().to_string());
+
         Ok(())
     }
 
-    pub fn set_sender_format(&mut self) -> anyhow::Result<()> {
+    /// Sets the format of the shared texture, this must match the
+    /// format of the texture provided to [SpoutSender::send_texture]
+    pub fn set_sender_format(&mut self, format: DXGI_FORMAT) -> anyhow::Result<()> {
+        let spout_format = spout_format(format)
+            .ok_or_else(|| anyhow::anyhow!("sender format {format} is not supported by spout"))?;
+
         let library = self.handle.as_mut().unwrap();
 
         unsafe {
-            spoutDX::SetSenderFormat(library, ffi::DXGI_FORMAT::DXGI_FORMAT_R8G8B8A8_UNORM);
+            spoutDX::SetSenderFormat(library, spout_format);
         }
 
+        self.format = format;
+
         Ok(())
     }
     pub fn open_directx11(&mut self, device: *mut ID3D11Device) -> anyhow::Result<()> {
         let library = self.handle.as_mut().unwrap();
 
-        unsafe {
-            spoutDX::OpenDirectX11(library, device.cast());
+        let opened = unsafe { spoutDX::OpenDirectX11(library, device.cast()) };
+        if !opened {
+            anyhow::bail!("spout failed to open directx11 using the device");
         }
 
         Ok(())
     }
-    pub fn send_texture(&mut self, texture: *mut ID3D11Texture2D) -> anyhow::Result<()> {
+
+    /// Shares the texture with receivers, send failures are reported
+    /// through the returned [SendStatus] rather than as errors so the host
+    /// can keep rendering while showing the sender is not connected.
+    ///
+    /// Returns a [SendError] without sending when the texture no longer
+    /// matches the format or size receivers are reading
+    pub fn send_texture(
+        &mut self,
+        texture: &mut ComPtr<ID3D11Texture2D>,
+    ) -> Result<SendStatus, SendError> {
+        // Ensure the texture matches the format receivers are expecting
+        let mut desc: D3D11_TEXTURE2D_DESC = unsafe { std::mem::zeroed() };
+        unsafe { texture.GetDesc(&mut desc) };
+        if desc.Format != self.format {
+            return Err(SendError::FormatMismatch {
+                sender: self.format,
+                texture: desc.Format,
+            });
+        }
+
+        // The sender takes the size of the first texture sent (0 before)
+        let sender = self.sender_size();
+        let texture_size = (desc.Width, desc.Height);
+        if sender != (0, 0) && sender != texture_size {
+            return Err(SendError::SizeMismatch {
+                sender,
+                texture: texture_size,
+            });
+        }
+
+        let library = self.handle.as_mut().unwrap();
+
+        let sent = unsafe { spoutDX::SendTexture(library, texture.as_ptr().cast()) };
+        if sent {
+            self.consecutive_failures = 0;
+            return Ok(SendStatus::Sent);
+        }
+
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        if self.consecutive_failures >= DISCONNECT_THRESHOLD {
+            Ok(SendStatus::Disconnected)
+        } else {
+            Ok(SendStatus::Failed)
+        }
+    }
+
+    /// Size (width, height) of the shared texture receivers are reading,
+    /// (0, 0) until the first frame is sent
+    pub fn sender_size(&mut self) -> (u32, u32) {
+        let library = self.handle.as_mut().unwrap();
+        let width = spoutDX::GetWidth(library).0;
+
         let library = self.handle.as_mut().unwrap();
+        let height = spoutDX::GetHeight(library).0;
 
-        let value = unsafe {
-            spoutDX::SendTexture(library, texture.cast());
-        };
+        (width, height)
+    }
+
+    /// Releases the shared texture so the next [SpoutSender::send_texture]
+    /// creates the sender again at the size of the texture sent, used
+    /// after recreating the render target at a new size
+    pub fn reset_sender(&mut self) {
+        let library = self.handle.as_mut().unwrap();
+        spoutDX::ReleaseSender(library);
+        self.consecutive_failures = 0;
+    }
+
+    /// Releases the sender and closes DirectX, receivers will see the
+    /// sender disappear. The sender must not be used after this
+    pub fn release(&mut self) {
+        if self.is_frame_sync_enabled() {
+            self.close_frame_sync();
+        }
+
+        let library = self.handle.as_mut().unwrap();
+        spoutDX::ReleaseSender(library);
+
+        let library = self.handle.as_mut().unwrap();
+        spoutDX::CloseDirectX11(library);
+    }
+
+    /// Frame counter shared by the frame count and frame sync functions
+    fn frame(&mut self) -> Pin<&mut spoutFrameCount> {
+        let library = self.handle.as_mut().unwrap();
+        ffi::SpoutDXFrame(library)
+    }
+
+    /// Stops numbering frames sent by this sender, the global Spout frame
+    /// count setting is left untouched for other applications
+    pub fn disable_frame_count(&mut self) {
+        let library = self.handle.as_mut().unwrap();
+        spoutDX::DisableFrameCount(library);
+    }
+
+    /// Whether sent frames are being numbered, enabled when frame counting
+    /// is turned on in the Spout settings unless disabled for this sender
+    pub fn is_frame_count_enabled(&mut self) -> bool {
+        let library = self.handle.as_mut().unwrap();
+        spoutDX::IsFrameCountEnabled(library)
+    }
+
+    /// Number of the most recently sent frame, only counts while frame
+    /// counting is enabled
+    pub fn frame_number(&mut self) -> i64 {
+        let library = self.handle.as_mut().unwrap();
+        spoutDX::GetFrame(library).0 as i64
+    }
+
+    /// Rate frames are being sent at, measured by the frame counter
+    pub fn fps(&mut self) -> f64 {
+        let library = self.handle.as_mut().unwrap();
+        spoutDX::GetFps(library)
+    }
+
+    /// Enables frame sync signalling between the sender and receivers
+    pub fn enable_frame_sync(&mut self, enabled: bool) {
+        spoutFrameCount::EnableFrameSync(self.frame(), enabled);
+    }
+
+    /// Whether frame sync signalling is enabled
+    pub fn is_frame_sync_enabled(&mut self) -> bool {
+        spoutFrameCount::IsFrameSyncEnabled(self.frame())
+    }
+
+    /// Signals the named sender's frame sync event, the sender signals
+    /// after sending a frame to wake receivers waiting for it
+    pub fn set_frame_sync<N: AsRef<str>>(&mut self, name: N) -> anyhow::Result<()> {
+        let library = self.handle.as_mut().unwrap();
+        let sender_name = CString::new(name.as_ref())?;
+
+        unsafe {
+            spoutDX::SetFrameSync(library, sender_name.as_ptr());
+        }
 
         Ok(())
     }
+
+    /// Waits for the named sender's frame sync event to be signalled,
+    /// returns false if the timeout elapsed first
+    pub fn wait_frame_sync<N: AsRef<str>>(
+        &mut self,
+        name: N,
+        timeout: Duration,
+    ) -> anyhow::Result<bool> {
+        let library = self.handle.as_mut().unwrap();
+        let sender_name = CString::new(name.as_ref())?;
+        let timeout_ms = c_ulong(timeout.as_millis().min(u32::MAX as u128) as _);
+
+        let signalled =
+            unsafe { spoutDX::WaitFrameSync(library, sender_name.as_ptr(), timeout_ms) };
+
+        Ok(signalled)
+    }
+
+    /// Closes the frame sync event
+    pub fn close_frame_sync(&mut self) {
+        spoutFrameCount::CloseFrameSync(self.frame());
+    }
+
     pub fn hold_fps(&mut self, fps: c_int) -> anyhow::Result<()> {
         let library = self.handle.as_mut().unwrap();
 
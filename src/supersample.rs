@@ -0,0 +1,148 @@
+use nalgebra::Vector2;
+use winapi::{
+    shared::dxgiformat::DXGI_FORMAT,
+    um::{
+        d3d11::{ID3D11Device, ID3D11DeviceContext},
+        d3dcommon::D3D11_PRIMITIVE_TOPOLOGY_TRIANGLELIST,
+    },
+};
+
+use crate::{
+    dx::{
+        buffer::ConstantBuffer,
+        device::Viewport,
+        shader::{PixelShader, ShaderResourceView, VertexShader},
+        texture::RenderTargetTexture,
+    },
+    load_shader,
+};
+
+/// Largest supported render scale, the downsample pass reads up to
+/// (scale + 1)^2 texels per output pixel
+pub const MAX_RENDER_SCALE: f32 = 4.0;
+
+/// Supersample anti-aliasing, renders the scene at a multiple of the
+/// output size along each axis. Unlike MSAA this also smooths edges
+/// produced inside shaders such as shapes and outlines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ssaa {
+    /// Renders at 2N×2N, 4 samples per pixel
+    X2,
+    /// Renders at 4N×4N, 16 samples per pixel
+    X4,
+}
+
+impl Ssaa {
+    /// Scale of the scene along each axis
+    pub fn factor(&self) -> f32 {
+        match self {
+            Ssaa::X2 => 2.0,
+            Ssaa::X4 => 4.0,
+        }
+    }
+}
+
+/// Filter combining the scene texels into each output pixel
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum ResolveFilter {
+    /// Area weighted average of the covered texels, cheapest and never
+    /// rings
+    #[default]
+    Box = 0,
+    /// Lanczos windowed sinc, keeps small text sharper but reads many more
+    /// texels and can ring slightly around hard edges
+    Lanczos = 1,
+}
+
+#[repr(C, align(16))]
+struct DownsampleParams {
+    scale: Vector2<f32>,
+    premultiplied: u32,
+    filter: u32,
+}
+
+/// Scene render target larger than the output that is downsampled into
+/// the output each frame, gives sharper small details than rendering
+/// at the output size
+pub struct Supersample {
+    /// Render target the scene is drawn into
+    pub target: RenderTargetTexture,
+    srv: ShaderResourceView,
+    vertex: VertexShader,
+    pixel: PixelShader,
+    params: ConstantBuffer<DownsampleParams>,
+    /// Viewport covering the whole output render target
+    output_viewport: Viewport,
+}
+
+impl Supersample {
+    /// Creates a scene target of `scene_size` pixels that downsamples into
+    /// an output of `output_size` pixels. `premultiplied` is whether the
+    /// scene colors are premultiplied by alpha
+    pub fn create(
+        device: &ID3D11Device,
+        scene_size: Vector2<u32>,
+        output_size: Vector2<u32>,
+        format: DXGI_FORMAT,
+        premultiplied: bool,
+        filter: ResolveFilter,
+    ) -> anyhow::Result<Supersample> {
+        let mut target =
+            RenderTargetTexture::create_offscreen(device, scene_size.x, scene_size.y, format)?;
+        let srv = ShaderResourceView::create_from_texture(device, target.texture.cast_as_mut())?;
+
+        let vertex_blob = load_shader!("downsample.hlsl", "vs_5_0", "VSMain")?;
+        let pixel_blob = load_shader!("downsample.hlsl", "ps_5_0", "PSMain")?;
+        let vertex = VertexShader::create(device, vertex_blob)?;
+        let pixel = PixelShader::create(device, pixel_blob)?;
+
+        let params = ConstantBuffer::create(
+            device,
+            DownsampleParams {
+                scale: scene_size
+                    .cast::<f32>()
+                    .component_div(&output_size.cast::<f32>()),
+                premultiplied: premultiplied as u32,
+                filter: filter as u32,
+            },
+        )?;
+
+        Ok(Supersample {
+            target,
+            srv,
+            vertex,
+            pixel,
+            params,
+            output_viewport: Viewport::fullscreen(output_size.cast::<f32>()),
+        })
+    }
+
+    /// Downsamples the scene into `output`, the scene target must not be
+    /// bound as a render target. Leaves the output unbound, the pipeline
+    /// state must be rebound before drawing again
+    pub fn resolve(&mut self, ctx: &ID3D11DeviceContext, output: &mut RenderTargetTexture) {
+        output.bind(ctx);
+        self.output_viewport.bind(ctx);
+
+        self.vertex.set_shader(ctx);
+        self.pixel.set_shader(ctx);
+        self.srv.bind_slot(ctx, 0);
+
+        unsafe {
+            // The output is fully overwritten, blending and the clip region
+            // of the scene pass must not apply
+            ctx.OMSetBlendState(std::ptr::null_mut(), &[0.0; 4], 0xffffffff);
+            ctx.RSSetState(std::ptr::null_mut());
+
+            // Vertices come from the vertex id
+            ctx.IASetInputLayout(std::ptr::null_mut());
+            ctx.IASetPrimitiveTopology(D3D11_PRIMITIVE_TOPOLOGY_TRIANGLELIST);
+            ctx.PSSetConstantBuffers(0, 1, &self.params.buffer.as_ptr());
+            ctx.Draw(3, 0);
+        }
+
+        ShaderResourceView::unbind_slots(ctx, 0, 1);
+        output.unbind(ctx);
+    }
+}
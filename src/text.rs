@@ -0,0 +1,127 @@
+use std::{collections::HashMap, path::Path};
+
+use ab_glyph::{point, Font, FontArc, PxScale, ScaleFont};
+use image::ImageBuffer;
+use winapi::um::d3d11::ID3D11Device;
+
+use crate::{
+    dx::{shader::ShaderResourceView, texture::Texture},
+    texture_loader::TextureData,
+};
+
+/// Maximum number of label textures kept in the cache before it is cleared
+const MAX_CACHED_LABELS: usize = 128;
+
+/// Texture and view for a rendered label
+#[derive(Clone)]
+pub struct LabelTexture {
+    pub texture: Texture,
+    pub shader_resource_view: ShaderResourceView,
+}
+
+/// Rasterizes text into textures for item labels, caching the
+/// created textures by their text and size
+pub struct TextRenderer {
+    font: FontArc,
+    cache: HashMap<(String, u32), LabelTexture>,
+}
+
+impl TextRenderer {
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<TextRenderer> {
+        let data = std::fs::read(path)?;
+        Self::from_font_data(data)
+    }
+
+    pub fn from_font_data(data: Vec<u8>) -> anyhow::Result<TextRenderer> {
+        let font = FontArc::try_from_vec(data)?;
+        Ok(TextRenderer {
+            font,
+            cache: HashMap::new(),
+        })
+    }
+
+    /// Rasterizes a single line of white text with the provided pixel height
+    pub fn rasterize(&self, text: &str, size: f32) -> anyhow::Result<TextureData> {
+        let font = self.font.as_scaled(PxScale::from(size));
+
+        // Layout the glyphs along a single line
+        let mut glyphs = Vec::new();
+        let mut caret = point(0.0, font.ascent());
+        let mut previous = None;
+        for character in text.chars() {
+            let glyph_id = font.glyph_id(character);
+            if let Some(previous) = previous {
+                caret.x += font.kern(previous, glyph_id);
+            }
+            glyphs.push(glyph_id.with_scale_and_position(font.scale(), caret));
+            caret.x += font.h_advance(glyph_id);
+            previous = Some(glyph_id);
+        }
+
+        let width = caret.x.ceil() as u32;
+        let height = (font.ascent() - font.descent()).ceil() as u32;
+        if width == 0 || height == 0 {
+            anyhow::bail!("label text {text:?} produced an empty texture");
+        }
+
+        let mut buffer = ImageBuffer::from_pixel(width, height, image::Rgba([255, 255, 255, 0]));
+        for glyph in glyphs {
+            let Some(outlined) = font.outline_glyph(glyph) else {
+                continue;
+            };
+
+            let bounds = outlined.px_bounds();
+            outlined.draw(|x, y, coverage| {
+                let x = x as i32 + bounds.min.x as i32;
+                let y = y as i32 + bounds.min.y as i32;
+                if x < 0 || y < 0 || x >= width as i32 || y >= height as i32 {
+                    return;
+                }
+
+                let pixel = buffer.get_pixel_mut(x as u32, y as u32);
+                let alpha = (coverage.clamp(0.0, 1.0) * 255.0) as u8;
+                pixel.0[3] = pixel.0[3].max(alpha);
+            });
+        }
+
+        Ok(TextureData {
+            buffer,
+            width,
+            height,
+        })
+    }
+
+    /// Gets the texture for the provided label, creating and caching it
+    /// if it has not been created yet
+    pub fn get_or_create(
+        &mut self,
+        device: &ID3D11Device,
+        text: &str,
+        size: f32,
+    ) -> anyhow::Result<LabelTexture> {
+        let key = (text.to_string(), size.to_bits());
+        if let Some(label) = self.cache.get(&key) {
+            return Ok(label.clone());
+        }
+
+        let data = self.rasterize(text, size)?;
+        let mut texture = Texture::create_from_data(device, data.width, data.height, &data.buffer)?;
+        let shader_resource_view =
+            ShaderResourceView::create_from_texture(device, texture.texture.cast_as_mut())?;
+
+        let label = LabelTexture {
+            texture,
+            shader_resource_view,
+        };
+
+        // Textures are reference counted so clearing the cache only releases
+        // the textures that are no longer used by any items
+        if self.cache.len() >= MAX_CACHED_LABELS {
+            self.cache.clear();
+        }
+
+        self.cache.insert(key, label.clone());
+
+        Ok(label)
+    }
+}
@@ -1,26 +1,470 @@
-use image::{GenericImageView, ImageBuffer, Rgba};
+use std::{io::Cursor, sync::Arc};
+
+use image::{
+    imageops::FilterType, DynamicImage, GenericImageView, ImageBuffer, ImageDecoder, ImageFormat,
+    ImageReader, Rgb, Rgba,
+};
+use nalgebra::Vector2;
+use resvg::{tiny_skia, usvg};
 use tokio::{
-    sync::{mpsc, oneshot},
+    sync::{mpsc, oneshot, Semaphore},
     task::spawn_blocking,
 };
+use winapi::um::d3d11::D3D11_REQ_TEXTURE2D_U_OR_V_DIMENSION;
+
+/// Largest texture dimension supported by D3D11
+pub const MAX_TEXTURE_DIMENSION: u32 = D3D11_REQ_TEXTURE2D_U_OR_V_DIMENSION;
+
+/// Default maximum dimension, images larger than this are downscaled
+/// to avoid wasting VRAM on detail that is never visible
+pub const DEFAULT_MAX_TEXTURE_DIMENSION: u32 = 2048;
 
 pub struct TextureData {
     pub buffer: ImageBuffer<Rgba<u8>, Vec<u8>>,
     pub width: u32,
     pub height: u32,
 }
-pub async fn load_texture_data(data: Vec<u8>) -> anyhow::Result<TextureData> {
-    spawn_blocking(|| -> anyhow::Result<TextureData> {
-        let data = data;
-        let img = image::load_from_memory(&data)?;
-        let (width, height) = img.dimensions();
-        let img = img.to_rgba8(); // Convert to RGBA8 format
+
+/// Direction a linear gradient runs in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GradientDirection {
+    /// From the left edge to the right edge
+    Horizontal,
+    /// From the top edge to the bottom edge
+    Vertical,
+}
+
+impl TextureData {
+    /// Texture filled with a single color (RGBA)
+    pub fn solid_color(width: u32, height: u32, color: [u8; 4]) -> TextureData {
+        TextureData {
+            buffer: ImageBuffer::from_pixel(width, height, Rgba(color)),
+            width,
+            height,
+        }
+    }
+
+    /// Texture blending linearly between two colors (RGBA)
+    pub fn linear_gradient(
+        width: u32,
+        height: u32,
+        from: [u8; 4],
+        to: [u8; 4],
+        direction: GradientDirection,
+    ) -> TextureData {
+        let buffer = ImageBuffer::from_fn(width, height, |x, y| {
+            let (position, length) = match direction {
+                GradientDirection::Horizontal => (x, width),
+                GradientDirection::Vertical => (y, height),
+            };
+            let t = position as f32 / length.saturating_sub(1).max(1) as f32;
+
+            Rgba(std::array::from_fn(|channel| {
+                let from = from[channel] as f32;
+                let to = to[channel] as f32;
+                (from + (to - from) * t).round() as u8
+            }))
+        });
+
+        TextureData {
+            buffer,
+            width,
+            height,
+        }
+    }
+
+    /// Magenta and black checkerboard used in place of textures that
+    /// failed to load
+    pub fn placeholder() -> TextureData {
+        const SIZE: u32 = 64;
+        const CELL_SIZE: u32 = 8;
+
+        let buffer = ImageBuffer::from_fn(SIZE, SIZE, |x, y| {
+            if (x / CELL_SIZE + y / CELL_SIZE).is_multiple_of(2) {
+                Rgba([255, 0, 255, 255])
+            } else {
+                Rgba([0, 0, 0, 255])
+            }
+        });
+
+        TextureData {
+            buffer,
+            width: SIZE,
+            height: SIZE,
+        }
+    }
+}
+
+/// Loads texture data from the provided encoded image data. Images larger
+/// than `max_dimension` (Clamped to [MAX_TEXTURE_DIMENSION]) are downscaled
+/// to fit, the returned width and height are the effective size after scaling
+pub async fn load_texture_data(
+    data: Vec<u8>,
+    max_dimension: Option<u32>,
+) -> anyhow::Result<TextureData> {
+    spawn_blocking(move || decode_texture_data(&data, None, max_dimension, None)).await?
+}
+
+/// Loads multiple textures in parallel, at most `concurrency` images are
+/// decoded at once. Results are returned in the same order as the input
+pub async fn load_texture_data_batch(
+    data: Vec<Vec<u8>>,
+    max_dimension: Option<u32>,
+    concurrency: usize,
+) -> Vec<anyhow::Result<TextureData>> {
+    let semaphore = Arc::new(Semaphore::new(concurrency.max(1)));
+    let mut handles = Vec::with_capacity(data.len());
+
+    for data in data {
+        let semaphore = semaphore.clone();
+        handles.push(tokio::spawn(async move {
+            let _permit = semaphore.acquire_owned().await?;
+            spawn_blocking(move || decode_texture_data(&data, None, max_dimension, None)).await?
+        }));
+    }
+
+    let mut results = Vec::with_capacity(handles.len());
+    for handle in handles {
+        results.push(match handle.await {
+            Ok(result) => result,
+            Err(err) => Err(err.into()),
+        });
+    }
+
+    results
+}
+
+/// Loads texture data from encoded image data of a known format, skips
+/// sniffing the format from the data itself
+pub async fn load_texture_data_with_format(
+    data: Vec<u8>,
+    format: ImageFormat,
+    max_dimension: Option<u32>,
+) -> anyhow::Result<TextureData> {
+    spawn_blocking(move || decode_texture_data(&data, Some(format), max_dimension, None)).await?
+}
+
+/// Pixel layout of raw uncompressed image data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RawPixelFormat {
+    /// 8-bit RGBA, 4 bytes per pixel
+    Rgba8,
+    /// 8-bit BGRA, 4 bytes per pixel
+    Bgra8,
+    /// 8-bit RGB without alpha, 3 bytes per pixel
+    Rgb8,
+}
+
+impl RawPixelFormat {
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            RawPixelFormat::Rgba8 | RawPixelFormat::Bgra8 => 4,
+            RawPixelFormat::Rgb8 => 3,
+        }
+    }
+}
+
+/// Loads texture data from raw tightly packed pixel data without any
+/// container format, the data is converted to RGBA8
+pub async fn load_texture_data_raw(
+    data: Vec<u8>,
+    width: u32,
+    height: u32,
+    format: RawPixelFormat,
+) -> anyhow::Result<TextureData> {
+    spawn_blocking(move || -> anyhow::Result<TextureData> {
+        validate_dimensions(width, height)?;
+
+        let expected_len = width as usize * height as usize * format.bytes_per_pixel();
+        if data.len() != expected_len {
+            anyhow::bail!(
+                "raw {format:?} data for {width}x{height} image should be {expected_len} bytes (got {})",
+                data.len()
+            );
+        }
+
+        let buffer = match format {
+            RawPixelFormat::Rgba8 => ImageBuffer::from_raw(width, height, data),
+            RawPixelFormat::Bgra8 => {
+                let mut data = data;
+                data.chunks_exact_mut(4).for_each(|pixel| pixel.swap(0, 2));
+                ImageBuffer::from_raw(width, height, data)
+            }
+            RawPixelFormat::Rgb8 => ImageBuffer::<Rgb<u8>, _>::from_raw(width, height, data)
+                .map(|img| DynamicImage::ImageRgb8(img).to_rgba8()),
+        }
+        .ok_or_else(|| anyhow::anyhow!("raw pixel buffer size mismatch"))?;
+
+        Ok(TextureData {
+            buffer,
+            width,
+            height,
+        })
+    })
+    .await?
+}
+
+/// Color treated as transparent for images without an alpha channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColorKey {
+    /// Color to make transparent (RGB)
+    pub color: [u8; 3],
+    /// Maximum difference per channel for a pixel to still match
+    pub tolerance: u8,
+}
+
+impl ColorKey {
+    /// Whether the pixel matches the key color within the tolerance
+    pub fn matches(&self, pixel: &Rgba<u8>) -> bool {
+        pixel.0[..3]
+            .iter()
+            .zip(self.color)
+            .all(|(channel, key)| channel.abs_diff(key) <= self.tolerance)
+    }
+
+    /// Makes all matching pixels fully transparent
+    pub fn apply(&self, img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>) {
+        img.pixels_mut()
+            .filter(|pixel| self.matches(pixel))
+            .for_each(|pixel| *pixel = Rgba([0, 0, 0, 0]));
+    }
+}
+
+/// Loads texture data from the provided encoded image data treating pixels
+/// matching the color key as transparent, see [load_texture_data]
+pub async fn load_texture_data_color_keyed(
+    data: Vec<u8>,
+    max_dimension: Option<u32>,
+    color_key: ColorKey,
+) -> anyhow::Result<TextureData> {
+    spawn_blocking(move || decode_texture_data(&data, None, max_dimension, Some(color_key))).await?
+}
+
+/// Decodes the texture data, limiting the image to the max dimension
+fn decode_texture_data(
+    data: &[u8],
+    format: Option<ImageFormat>,
+    max_dimension: Option<u32>,
+    color_key: Option<ColorKey>,
+) -> anyhow::Result<TextureData> {
+    let mut img = decode_oriented(data, format)?;
+
+    // Key before downscaling so the key color isn't blended into the edges
+    if let Some(color_key) = color_key {
+        let mut rgba = img.to_rgba8();
+        color_key.apply(&mut rgba);
+        img = DynamicImage::ImageRgba8(rgba);
+    }
+
+    let img = limit_dimensions(img, max_dimension);
+    let (width, height) = img.dimensions();
+    validate_dimensions(width, height)?;
+    let img = img.to_rgba8(); // Convert to RGBA8 format
+
+    Ok(TextureData {
+        buffer: img,
+        width,
+        height,
+    })
+}
+
+/// Multiplies the color of every pixel by its alpha, items drawn from
+/// premultiplied textures don't get dark fringes where bilinear filtering
+/// blends opaque texels with the color of transparent ones
+pub fn premultiply_alpha(mut data: TextureData) -> TextureData {
+    for pixel in data.buffer.pixels_mut() {
+        let alpha = pixel.0[3] as u32;
+        for channel in &mut pixel.0[..3] {
+            *channel = ((*channel as u32 * alpha + 127) / 255) as u8;
+        }
+    }
+
+    data
+}
+
+/// Rejects images without any pixels, a zero sized texture can't be
+/// created and would give the item a zero (or NaN) size. Thin images
+/// (e.g. 1x1 or 1xN) are valid
+fn validate_dimensions(width: u32, height: u32) -> anyhow::Result<()> {
+    if width == 0 || height == 0 {
+        anyhow::bail!("image has no pixels ({width}x{height})");
+    }
+
+    Ok(())
+}
+
+/// Downscales the image if either dimension exceeds the maximum allowed
+/// dimension, preserving the aspect ratio of the image
+fn limit_dimensions(img: DynamicImage, max_dimension: Option<u32>) -> DynamicImage {
+    let (width, height) = img.dimensions();
+    match limited_size(width, height, max_dimension) {
+        Some((width, height)) => img.resize_exact(width, height, FilterType::Lanczos3),
+        None => img,
+    }
+}
+
+/// Size (width, height) an image must be downscaled to so neither side
+/// exceeds `max_dimension` while keeping its aspect ratio, [None] when the
+/// image already fits
+fn limited_size(width: u32, height: u32, max_dimension: Option<u32>) -> Option<(u32, u32)> {
+    let max_dimension = max_dimension
+        .unwrap_or(MAX_TEXTURE_DIMENSION)
+        .clamp(1, MAX_TEXTURE_DIMENSION);
+
+    if width <= max_dimension && height <= max_dimension {
+        return None;
+    }
+
+    // Very thin images (e.g. 1x100000) would scale their short side below
+    // a pixel, keep at least one
+    let scale = max_dimension as f64 / width.max(height) as f64;
+    let scaled = |size: u32| ((size as f64 * scale).round() as u32).clamp(1, max_dimension);
+
+    Some((scaled(width), scaled(height)))
+}
+
+/// Decodes the image data applying any rotation or flip from the
+/// embedded EXIF orientation so the image appears upright
+fn decode_oriented(data: &[u8], format: Option<ImageFormat>) -> anyhow::Result<DynamicImage> {
+    let reader = match format {
+        Some(format) => ImageReader::with_format(Cursor::new(data), format),
+        None => ImageReader::new(Cursor::new(data)).with_guessed_format()?,
+    };
+    let mut decoder = reader.into_decoder()?;
+    let orientation = decoder.orientation()?;
+
+    let mut img = DynamicImage::from_decoder(decoder)?;
+    img.apply_orientation(orientation);
+
+    Ok(img)
+}
+
+/// Loads texture data from SVG data, the SVG is rasterized to fit within
+/// the provided `target_size` (preserving its aspect ratio) so that it
+/// remains crisp when rendered at that size
+pub async fn load_texture_data_svg(
+    data: Vec<u8>,
+    target_size: Vector2<u32>,
+) -> anyhow::Result<TextureData> {
+    spawn_blocking(move || -> anyhow::Result<TextureData> {
+        let tree = usvg::Tree::from_data(&data, &usvg::Options::default())?;
+        let svg_size = tree.size();
+
+        // Scale to fit the target size without distorting the image
+        let target_size = target_size.map(|value| value.min(MAX_TEXTURE_DIMENSION));
+        let scale =
+            (target_size.x as f32 / svg_size.width()).min(target_size.y as f32 / svg_size.height());
+        let width = ((svg_size.width() * scale).round() as u32).max(1);
+        let height = ((svg_size.height() * scale).round() as u32).max(1);
+
+        let mut pixmap = tiny_skia::Pixmap::new(width, height)
+            .ok_or_else(|| anyhow::anyhow!("failed to allocate {width}x{height} svg pixmap"))?;
+        resvg::render(
+            &tree,
+            tiny_skia::Transform::from_scale(scale, scale),
+            &mut pixmap.as_mut(),
+        );
+
+        // Pixmap is premultiplied, convert back to straight alpha
+        let pixels: Vec<u8> = pixmap
+            .pixels()
+            .iter()
+            .flat_map(|pixel| {
+                let color = pixel.demultiply();
+                [color.red(), color.green(), color.blue(), color.alpha()]
+            })
+            .collect();
+
+        let buffer = ImageBuffer::from_raw(width, height, pixels)
+            .ok_or_else(|| anyhow::anyhow!("svg pixel buffer size mismatch"))?;
 
         Ok(TextureData {
-            buffer: img,
+            buffer,
             width,
             height,
         })
     })
     .await?
 }
+
+#[cfg(test)]
+mod test {
+    use image::RgbaImage;
+
+    use super::*;
+
+    #[test]
+    fn limits_oversized_images() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::new(32, 32));
+        let img = limit_dimensions(img, Some(16));
+        assert_eq!(img.dimensions(), (16, 16));
+
+        // The longest side is clamped, the aspect ratio is kept
+        let img = DynamicImage::ImageRgba8(RgbaImage::new(64, 32));
+        let img = limit_dimensions(img, Some(16));
+        assert_eq!(img.dimensions(), (16, 8));
+    }
+
+    #[test]
+    fn limits_large_image_sizes() {
+        assert_eq!(
+            limited_size(8000, 4000, Some(DEFAULT_MAX_TEXTURE_DIMENSION)),
+            Some((2048, 1024))
+        );
+
+        // Without a limit the device maximum applies
+        assert_eq!(
+            limited_size(20_000, 10_000, None),
+            Some((MAX_TEXTURE_DIMENSION, MAX_TEXTURE_DIMENSION / 2))
+        );
+    }
+
+    #[test]
+    fn limits_thin_images() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::new(1, 100_000));
+        let img = limit_dimensions(img, Some(DEFAULT_MAX_TEXTURE_DIMENSION));
+        assert_eq!(img.dimensions(), (1, 2048));
+    }
+
+    #[test]
+    fn keeps_images_within_limit() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::new(300, 200));
+        let img = limit_dimensions(img, Some(DEFAULT_MAX_TEXTURE_DIMENSION));
+        assert_eq!(img.dimensions(), (300, 200));
+    }
+
+    /// Encodes an image as PNG
+    fn encode_png(width: u32, height: u32) -> Vec<u8> {
+        let img = RgbaImage::from_pixel(width, height, Rgba([255, 0, 0, 255]));
+        let mut data = Cursor::new(Vec::new());
+        img.write_to(&mut data, ImageFormat::Png).unwrap();
+        data.into_inner()
+    }
+
+    #[test]
+    fn loads_thin_images() {
+        for (width, height) in [(1, 1), (1, 16), (16, 1)] {
+            let data = encode_png(width, height);
+            let texture = decode_texture_data(&data, None, None, None).unwrap();
+            assert_eq!((texture.width, texture.height), (width, height));
+            assert_eq!(texture.buffer.dimensions(), (width, height));
+        }
+    }
+
+    #[test]
+    fn loads_thin_images_past_limit() {
+        let data = encode_png(1, 100_000);
+        let texture =
+            decode_texture_data(&data, None, Some(DEFAULT_MAX_TEXTURE_DIMENSION), None).unwrap();
+        assert_eq!((texture.width, texture.height), (1, 2048));
+    }
+
+    #[tokio::test]
+    async fn rejects_empty_raw_data() {
+        for (width, height) in [(0, 0), (0, 4), (4, 0)] {
+            let result =
+                load_texture_data_raw(Vec::new(), width, height, RawPixelFormat::Rgba8).await;
+            assert!(result.is_err(), "{width}x{height} raw data was accepted");
+        }
+    }
+}